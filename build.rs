@@ -47,6 +47,13 @@ fn main() {
         }
     }
 
+    // I2S is a mode of the SPI peripherals, only available on the ones with I2S pins.
+    let has_i2s = METADATA.peripherals.iter().any(|p| {
+        p.registers.as_ref().is_some_and(|r| r.kind == "spi")
+            && p.pins.iter().any(|pin| pin.signal == "I2S_WS")
+    });
+    cfgs.set("i2s", has_i2s);
+
     for &(kind, versions) in ALL_PERIPHERAL_VERSIONS.iter() {
         cfgs.declare(kind);
         for &version in versions.iter() {
@@ -140,6 +147,36 @@ fn main() {
         singletons.push(format!("EXTI{}", pin_num));
     }
 
+    // One singleton per internal EXTI line, with the interrupt of the peripheral driving it.
+    // TODO: move to py32-metapac
+    let mut exti_internal_lines: Vec<(u8, &str)> = Vec::new();
+    let pvd_irq = METADATA.interrupts.iter().find(|irq| irq.name == "PVD");
+    if let Some(irq) = pvd_irq {
+        exti_internal_lines.push((16, irq.name));
+    }
+    // The `pwr::Pvd` driver needs the PVD interrupt.
+    cfgs.set("pvd", pvd_irq.is_some());
+    for (line, peri) in [
+        (17, "COMP1"),
+        (18, "COMP2"),
+        (19, "RTC"),
+        (29, "LPTIM"),
+        (29, "LPTIM1"),
+    ] {
+        let irq = METADATA
+            .peripherals
+            .iter()
+            .filter(|p| p.name == peri)
+            .flat_map(|p| p.interrupts)
+            .find(|irq| irq.signal == "GLOBAL");
+        if let Some(irq) = irq {
+            exti_internal_lines.push((line, irq.interrupt));
+        }
+    }
+    for (line, _) in &exti_internal_lines {
+        singletons.push(format!("EXTI{}", line));
+    }
+
     // One singleton per DMA channel
     for c in METADATA.dma_channels {
         singletons.push(c.name.to_string());
@@ -233,6 +270,10 @@ fn main() {
         Some("tim23") => "TIM23",
         Some("tim24") => "TIM24",
         Some("systick") => "",
+        Some("lptim") => ["LPTIM", "LPTIM1"]
+            .iter()
+            .find(|lptim| singletons.contains(&lptim.to_string()))
+            .expect("time-driver-lptim requested, but the chip doesn't have LPTIM or LPTIM1."),
         Some("any") => {
             // Order of TIM candidators:
             // 1. 2CH -> 2CH_CMP -> GP16 -> GP32 -> ADV
@@ -265,7 +306,7 @@ fn main() {
     }
     for tim in [
         "tim1", "tim2", "tim3", "tim4", "tim5", "tim8", "tim9", "tim12", "tim15", "tim20", "tim21",
-        "tim22", "tim23", "tim24",
+        "tim22", "tim23", "tim24", "lptim", "lptim1",
     ] {
         cfgs.declare(format!("time_driver_{}", tim));
     }
@@ -307,6 +348,18 @@ fn main() {
         );
     });
 
+    // ========
+    // Generate internal EXTI lines
+
+    for (line, irq) in &exti_internal_lines {
+        let name = format_ident!("EXTI{}", line);
+        let irq = format_ident!("{}", irq);
+        g.extend(quote! {
+            #[cfg(feature = "exti")]
+            impl_exti_internal_line!(#name, #line, #irq);
+        });
+    }
+
     // ========
     // Generate FLASH regions
     // let mut flash_regions = TokenStream::new();
@@ -575,6 +628,8 @@ fn main() {
     }
 
     let mut refcount_idxs = HashMap::new();
+    // Peripherals enabled once per driver instance, such as the two DAC channels.
+    let force_refcount = HashSet::from(["dac"]);
 
     for p in METADATA.peripherals {
         if !singletons.contains(&p.name.to_string()) {
@@ -623,10 +678,12 @@ fn main() {
             };
             let enable_offset_and_bit = get_offset_and_bit(en_reg);
 
-            let needs_refcount = *rcc_field_count
-                .get(&(en_reg.register, en_reg.field))
-                .unwrap()
-                > 1;
+            let ptype = p.registers.as_ref().map(|r| r.kind).unwrap_or("");
+            let needs_refcount = force_refcount.contains(ptype)
+                || *rcc_field_count
+                    .get(&(en_reg.register, en_reg.field))
+                    .unwrap()
+                    > 1;
             let refcount_idx = if needs_refcount {
                 let next_refcount_idx = refcount_idxs.len() as u8;
                 let refcount_idx = *refcount_idxs
@@ -642,13 +699,19 @@ fn main() {
                 PeripheralRccKernelClock::Clock(clock) => clock_gen.gen_clock(p.name, clock),
             };
 
-            // A refcount leak can result if the same field is shared by peripherals with different stop modes
-            // This condition should be checked in stm32-data
-            // let stop_mode = match rcc.stop_mode {
-            //     StopMode::Standby => quote! { crate::rcc::StopMode::Standby },
-            //     StopMode::Stop2 => quote! { crate::rcc::StopMode::Stop2 },
-            //     StopMode::Stop1 => quote! { crate::rcc::StopMode::Stop1 },
-            // };
+            // py32-data has no stop mode information yet. Only the peripherals clocked by
+            // LSI/LSE, or that keep their state in Stop mode, don't prevent it.
+            // GPIO and DMA are enabled at startup; a DMA transfer always belongs to a driver
+            // whose peripheral is enabled, which prevents Stop mode on its own.
+            let stop_mode = match p.registers.as_ref().map(|r| r.kind) {
+                Some(
+                    "rtc" | "iwdg" | "lptim" | "pwr" | "exti" | "dbgmcu" | "syscfg" | "flash"
+                    | "gpio" | "dma",
+                ) => {
+                    quote! { crate::rcc::StopMode::Standby }
+                }
+                _ => quote! { crate::rcc::StopMode::Stop1 },
+            };
 
             g.extend(quote! {
                 impl crate::rcc::SealedRccPeripheral for peripherals::#pname {
@@ -661,8 +724,8 @@ fn main() {
                             #reset_offset_and_bit,
                             #enable_offset_and_bit,
                             #refcount_idx,
-                            // #[cfg(feature = "low-power")]
-                            // #stop_mode,
+                            #[cfg(feature = "low-power")]
+                            #stop_mode,
                         )
                     };
                 }
@@ -850,13 +913,13 @@ fn main() {
         (("sai", "MCLK_A"), quote!(crate::sai::MclkPin<A>)),
         (("sai", "MCLK_B"), quote!(crate::sai::MclkPin<B>)),
         (("sai", "WS"), quote!(crate::sai::WsPin)),
-        // (("spi", "SCK"), quote!(crate::spi::SckPin)),
-        // (("spi", "MOSI"), quote!(crate::spi::MosiPin)),
-        // (("spi", "MISO"), quote!(crate::spi::MisoPin)),
-        // (("spi", "NSS"), quote!(crate::spi::CsPin)),
-        // (("spi", "I2S_MCK"), quote!(crate::spi::MckPin)),
-        // (("spi", "I2S_CK"), quote!(crate::spi::CkPin)),
-        // (("spi", "I2S_WS"), quote!(crate::spi::WsPin)),
+        (("spi", "SCK"), quote!(crate::spi::SckPin)),
+        (("spi", "MOSI"), quote!(crate::spi::MosiPin)),
+        (("spi", "MISO"), quote!(crate::spi::MisoPin)),
+        (("spi", "NSS"), quote!(crate::spi::CsPin)),
+        (("spi", "I2S_MCK"), quote!(crate::spi::MckPin)),
+        (("spi", "I2S_CK"), quote!(crate::spi::CkPin)),
+        (("spi", "I2S_WS"), quote!(crate::spi::WsPin)),
         (("i2c", "SDA"), quote!(crate::i2c::SdaPin)),
         (("i2c", "SCL"), quote!(crate::i2c::SclPin)),
         (("rcc", "MCO"), quote!(crate::rcc::McoPin)),
@@ -1068,6 +1131,7 @@ fn main() {
         (("hrtim", "CHE2"), quote!(crate::hrtim::ChannelEComplementaryPin)),
         (("hrtim", "CHF1"), quote!(crate::hrtim::ChannelFPin)),
         (("hrtim", "CHF2"), quote!(crate::hrtim::ChannelFComplementaryPin)),
+        (("comp", "OUT"), quote!(crate::comp::OutputPin)),
         (("lptim", "CH1"), quote!(crate::lptim::Channel1Pin)),
         (("lptim", "CH2"), quote!(crate::lptim::Channel2Pin)),
         (("lptim", "OUT"), quote!(crate::lptim::OutputPin)),
@@ -1240,6 +1304,22 @@ fn main() {
                     }
                 }
 
+                if regs.kind == "comp" {
+                    let peri = format_ident!("{}", p.name);
+                    let pin_name = format_ident!("{}", pin.pin);
+                    if let Some(ch) = pin.signal.strip_prefix("INP") {
+                        let ch: u8 = ch.parse().unwrap();
+                        g.extend(quote! {
+                            impl_comp_inp_pin!( #peri, #pin_name, #ch);
+                        })
+                    } else if let Some(ch) = pin.signal.strip_prefix("INM") {
+                        let ch: u8 = ch.parse().unwrap();
+                        g.extend(quote! {
+                            impl_comp_inm_pin!( #peri, #pin_name, #ch);
+                        })
+                    }
+                }
+
                 // DAC is special
                 if regs.kind == "dac" {
                     let peri = format_ident!("{}", p.name);
@@ -1272,8 +1352,8 @@ fn main() {
         // (("lpuart", "TX"), quote!(crate::usart::TxDma)),
         // (("sai", "A"), quote!(crate::sai::Dma<A>)),
         // (("sai", "B"), quote!(crate::sai::Dma<B>)),
-        // (("spi", "RX"), quote!(crate::spi::RxDma)),
-        // (("spi", "TX"), quote!(crate::spi::TxDma)),
+        (("spi", "RX"), quote!(crate::spi::RxDma)),
+        (("spi", "TX"), quote!(crate::spi::TxDma)),
         (("i2c", "RX"), quote!(crate::i2c::RxDma)),
         (("i2c", "TX"), quote!(crate::i2c::TxDma)),
         // (("dcmi", "DCMI"), quote!(crate::dcmi::FrameDma)),
@@ -1282,8 +1362,8 @@ fn main() {
         // (("sdmmc", "RX"), quote!(crate::sdmmc::SdmmcDma)),
         // (("quadspi", "QUADSPI"), quote!(crate::qspi::QuadDma)),
         // (("octospi", "OCTOSPI1"), quote!(crate::ospi::OctoDma)),
-        // (("dac", "CH1"), quote!(crate::dac::DacDma1)),
-        // (("dac", "CH2"), quote!(crate::dac::DacDma2)),
+        (("dac", "CH1"), quote!(crate::dac::DacDma1)),
+        (("dac", "CH2"), quote!(crate::dac::DacDma2)),
         // (("timer", "UP"), quote!(crate::timer::UpDma)),
         // (("hash", "IN"), quote!(crate::hash::Dma)),
         // (("cryp", "IN"), quote!(crate::cryp::DmaIn)),
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::adc::{Adc, AdcChannel, AveragedAdc, RingBufferedAdc, SampleTime};
+use py32_hal::peripherals::ADC1;
+use py32_hal::{adc, bind_interrupts};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut adc = Adc::new(p.ADC1, Irqs);
+    adc.set_sample_time(SampleTime::CYCLES239_5);
+    // Average 16 conversions, keeping 12-bit values.
+    adc.set_oversampling(16, 4);
+
+    let mut pin = p.PA1;
+    info!("PA1: {}", adc.read(&mut pin).await);
+
+    let mut dma_buf = [0u16; 2 * 64];
+    let mut adc: RingBufferedAdc<ADC1> = adc.into_ring_buffered(p.DMA1_CH1, &mut dma_buf);
+    adc.set_channels(&mut [p.PA0.degrade_adc(), pin.degrade_adc()]);
+
+    let mut adc: AveragedAdc<_, 2, 64> = AveragedAdc::new(adc);
+    loop {
+        match adc.read().await {
+            Ok([pa0, pa1]) => info!("PA0: {}, PA1: {}", pa0, pa1),
+            Err(e) => warn!("Error: {:?}", e),
+        }
+        Timer::after_millis(500).await;
+    }
+}
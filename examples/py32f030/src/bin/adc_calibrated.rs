@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::adc::{calibration, Adc, Calibration, SampleTime};
+use py32_hal::peripherals::ADC1;
+use py32_hal::{adc, bind_interrupts};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut adc = Adc::new(p.ADC1, Irqs);
+    // The temperature sensor needs a long sample time.
+    adc.set_sample_time(SampleTime::CYCLES239_5);
+    let mut pin = p.PA1;
+
+    let mut vrefint = adc.enable_vref();
+    let mut temperature = adc.enable_temperature();
+    let cal = Calibration::read();
+    info!("calibration: {}", cal);
+
+    loop {
+        let vdda_mv = calibration::vdda_mv(adc.read(&mut vrefint).await);
+        let pin_mv = calibration::to_millivolts(adc.read(&mut pin).await, vdda_mv);
+        let celsius = cal.temperature(adc.read(&mut temperature).await, vdda_mv);
+        info!("VDDA: {} mV, PA1: {} mV, {} °C", vdda_mv, pin_mv, celsius);
+        Timer::after_millis(500).await;
+    }
+}
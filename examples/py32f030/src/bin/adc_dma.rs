@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::adc::{Adc, AdcChannel, RingBufferedAdc, SampleTime};
+use py32_hal::peripherals::ADC1;
+use py32_hal::{adc, bind_interrupts};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut adc = Adc::new(p.ADC1, Irqs);
+    adc.set_sample_time(SampleTime::CYCLES71_5);
+
+    // Channels are converted in the order of their channel numbers: PA0, PA1, then Vrefint.
+    let mut channels = [
+        p.PA0.degrade_adc(),
+        p.PA1.degrade_adc(),
+        adc.enable_vref().degrade_adc(),
+    ];
+
+    // One-shot scans, two passes over the sequence.
+    for _ in 0..5 {
+        let mut readings = [0u16; 6];
+        adc.read_sequence(&mut p.DMA1_CH1, &mut channels, &mut readings)
+            .await;
+        info!("PA0, PA1, Vrefint: {}", readings);
+        Timer::after_millis(500).await;
+    }
+
+    // Continuous background sampling.
+    let mut dma_buf = [0u16; 3 * 40];
+    let mut adc: RingBufferedAdc<ADC1> = adc.into_ring_buffered(p.DMA1_CH1, &mut dma_buf);
+    adc.set_channels(&mut channels);
+
+    let mut measurements = [0u16; 3 * 20];
+    loop {
+        match adc.read(&mut measurements).await {
+            Ok(_) => {
+                info!("PA0, PA1, Vrefint: {}", measurements[..3]);
+                adc.teardown_adc();
+            }
+            Err(e) => {
+                warn!("Error: {:?}", e);
+            }
+        }
+        Timer::after_millis(500).await;
+    }
+}
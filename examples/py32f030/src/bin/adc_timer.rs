@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::adc::{Adc, AdcChannel, ExternalTrigger, RingBufferedAdc, SampleTime, TriggerEdge};
+use py32_hal::peripherals::ADC1;
+use py32_hal::time::Hertz;
+use py32_hal::timer::low_level::Timer;
+use py32_hal::{adc, bind_interrupts};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    // TIM3 update events trigger a scan of PA0 and PA1 every millisecond.
+    let tim = Timer::new(p.TIM3);
+    tim.set_trigger_output_frequency(Hertz::khz(1));
+
+    let mut adc = Adc::new(p.ADC1, Irqs);
+    adc.set_sample_time(SampleTime::CYCLES71_5);
+    adc.set_external_trigger(ExternalTrigger::Tim3Trgo, TriggerEdge::Rising);
+
+    let mut dma_buf = [0u16; 2 * 200];
+    let mut adc: RingBufferedAdc<ADC1> = adc.into_ring_buffered(p.DMA1_CH1, &mut dma_buf);
+    adc.set_channels(&mut [p.PA0.degrade_adc(), p.PA1.degrade_adc()]);
+    unwrap!(adc.start());
+    tim.start();
+
+    // 100 scans, one every 1 ms: one read every 100 ms.
+    let mut measurements = [0u16; 2 * 100];
+    loop {
+        match adc.read(&mut measurements).await {
+            Ok(_) => info!("PA0, PA1: {}", measurements[..2]),
+            Err(e) => warn!("Error: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::adc::{Adc, SampleTime, WatchdogChannels};
+use py32_hal::peripherals::ADC1;
+use py32_hal::{adc, bind_interrupts};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut adc = Adc::new(p.ADC1, Irqs);
+    adc.set_sample_time(SampleTime::CYCLES239_5);
+    let mut pin = p.PA1;
+
+    // Flag PA1 outside of about 1 V to 2.5 V, with VDDA = 3.3 V.
+    adc.set_watchdog(WatchdogChannels::single(&pin), 1241, 3103);
+
+    loop {
+        adc.wait_for_watchdog(&mut pin).await;
+        let v = adc.read(&mut pin).await;
+        info!("PA1 left the window: {}", v);
+        Timer::after_millis(500).await;
+    }
+}
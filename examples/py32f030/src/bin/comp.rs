@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::bind_interrupts;
+use py32_hal::comp::{self, Comparator, InvertingInput, VrefFraction};
+use py32_hal::peripherals::COMP1;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    ADC_COMP => comp::InterruptHandler<COMP1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = comp::Config::default();
+    config.hysteresis = true;
+    let mut comp = Comparator::new(
+        p.COMP1,
+        Irqs,
+        p.PA1,
+        InvertingInput::vrefint(VrefFraction::Half),
+        config,
+    );
+    info!("output: {}", comp.output_level());
+
+    loop {
+        comp.wait_for_rising_edge().await;
+        info!("PA1 rose above VREFINT / 2");
+        comp.wait_for_falling_edge().await;
+        info!("PA1 fell below VREFINT / 2");
+    }
+}
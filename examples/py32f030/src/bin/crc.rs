@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::crc::Crc;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut crc = Crc::new(p.CRC);
+
+    // CRC-32/MPEG-2 check value.
+    let result = crc.feed_bytes(b"123456789");
+    info!("CRC-32/MPEG-2: {:#010x}", result);
+    assert_eq!(result, 0x0376_E6E7);
+
+    // Bytes may arrive in pieces of any size.
+    crc.reset();
+    crc.feed_bytes(b"12345");
+    let result = crc.feed_bytes(b"6789");
+    assert_eq!(result, 0x0376_E6E7);
+
+    // Reflected CRC-32 (zlib) over little-endian words.
+    crc.reset();
+    let words = [u32::from_le_bytes(*b"1234"), u32::from_le_bytes(*b"5678")];
+    for word in words {
+        crc.feed_word(word.reverse_bits());
+    }
+    let result = !crc.read().reverse_bits();
+    info!("CRC-32: {:#010x}", result);
+    assert_eq!(result, 0x9AE0_DAAF);
+
+    info!("done");
+}
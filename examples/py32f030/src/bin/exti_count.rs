@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::exti::{Edge, ExtiInput};
+use py32_hal::gpio::Pull;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut input = ExtiInput::new(p.PB5, p.EXTI5, Pull::Up);
+
+    // Count the falling edges in the interrupt, none are lost while the task sleeps.
+    input.listen(Edge::Falling);
+
+    loop {
+        let n = input.wait_for_edges().await;
+        info!("{} pulses", n);
+        Timer::after_millis(500).await;
+    }
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::bind_interrupts;
+use py32_hal::exti::{Edge, ExtiLine};
+use py32_hal::pwr::{self, Pvd, PvdConfig, PvdLevel, StopConfig, WakeupOn};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PVD => pwr::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = PvdConfig::default();
+    config.level = PvdLevel::from_bits(3);
+    let pvd = Pvd::new(p.PWR, Irqs, config);
+
+    // The PVD line wakes the core from Stop mode as an event, no interrupt handler runs.
+    let mut line = ExtiLine::new_event(p.EXTI16, Edge::Both);
+
+    let mut stop_config = StopConfig::default();
+    stop_config.wakeup_on = WakeupOn::Event;
+
+    loop {
+        pwr::enter_stop(stop_config);
+        if line.is_pending() {
+            line.clear_pending();
+            info!(
+                "woken up by the PVD, VDD below threshold: {}",
+                pvd.is_below()
+            );
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+// I2C target at address 0x42 on PA3 (SCL) and PA2 (SDA), for a controller on another board.
+// Writing `[index, data..]` stores the data from `index`, a read returns the registers from
+// the last index written.
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::i2c::{CommandKind, I2c, SlaveAddrConfig};
+use py32_hal::time::Hertz;
+use py32_hal::{bind_interrupts, i2c, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+const ADDRESS: u8 = 0x42;
+
+bind_interrupts!(struct Irqs {
+    I2C1 => i2c::GlobalInterruptHandler<peripherals::I2C1>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello world!");
+    let p = py32_hal::init(Default::default());
+
+    let i2c = I2c::new(
+        p.I2C1,
+        p.PA3,
+        p.PA2,
+        Irqs,
+        p.DMA1_CH2,
+        p.DMA1_CH1,
+        Hertz(100_000),
+        Default::default(),
+    );
+    let mut target = i2c.into_slave(SlaveAddrConfig::basic(ADDRESS));
+
+    // A tiny register file: the controller writes a register index, then reads it back.
+    let mut registers = [0u8; 16];
+    let mut index = 0usize;
+
+    loop {
+        let command = match target.listen().await {
+            Ok(command) => command,
+            Err(e) => {
+                error!("I2c Error: {:?}", e);
+                continue;
+            }
+        };
+
+        match command.kind {
+            CommandKind::Write => {
+                let mut buf = [0u8; 17];
+                match target.respond_to_write(&mut buf).await {
+                    Ok(0) => {}
+                    Ok(len) => {
+                        index = buf[0] as usize % registers.len();
+                        for (i, byte) in buf[1..len].iter().enumerate() {
+                            registers[(index + i) % 16] = *byte;
+                        }
+                    }
+                    Err(e) => error!("I2c Error: {:?}", e),
+                }
+            }
+            CommandKind::Read | CommandKind::WriteRead => {
+                if let Err(e) = target.respond_to_read(&registers[index..]).await {
+                    error!("I2c Error: {:?}", e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::wdg::IndependentWatchdog;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    if IndependentWatchdog::<py32_hal::peripherals::IWDG>::caused_last_reset() {
+        info!("Reset by the watchdog");
+    }
+
+    let mut wdg = IndependentWatchdog::new(p.IWDG, 1_000_000);
+    wdg.unleash();
+
+    for _ in 0..10 {
+        Timer::after_millis(500).await;
+        info!("pet");
+        wdg.pet();
+    }
+
+    info!("Stop petting, the chip resets in 1 second");
+    loop {
+        Timer::after_millis(500).await;
+    }
+}
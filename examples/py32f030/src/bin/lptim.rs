@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::lptim::{self, Lptim, Prescaler};
+use py32_hal::rcc::{mux, LsConfig};
+use py32_hal::{bind_interrupts, peripherals};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    LPTIM1 => lptim::InterruptHandler<peripherals::LPTIM>;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut cfg: py32_hal::Config = Default::default();
+    cfg.rcc.ls = LsConfig::default_lsi();
+    cfg.rcc.mux.lptimsel = mux::Lptimsel::LSI;
+    let p = py32_hal::init(cfg);
+    info!("Hello World!");
+
+    let mut lptim_config = lptim::Config::default();
+    lptim_config.prescaler = Prescaler::Div32;
+    let mut lptim = Lptim::new(p.LPTIM, Irqs, lptim_config);
+    lptim.enable_wakeup(true);
+
+    // 32768 Hz / 32 = 1024 Hz, tick every 500 ms
+    lptim.start_continuous_us(500_000);
+    lptim.set_compare(256);
+
+    loop {
+        lptim.wait_compare().await;
+        info!("compare, counter = {}", lptim.counter());
+        lptim.wait_autoreload().await;
+        info!("autoreload");
+    }
+}
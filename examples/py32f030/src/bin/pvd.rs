@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::bind_interrupts;
+use py32_hal::pwr::{self, Crossing, Pvd, PvdConfig, PvdLevel};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    PVD => pwr::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut config = PvdConfig::default();
+    config.level = PvdLevel::from_bits(3);
+    let mut pvd = Pvd::new(p.PWR, Irqs, config);
+    info!("VDD below threshold: {}", pvd.is_below());
+
+    loop {
+        match pvd.wait_for_threshold_crossing().await {
+            Crossing::Below => warn!("VDD dropped below the threshold"),
+            Crossing::Above => info!("VDD is back above the threshold"),
+        }
+    }
+}
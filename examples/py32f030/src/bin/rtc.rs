@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::rcc::LsConfig;
+use py32_hal::rtc::{DateTime, Rtc};
+use py32_hal::{bind_interrupts, rtc};
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    RTC => rtc::InterruptHandler;
+});
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut cfg: py32_hal::Config = Default::default();
+    cfg.rcc.ls = LsConfig::default_lsi();
+    let p = py32_hal::init(cfg);
+    info!("Hello World!");
+
+    let mut rtc = Rtc::new(p.RTC, Irqs);
+
+    // The counter survives a system reset, only set it after a backup domain reset.
+    if rtc.now().year() == 1970 {
+        rtc.set_datetime(unwrap!(DateTime::from(2024, 10, 1, 12, 0, 0)));
+    }
+
+    for _ in 0..5 {
+        rtc.wait_second().await;
+        let now = rtc.now();
+        info!("{}:{}:{}", now.hour(), now.minute(), now.second());
+    }
+
+    let at = DateTime::from_timestamp(rtc.now().timestamp() + 10);
+    rtc.wait_alarm(at).await;
+    info!("alarm!");
+}
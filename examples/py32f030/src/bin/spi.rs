@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::spi::{Config, Spi};
+use py32_hal::time::Hertz;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    info!("Hello world!");
+    let p = py32_hal::init(Default::default());
+
+    let mut config = Config::default();
+    config.frequency = Hertz(1_000_000);
+
+    // Connect MOSI (PA7) to MISO (PA6) to loop the data back.
+    let mut spi = Spi::new(p.SPI1, p.PA5, p.PA7, p.PA6, p.DMA1_CH1, p.DMA1_CH2, config);
+
+    let write = [0x0Au8, 0x0B, 0x0C, 0x0D];
+    let mut read = [0u8; 4];
+
+    unwrap!(spi.blocking_transfer(&mut read, &write));
+    info!("blocking transfer: {:x}", read);
+
+    unwrap!(spi.transfer(&mut read, &write).await);
+    info!("dma transfer: {:x}", read);
+}
@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let (_p, info) = py32_hal::init_with_info(Default::default());
+
+    info!("reset cause: {}", info.reset_cause);
+    info!("uid: {:02x}", info.uid);
+    info!(
+        "configured flash size: {} KiB",
+        info.configured_flash_size / 1024
+    );
+    info!(
+        "dev id: {:#x}, rev id: {:#x}",
+        info.idcode.dev_id, info.idcode.rev_id
+    );
+}
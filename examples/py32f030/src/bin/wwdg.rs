@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use py32_hal::wdg::{self, WindowWatchdog};
+use py32_hal::bind_interrupts;
+use {defmt_rtt as _, panic_probe as _};
+
+bind_interrupts!(struct Irqs {
+    WWDG => wdg::InterruptHandler;
+});
+
+fn last_gasp() {
+    error!("WWDG is about to reset the chip");
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    if WindowWatchdog::caused_last_reset() {
+        info!("Reset by the window watchdog");
+    }
+
+    // Must be refreshed between 10 ms and 30 ms after the previous refresh.
+    let mut wdg = WindowWatchdog::new(p.WWDG, Irqs, 10_000, 30_000);
+    wdg.set_early_wakeup_callback(Some(last_gasp));
+    wdg.unleash();
+
+    for _ in 0..100 {
+        Timer::after_millis(20).await;
+        unwrap!(wdg.pet());
+    }
+
+    // Refreshing too early is reported instead of resetting the chip.
+    Timer::after_millis(20).await;
+    unwrap!(wdg.pet());
+    if let Err(e) = wdg.pet() {
+        warn!("{}", e);
+    }
+
+    info!("Stop petting");
+    wdg.wait_early_wakeup().await;
+    loop {}
+}
@@ -17,10 +17,15 @@ use defmt::*;
 use embassy_executor::Spawner;
 use embassy_time::Instant;
 use py32_hal::adc::{Adc, RingBufferedAdc, SampleTime, Sequence};
+use py32_hal::peripherals::ADC1;
 use py32_hal::rcc::{HsiFs, Pll, PllMul, PllSource, Sysclk};
-use py32_hal::Peripherals;
+use py32_hal::{adc, bind_interrupts, Peripherals};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let mut cfg: py32_hal::Config = Default::default();
@@ -41,7 +46,7 @@ async fn adc_task(mut p: Peripherals) {
     let adc_data: &mut [u16; ADC_BUF_SIZE] =
         singleton!(ADCDAT : [u16; ADC_BUF_SIZE] = [0u16; ADC_BUF_SIZE]).unwrap();
 
-    let adc = Adc::new_with_prediv(p.ADC1, py32_hal::adc::Prescaler::Div8);
+    let adc = Adc::new_with_prediv(p.ADC1, Irqs, py32_hal::adc::Prescaler::Div8);
     let mut vrefint = adc.enable_vrefint();
 
     let mut adc: RingBufferedAdc<py32_hal::peripherals::ADC1> =
@@ -6,9 +6,15 @@ use defmt::*;
 use embassy_executor::Spawner;
 use embassy_time::Timer;
 use py32_hal::adc::{Adc, SampleTime};
+use py32_hal::peripherals::ADC1;
 use py32_hal::rcc::{HsiFs, Pll, PllMul, PllSource, Sysclk};
+use py32_hal::{adc, bind_interrupts};
 use {defmt_rtt as _, panic_probe as _};
 
+bind_interrupts!(struct Irqs {
+    ADC_COMP => adc::InterruptHandler<ADC1>;
+});
+
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let mut cfg: py32_hal::Config = Default::default();
@@ -23,10 +29,10 @@ async fn main(_spawner: Spawner) {
     info!("Hello World!");
 
     // Automatically calculate the minimum prescaler using PCLK.
-    let mut adc = Adc::new(p.ADC1);
+    let mut adc = Adc::new(p.ADC1, Irqs);
 
     // use py32_hal::adc::Prescaler;
-    // let mut adc = Adc::new_with_prediv(p.ADC1, Prescaler::Div4);
+    // let mut adc = Adc::new_with_prediv(p.ADC1, Irqs, Prescaler::Div4);
 
     // The minimum conversion time for each resolution is as follows (sampling time + conversion time):
     // 12-bit: 3.5 + 12.5 = 16 ADCCLK cycles
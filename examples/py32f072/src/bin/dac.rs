@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_time::Timer as Delay;
+use py32_hal::dac::{Alignment, DacCh1, TriggerSel, Value};
+use py32_hal::pac::timer::vals::Mms;
+use py32_hal::time::Hertz;
+use py32_hal::timer::low_level::Timer;
+use {defmt_rtt as _, panic_halt as _};
+
+const SINE: [u16; 32] = [
+    2048, 2447, 2831, 3185, 3495, 3750, 3939, 4056, 4095, 4056, 3939, 3750, 3495, 3185, 2831, 2447,
+    2048, 1648, 1264, 910, 600, 345, 156, 39, 0, 39, 156, 345, 600, 910, 1264, 1648,
+];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let mut p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    // Blocking writes, the value is output immediately.
+    {
+        let mut dac = DacCh1::new_blocking(&mut p.DAC1, &mut p.PA4);
+        for v in (0..4096).step_by(512) {
+            dac.set(Value::Bit12Right(v));
+            info!("output: {}", dac.read());
+            Delay::after_millis(100).await;
+        }
+    }
+
+    // TIM6 update events trigger the DAC at 32 kHz, giving a 1 kHz sine wave.
+    let tim = Timer::new(p.TIM6);
+    tim.set_frequency(Hertz::khz(32));
+    tim.regs_basic().cr2().modify(|w| w.set_mms(Mms::UPDATE));
+    tim.start();
+
+    let mut dac = DacCh1::new(p.DAC1, p.DMA1_CH3, p.PA4);
+    dac.set_trigger(TriggerSel::Tim6);
+    dac.set_triggering(true);
+
+    let mut dma_buf = [0u16; 128];
+    let mut dac = dac.into_ring_buffered(&mut dma_buf, Alignment::Right);
+    unwrap!(dac.write_immediate(&SINE));
+    dac.start();
+
+    loop {
+        if let Err(e) = dac.write_exact(&SINE).await {
+            warn!("underrun: {}", e);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+#![feature(impl_trait_in_assoc_type)]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use py32_hal::i2s::{Config, I2S};
+use py32_hal::time::Hertz;
+use {defmt_rtt as _, panic_halt as _};
+
+// One period of a 1 kHz square wave at 16 kHz, interleaved left/right samples.
+const SQUARE: [u16; 32] = [
+    0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000, 0x2000,
+    0x2000, 0x2000, 0x2000, 0x2000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000,
+    0xE000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000, 0xE000,
+];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = py32_hal::init(Default::default());
+    info!("Hello World!");
+
+    let mut dma_buf = [0u16; 256];
+    let mut i2s = I2S::new_txonly_nomck(
+        p.SPI1,
+        p.PA7,
+        p.PA4,
+        p.PA5,
+        p.DMA1_CH1,
+        &mut dma_buf,
+        Hertz::khz(16),
+        Config::default(),
+    );
+    info!(
+        "audio frequency: {} Hz, error: {} ppm",
+        i2s.audio_frequency().0,
+        i2s.audio_frequency_error_ppm()
+    );
+
+    unwrap!(i2s.write_immediate(&SQUARE));
+    i2s.start();
+
+    loop {
+        if let Err(e) = i2s.write(&SQUARE).await {
+            warn!("i2s error: {}", e);
+        }
+    }
+}
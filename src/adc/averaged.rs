@@ -0,0 +1,86 @@
+use crate::adc::{Instance, OverrunError, Oversampling, RingBufferedAdc};
+
+/// Averages the measurements of a [`RingBufferedAdc`], producing one value per channel.
+///
+/// `C` is the number of channels in the sequence and `N` the length of the reads from the
+/// ring buffer, half the length of its DMA buffer. Each value is the sum of `ratio` measurements
+/// of its channel, shifted right by `shift` bits, as set with
+/// [`Adc::set_oversampling`](crate::adc::Adc::set_oversampling) before `into_ring_buffered`.
+///
+/// Example
+/// ```rust,ignore
+/// adc.set_oversampling(16, 4);
+/// let adc = adc.into_ring_buffered(p.DMA1_CH1, &mut dma_buf);
+/// // Select 3 channels...
+/// let mut adc: AveragedAdc<_, 3, 60> = AveragedAdc::new(adc);
+/// let [a, b, c] = adc.read().await?;
+/// ```
+pub struct AveragedAdc<'d, T: Instance, const C: usize, const N: usize> {
+    adc: RingBufferedAdc<'d, T>,
+    oversampling: Oversampling,
+    buf: [u16; N],
+    buf_pos: usize,
+    channel: usize,
+    count: u16,
+    sums: [u32; C],
+}
+
+impl<'d, T: Instance, const C: usize, const N: usize> AveragedAdc<'d, T, C, N> {
+    /// Wraps a ring buffered ADC converting a sequence of `C` channels.
+    pub fn new(adc: RingBufferedAdc<'d, T>) -> Self {
+        assert!(C > 0, "Channel sequence is empty");
+
+        let oversampling = adc.oversampling;
+        Self {
+            adc,
+            oversampling,
+            buf: [0; N],
+            buf_pos: N,
+            channel: 0,
+            count: 0,
+            sums: [0; C],
+        }
+    }
+
+    /// Reads the next averaged value of each channel, in the order of the measurements in the
+    /// ring buffer.
+    ///
+    /// After an overrun, the accumulated measurements are dropped and the next call restarts
+    /// the ADC, see [`RingBufferedAdc::read`].
+    pub async fn read(&mut self) -> Result<[u16; C], OverrunError> {
+        loop {
+            while self.buf_pos < N {
+                self.sums[self.channel] += self.buf[self.buf_pos] as u32;
+                self.buf_pos += 1;
+                self.channel += 1;
+
+                if self.channel == C {
+                    self.channel = 0;
+                    self.count += 1;
+
+                    if self.count == self.oversampling.ratio {
+                        self.count = 0;
+                        let values = self.sums.map(|sum| self.oversampling.apply(sum));
+                        self.sums = [0; C];
+                        return Ok(values);
+                    }
+                }
+            }
+
+            if let Err(e) = self.adc.read(&mut self.buf).await {
+                // The ADC restarts at the first channel of the sequence.
+                self.buf_pos = N;
+                self.channel = 0;
+                self.count = 0;
+                self.sums = [0; C];
+                return Err(e);
+            }
+            self.buf_pos = 0;
+        }
+    }
+
+    /// Returns the wrapped ring buffered ADC.
+    pub fn into_inner(self) -> RingBufferedAdc<'d, T> {
+        self.adc
+    }
+}
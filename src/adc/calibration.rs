@@ -0,0 +1,135 @@
+//! Conversions of raw readings to physical units, using the factory calibration.
+
+/// Voltage of the internal reference, in millivolts.
+///
+/// The PY32 parts don't store a calibrated reading of the internal reference, the nominal
+/// voltage is used.
+pub const VREFINT_MV: u32 = 1200;
+/// VDDA during the factory calibration of the temperature sensor, in millivolts.
+pub const TS_CAL_VDDA_MV: u32 = 3300;
+/// Temperature of the first calibration point, in degrees Celsius.
+pub const TS_CAL1_TEMP: i32 = 30;
+/// Temperature of the second calibration point, in degrees Celsius.
+pub const TS_CAL2_TEMP: i32 = 85;
+
+const MAX_COUNT: u32 = 4095;
+
+// Offsets of the temperature sensor calibration in the config bytes.
+// TODO: move to py32-metapac
+cfg_if::cfg_if! {
+    if #[cfg(py32f072)] {
+        const TS_CAL1_OFFSET: usize = 0x28;
+        const TS_CAL2_OFFSET: usize = 0x30;
+    } else {
+        const TS_CAL1_OFFSET: usize = 0x14;
+        const TS_CAL2_OFFSET: usize = 0x18;
+    }
+}
+
+/// Factory calibration of the temperature sensor.
+///
+/// All readings are 12-bit right-aligned values.
+///
+/// Example
+/// ```rust,ignore
+/// let calibration = Calibration::read();
+/// let vdda_mv = calibration::vdda_mv(adc.read(&mut vrefint).await);
+/// let pin_mv = calibration::to_millivolts(adc.read(&mut pin).await, vdda_mv);
+/// let celsius = calibration.temperature(adc.read(&mut temperature).await, vdda_mv);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    /// Temperature sensor reading at [`TS_CAL1_TEMP`], with VDDA at [`TS_CAL_VDDA_MV`].
+    pub ts_cal1: u16,
+    /// Temperature sensor reading at [`TS_CAL2_TEMP`], with VDDA at [`TS_CAL_VDDA_MV`].
+    pub ts_cal2: u16,
+}
+
+impl Calibration {
+    /// Reads the calibration from the config bytes.
+    pub fn read() -> Self {
+        let base = crate::pac::CONFIGBYTES.as_ptr() as *const u8;
+        // Safety: the config bytes are always readable.
+        unsafe {
+            Self {
+                ts_cal1: core::ptr::read_volatile(base.add(TS_CAL1_OFFSET) as *const u16),
+                ts_cal2: core::ptr::read_volatile(base.add(TS_CAL2_OFFSET) as *const u16),
+            }
+        }
+    }
+
+    /// Converts a temperature sensor reading to degrees Celsius.
+    ///
+    /// `vdda_mv` is the supply voltage during the reading, see [`vdda_mv`].
+    pub fn temperature(&self, sample: u16, vdda_mv: u32) -> f32 {
+        // Scale the reading to the VDDA of the calibration.
+        let sample = sample as f32 * vdda_mv as f32 / TS_CAL_VDDA_MV as f32;
+        let slope =
+            (TS_CAL2_TEMP - TS_CAL1_TEMP) as f32 / (self.ts_cal2 as f32 - self.ts_cal1 as f32);
+        TS_CAL1_TEMP as f32 + (sample - self.ts_cal1 as f32) * slope
+    }
+}
+
+/// Computes VDDA in millivolts from a reading of the internal reference.
+pub fn vdda_mv(vrefint_sample: u16) -> u32 {
+    assert!(vrefint_sample != 0, "Invalid internal reference reading");
+    VREFINT_MV * MAX_COUNT / vrefint_sample as u32
+}
+
+/// Converts a reading of any channel to millivolts.
+pub fn to_millivolts(sample: u16, vdda_mv: u32) -> u32 {
+    sample as u32 * vdda_mv / MAX_COUNT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALIBRATION: Calibration = Calibration {
+        ts_cal1: 1000,
+        ts_cal2: 1220,
+    };
+
+    #[test]
+    fn test_vdda_mv() {
+        // 1.2 V reads 1489 with VDDA at 3.3 V.
+        assert_eq!(vdda_mv(1489), 3300);
+        assert_eq!(vdda_mv(4095), 1200);
+        assert_eq!(vdda_mv(2457), 2000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_vdda_mv_zero() {
+        vdda_mv(0);
+    }
+
+    #[test]
+    fn test_to_millivolts() {
+        assert_eq!(to_millivolts(0, 3300), 0);
+        assert_eq!(to_millivolts(4095, 3300), 3300);
+        assert_eq!(to_millivolts(2048, 3300), 1650);
+        assert_eq!(to_millivolts(4095, 1800), 1800);
+    }
+
+    #[test]
+    fn test_temperature_at_calibration_points() {
+        assert_eq!(CALIBRATION.temperature(1000, TS_CAL_VDDA_MV), 30.0);
+        assert_eq!(CALIBRATION.temperature(1220, TS_CAL_VDDA_MV), 85.0);
+    }
+
+    #[test]
+    fn test_temperature_interpolation() {
+        // 4 counts per degree.
+        assert_eq!(CALIBRATION.temperature(1100, TS_CAL_VDDA_MV), 55.0);
+        assert_eq!(CALIBRATION.temperature(900, TS_CAL_VDDA_MV), 5.0);
+    }
+
+    #[test]
+    fn test_temperature_vdda_compensation() {
+        // The same voltage reads higher with a lower VDDA.
+        let t = CALIBRATION.temperature(1650, 2000);
+        assert!((t - 30.0).abs() < 0.01, "{}", t);
+    }
+}
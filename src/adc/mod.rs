@@ -15,6 +15,13 @@ mod _version;
 #[allow(unused)]
 pub use _version::*;
 
+#[cfg(dma)]
+mod averaged;
+pub mod calibration;
+#[cfg(dma)]
+pub use averaged::AveragedAdc;
+pub use calibration::Calibration;
+
 use core::marker::PhantomData;
 use embassy_sync::waitqueue::AtomicWaker;
 
@@ -31,6 +38,50 @@ pub struct Adc<'d, T: Instance> {
     #[allow(unused)]
     adc: crate::PeripheralRef<'d, T>,
     sample_time: SampleTime,
+    oversampling: Oversampling,
+}
+
+/// Oversampling settings, see [`Adc::set_oversampling`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Oversampling {
+    /// Number of conversions summed into one result.
+    pub ratio: u16,
+    /// Right shift applied to the sum.
+    pub shift: u8,
+}
+
+impl Oversampling {
+    /// One conversion per result.
+    pub const NONE: Self = Self { ratio: 1, shift: 0 };
+
+    pub(crate) fn apply(&self, sum: u32) -> u16 {
+        (sum >> self.shift) as u16
+    }
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Sets up oversampling: each result is the sum of `ratio` conversions, shifted right by
+    /// `shift` bits. For example, a ratio of 16 with a shift of 4 averages 16 conversions.
+    ///
+    /// The PY32 ADCs have no hardware oversampler, the conversions are accumulated in software:
+    /// the single channel reads convert `ratio` times, and `AveragedAdc` accumulates the
+    /// measurements of the DMA ring buffer.
+    ///
+    /// The shifted sum of `ratio` 12-bit conversions must fit in 16 bits, for example a ratio
+    /// of 256 needs a shift of at least 4.
+    pub fn set_oversampling(&mut self, ratio: u16, shift: u8) {
+        assert!(
+            (1..=256).contains(&ratio),
+            "Oversampling ratio must be 1..=256"
+        );
+        assert!(shift <= 8, "Oversampling shift must be 0..=8");
+        assert!(
+            (ratio as u32 * 4095) >> shift <= u16::MAX as u32,
+            "Oversampling result must fit in 16 bits, increase the shift"
+        );
+        self.oversampling = Oversampling { ratio, shift };
+    }
 }
 
 pub struct State {
@@ -111,6 +162,23 @@ impl<T: Instance> SealedAdcChannel<T> for AnyAdcChannel<T> {
     }
 }
 
+/// Channels checked by the analog watchdog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WatchdogChannels {
+    /// Every converted channel.
+    All,
+    /// A single channel, by channel number.
+    Single(u8),
+}
+
+impl WatchdogChannels {
+    /// Watch only `channel`.
+    pub fn single<T>(channel: &impl AdcChannel<T>) -> Self {
+        Self::Single(channel.channel())
+    }
+}
+
 foreach_adc!(
     ($inst:ident, $common_inst:ident, $clock:ident) => {
         impl crate::adc::SealedInstance for peripherals::$inst {
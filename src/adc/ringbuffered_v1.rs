@@ -0,0 +1,332 @@
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+
+use super::{blocking_delay_us, set_channel_sequence};
+use crate::adc::{Adc, AnyAdcChannel, Instance, Oversampling, RxDma, SampleTime};
+use crate::dma::{Priority, ReadableRingBuffer, TransferOptions};
+use crate::pac::adc::vals::{Dmacfg, Exten};
+use crate::rcc;
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OverrunError;
+
+fn clear_interrupt_flags(r: crate::pac::adc::Adc) {
+    r.isr().modify(|regs| {
+        regs.set_eoc(true);
+        regs.set_eosmp(true);
+        regs.set_eoseq(true);
+        regs.set_ovr(true);
+    });
+}
+
+pub struct RingBufferedAdc<'d, T: Instance> {
+    _phantom: PhantomData<T>,
+    ring_buf: ReadableRingBuffer<'d, u16>,
+    pub(crate) oversampling: Oversampling,
+}
+
+impl<'d, T: Instance> Adc<'d, T> {
+    /// Configures the ADC to use a DMA ring buffer for continuous data acquisition.
+    ///
+    /// The `dma_buf` should be large enough to prevent DMA buffer overrun.
+    /// The length of the `dma_buf` should be a multiple of the ADC channel count.
+    /// For example, if 3 channels are measured, its length can be 3 * 40 = 120 measurements.
+    ///
+    /// `read` method is used to read out measurements from the DMA ring buffer, and its buffer should be exactly half of the `dma_buf` length.
+    /// It is critical to call `read` frequently to prevent DMA buffer overrun.
+    ///
+    /// [`read`]: #method.read
+    pub fn into_ring_buffered(
+        self,
+        dma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        dma_buf: &'d mut [u16],
+    ) -> RingBufferedAdc<'d, T> {
+        assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+        into_ref!(dma);
+
+        let opts: crate::dma::TransferOptions = TransferOptions {
+            half_transfer_ir: true,
+            priority: Priority::VeryHigh,
+            ..Default::default()
+        };
+
+        // Safety: we forget the struct before this function returns.
+        let rx_src = T::regs().dr().as_ptr() as *mut u16;
+        let request = dma.request();
+
+        let ring_buf = unsafe { ReadableRingBuffer::new(dma, request, rx_src, dma_buf, opts) };
+
+        T::regs()
+            .smpr()
+            .modify(|reg| reg.set_smp(self.sample_time.into()));
+
+        let oversampling = self.oversampling;
+
+        // Don't disable the clock
+        mem::forget(self);
+
+        RingBufferedAdc {
+            _phantom: PhantomData,
+            ring_buf,
+            oversampling,
+        }
+    }
+}
+
+impl<'d, T: Instance> RingBufferedAdc<'d, T> {
+    fn is_converting() -> bool {
+        T::regs().cr().read().adstart()
+    }
+
+    /// Selects the channels to convert.
+    ///
+    /// The ADC scans the channels in the order of their channel numbers, so `channels` must be
+    /// sorted by strictly ascending or descending channel number. The order picks the scan
+    /// direction, and the order of the measurements in the ring buffer.
+    ///
+    /// Must be called while the conversions are stopped, before [`start`] or after [`teardown_adc`].
+    ///
+    /// [`start`]: #method.start
+    /// [`teardown_adc`]: #method.teardown_adc
+    pub fn set_channels(&mut self, channels: &mut [AnyAdcChannel<T>]) {
+        assert!(!Self::is_converting(), "ADC conversions are running");
+
+        #[cfg(adc_v1b)]
+        T::regs().cr().modify(|reg| reg.set_addis(true));
+
+        let scandir = set_channel_sequence(channels);
+        T::regs().cfgr1().modify(|reg| reg.set_scandir(scandir));
+    }
+
+    /// Sets the sample time of all channels.
+    ///
+    /// Must be called while the conversions are stopped, before [`start`] or after [`teardown_adc`].
+    ///
+    /// [`start`]: #method.start
+    /// [`teardown_adc`]: #method.teardown_adc
+    pub fn set_sample_time(&mut self, sample_time: SampleTime) {
+        assert!(!Self::is_converting(), "ADC conversions are running");
+
+        T::regs()
+            .smpr()
+            .modify(|reg| reg.set_smp(sample_time.into()));
+    }
+
+    /// Turns on ADC if it is not already turned on and starts continuous DMA transfer.
+    pub fn start(&mut self) -> Result<(), OverrunError> {
+        self.setup_adc();
+        self.ring_buf.clear();
+
+        Ok(())
+    }
+
+    fn stop(&mut self, err: OverrunError) -> Result<usize, OverrunError> {
+        self.teardown_adc();
+        Err(err)
+    }
+
+    /// Stops DMA transfer and the conversions.
+    /// Calling `start` restarts continuous DMA transfer.
+    ///
+    /// [`start`]: #method.start
+    pub fn teardown_adc(&mut self) {
+        // Stop the DMA transfer
+        self.ring_buf.request_pause();
+
+        let r = T::regs();
+
+        // Stop ADC
+        if Self::is_converting() {
+            r.cr().modify(|reg| reg.set_adstp(true));
+            while r.cr().read().adstp() {}
+        }
+
+        r.cfgr1().modify(|reg| {
+            // Stop DMA
+            reg.set_dmaen(false);
+            reg.set_cont(false);
+        });
+
+        clear_interrupt_flags(r);
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    fn setup_adc(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+
+        self.ring_buf.start();
+
+        let r = T::regs();
+
+        // Clear all interrupts
+        clear_interrupt_flags(r);
+
+        r.cfgr1().modify(|w| {
+            // Enable circular DMA mode
+            w.set_dmacfg(Dmacfg::CIRCULAR);
+            w.set_dmaen(true);
+            // Enable continuous conversions, unless paced by an external trigger
+            w.set_cont(w.exten() == Exten::DISABLED);
+        });
+
+        // Begin ADC conversions, the ADC is re-enabled before each start.
+        r.cr().modify(|reg| reg.set_aden(true));
+        blocking_delay_us(1);
+        r.cr().modify(|reg| reg.set_adstart(true));
+    }
+
+    /// Waits until the analog watchdog flags a conversion.
+    ///
+    /// The watchdog is set up with `Adc::set_watchdog` before `into_ring_buffered`. Sampling
+    /// goes on in the background, so `read` must still be called often enough to avoid overruns.
+    pub async fn wait_for_watchdog(&mut self) {
+        let r = T::regs();
+
+        // Start background receive if it was not already started
+        if !r.cfgr1().read().dmaen() {
+            let _ = self.start();
+        }
+
+        r.isr().modify(|reg| reg.set_awd(true));
+        r.ier().modify(|w| w.set_awdie(true));
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if r.isr().read().awd() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        r.isr().modify(|reg| reg.set_awd(true));
+    }
+
+    /// Read bytes that are readily available in the ring buffer.
+    /// If no bytes are currently available in the buffer the call waits until the some
+    /// bytes are available (at least one byte and at most half the buffer size)
+    ///
+    /// Background receive is started if `start()` has not been previously called.
+    ///
+    /// Receive in the background is terminated if an error is returned.
+    /// It must then manually be started again by calling `start()` or by re-calling `read()`.
+    pub fn blocking_read<const N: usize>(
+        &mut self,
+        buf: &mut [u16; N],
+    ) -> Result<usize, OverrunError> {
+        let r = T::regs();
+
+        // Start background receive if it was not already started
+        if !r.cfgr1().read().dmaen() {
+            self.start()?;
+        }
+
+        // The ADC stops DMA requests after an overrun.
+        if r.isr().read().ovr() {
+            return self.stop(OverrunError);
+        }
+
+        loop {
+            match self.ring_buf.read(buf) {
+                Ok((0, _)) => {}
+                Ok((len, _)) => {
+                    return Ok(len);
+                }
+                Err(_) => {
+                    return self.stop(OverrunError);
+                }
+            }
+        }
+    }
+
+    /// Reads measurements from the DMA ring buffer.
+    ///
+    /// This method fills the provided `measurements` array with ADC readings from the DMA buffer.
+    /// The length of the `measurements` array should be exactly half of the DMA buffer length. Because interrupts are only generated if half or full DMA transfer completes.
+    ///
+    /// Each call to `read` will populate the `measurements` array in the scan order of the channels selected with `set_channels`.
+    /// There will be many sequences worth of measurements in this array because it only returns if at least half of the DMA buffer is filled.
+    /// For example if channels 0, 1 and 4 are sampled `measurements` contain: `[ch0 ch1 ch4 ch0 ch1 ch4 ch0 ch1 ch4 ..]`.
+    ///
+    /// If an error is returned, it indicates a DMA overrun, and the process must be restarted by calling `start` or `read` again.
+    ///
+    /// By default, the ADC fills the DMA buffer as quickly as possible. To sample at a fixed rate, select a timer TRGO with `Adc::set_external_trigger`
+    /// before `into_ring_buffered`, each trigger event then converts the channels once.
+    /// Otherwise, call `teardown_adc` after each readout, and then start the DMA again at the desired interval.
+    ///
+    /// Example:
+    /// ```rust,ignore
+    /// const DMA_BUF_LEN: usize = 120;
+    /// let adc_dma_buf = [0u16; DMA_BUF_LEN];
+    /// let mut adc: RingBufferedAdc<py32_hal::peripherals::ADC1> = adc.into_ring_buffered(p.DMA1_CH1, adc_dma_buf);
+    ///
+    /// adc.set_channels(&mut [p.PA0.degrade_adc(), p.PA1.degrade_adc(), p.PA4.degrade_adc()]);
+    ///
+    /// let mut measurements = [0u16; DMA_BUF_LEN / 2];
+    /// loop {
+    ///     match adc.read(&mut measurements).await {
+    ///         Ok(_) => {
+    ///             defmt::info!("adc1: {}", measurements);
+    ///             // Only needed to manually control sample rate.
+    ///             adc.teardown_adc();
+    ///         }
+    ///         Err(e) => {
+    ///             defmt::warn!("Error: {:?}", e);
+    ///             // DMA overrun, next call to `read` restarts ADC.
+    ///         }
+    ///     }
+    ///
+    ///     // Manually control sample rate.
+    ///     Timer::after_millis(100).await;
+    /// }
+    /// ```
+    ///
+    /// [`set_channels`]: #method.set_channels
+    /// [`teardown_adc`]: #method.teardown_adc
+    /// [`start`]: #method.start
+    pub async fn read<const N: usize>(
+        &mut self,
+        measurements: &mut [u16; N],
+    ) -> Result<usize, OverrunError> {
+        assert_eq!(
+            self.ring_buf.capacity() / 2,
+            N,
+            "Buffer size must be half the size of the ring buffer"
+        );
+
+        let r = T::regs();
+
+        // Start background receive if it was not already started
+        if !r.cfgr1().read().dmaen() {
+            self.start()?;
+        }
+
+        // The ADC stops DMA requests after an overrun.
+        if r.isr().read().ovr() {
+            return self.stop(OverrunError);
+        }
+        match self.ring_buf.read_exact(measurements).await {
+            Ok(len) => Ok(len),
+            Err(_) => self.stop(OverrunError),
+        }
+    }
+}
+
+impl<T: Instance> Drop for RingBufferedAdc<'_, T> {
+    fn drop(&mut self) {
+        self.teardown_adc();
+        rcc::disable::<T>();
+    }
+}
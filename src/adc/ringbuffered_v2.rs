@@ -2,14 +2,16 @@
 // https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
 // Special thanks to the Embassy Project and its contributors for their work!
 
+use core::future::poll_fn;
 use core::marker::PhantomData;
 use core::mem;
 use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
 
 use embassy_hal_internal::{into_ref, Peripheral};
-use py32_metapac::adc::vals::SampleTime;
+use py32_metapac::adc::vals::{Extsel, SampleTime};
 
-use crate::adc::{Adc, AdcChannel, Instance, RxDma};
+use crate::adc::{Adc, AdcChannel, Instance, Oversampling, RxDma};
 use crate::dma::{Priority, ReadableRingBuffer, TransferOptions};
 use crate::rcc;
 
@@ -93,6 +95,7 @@ impl From<u8> for Sequence {
 pub struct RingBufferedAdc<'d, T: Instance> {
     _phantom: PhantomData<T>,
     ring_buf: ReadableRingBuffer<'d, u16>,
+    pub(crate) oversampling: Oversampling,
 }
 
 impl<'d, T: Instance> Adc<'d, T> {
@@ -126,12 +129,15 @@ impl<'d, T: Instance> Adc<'d, T> {
 
         let ring_buf = unsafe { ReadableRingBuffer::new(dma, request, rx_src, dma_buf, opts) };
 
+        let oversampling = self.oversampling;
+
         // Don't disable the clock
         mem::forget(self);
 
         RingBufferedAdc {
             _phantom: PhantomData,
             ring_buf,
+            oversampling,
         }
     }
 }
@@ -318,8 +324,8 @@ impl<'d, T: Instance> RingBufferedAdc<'d, T> {
         r.cr2().modify(|w| {
             // Enable DMA mode
             w.set_dma(true);
-            // Enable continuous conversions
-            w.set_cont(true);
+            // Enable continuous conversions, unless paced by an external trigger
+            w.set_cont(w.extsel() == Extsel::SWSTART);
         });
 
         // Begin ADC conversions
@@ -332,6 +338,35 @@ impl<'d, T: Instance> RingBufferedAdc<'d, T> {
         super::blocking_delay_us(3);
     }
 
+    /// Waits until the analog watchdog flags a conversion.
+    ///
+    /// The watchdog is set up with `Adc::set_watchdog` before `into_ring_buffered`. Sampling
+    /// goes on in the background, so `read` must still be called often enough to avoid overruns.
+    pub async fn wait_for_watchdog(&mut self) {
+        let r = T::regs();
+
+        // Start background receive if it was not already started
+        if !r.cr2().read().dma() {
+            let _ = self.start();
+        }
+
+        r.sr().modify(|reg| reg.set_awd(false));
+        r.cr1().modify(|w| w.set_awdie(true));
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if r.sr().read().awd() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        r.sr().modify(|reg| reg.set_awd(false));
+    }
+
     /// Read bytes that are readily available in the ring buffer.
     /// If no bytes are currently available in the buffer the call waits until the some
     /// bytes are available (at least one byte and at most half the buffer size)
@@ -380,7 +415,9 @@ impl<'d, T: Instance> RingBufferedAdc<'d, T> {
     ///
     /// If an error is returned, it indicates a DMA overrun, and the process must be restarted by calling `start` or `read` again.
     ///
-    /// By default, the ADC fills the DMA buffer as quickly as possible. To control the sample rate, call `teardown_adc` after each readout, and then start the DMA again at the desired interval.
+    /// By default, the ADC fills the DMA buffer as quickly as possible. To sample at a fixed rate, select a timer with `Adc::set_external_trigger`
+    /// before `into_ring_buffered`, each trigger event then converts the sequence once.
+    /// Otherwise, call `teardown_adc` after each readout, and then start the DMA again at the desired interval.
     /// Note that even if using `teardown_adc` to control the sample rate, with each call to `read`, measurements equivalent to half the size of the DMA buffer are still collected.
     ///
     /// Example:
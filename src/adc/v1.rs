@@ -6,15 +6,30 @@ use core::future::poll_fn;
 use core::marker::PhantomData;
 use core::task::Poll;
 
+#[cfg(dma)]
+use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::into_ref;
-use py32_metapac::adc::vals::Ckmode;
+use py32_metapac::adc::vals::{Ckmode, Exten, Extsel};
 
 use super::blocking_delay_us;
-use crate::adc::{Adc, AdcChannel, Instance, Resolution, SampleTime};
+use crate::adc::{
+    Adc, AdcChannel, Instance, Oversampling, Resolution, SampleTime, WatchdogChannels,
+};
+#[cfg(dma)]
+use crate::adc::{AnyAdcChannel, RxDma, SealedAdcChannel};
+#[cfg(dma)]
+use crate::dma::Transfer;
 use crate::interrupt::typelevel::Interrupt;
+#[cfg(dma)]
+use crate::pac::adc::vals::{Dmacfg, Scandir};
 use crate::peripherals::ADC1;
 use crate::{interrupt, rcc, Peripheral};
 
+#[cfg(dma)]
+mod ringbuffered_v1;
+#[cfg(dma)]
+pub use ringbuffered_v1::{OverrunError, RingBufferedAdc};
+
 pub const VDDA_CALIB_MV: u32 = 3300;
 pub const VREF_INT: u32 = 1200;
 
@@ -25,8 +40,12 @@ pub struct InterruptHandler<T: Instance> {
 
 impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
     unsafe fn on_interrupt() {
-        if T::regs().isr().read().eoc() {
+        let isr = T::regs().isr().read();
+        let ier = T::regs().ier().read();
+        if ier.eocie() && isr.eoc() {
             T::regs().ier().modify(|w| w.set_eocie(false));
+        } else if ier.awdie() && isr.awd() {
+            T::regs().ier().modify(|w| w.set_awdie(false));
         } else {
             return;
         }
@@ -75,6 +94,43 @@ impl super::SealedAdcChannel<ADC1> for Temperature {
     }
 }
 
+/// External trigger for the conversions.
+// TODO: move to py32-metapac
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ExternalTrigger {
+    /// TIM1 TRGO
+    Tim1Trgo = 0,
+    /// TIM1 CC4
+    Tim1Cc4 = 1,
+    /// TIM3 TRGO
+    #[cfg(not(py32f002b))]
+    Tim3Trgo = 3,
+}
+
+/// Edge of the external trigger that starts the conversions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerEdge {
+    /// Start on rising edges.
+    Rising,
+    /// Start on falling edges.
+    Falling,
+    /// Start on both edges.
+    Both,
+}
+
+impl From<TriggerEdge> for Exten {
+    fn from(edge: TriggerEdge) -> Self {
+        match edge {
+            TriggerEdge::Rising => Exten::RISINGEDGE,
+            TriggerEdge::Falling => Exten::FALLINGEDGE,
+            TriggerEdge::Both => Exten::BOTHEDGES,
+        }
+    }
+}
+
 impl<'d, T: Instance> Adc<'d, T> {
     pub fn new(
         adc: impl Peripheral<P = T> + 'd,
@@ -112,6 +168,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         Self {
             adc,
             sample_time: SampleTime::from_bits(0),
+            oversampling: Oversampling::NONE,
         }
     }
 
@@ -206,6 +263,102 @@ impl<'d, T: Instance> Adc<'d, T> {
         T::regs().cfgr2().modify(|reg| reg.set_ckmode(ckmode));
     }
 
+    /// Starts the conversions on an edge of `trigger` instead of software.
+    ///
+    /// Each trigger event converts the selected channels once. [`read`](Self::read) and
+    /// `read_sequence` arm the ADC and wait for the trigger, and `RingBufferedAdc` samples at
+    /// the pace of the trigger.
+    pub fn set_external_trigger(&mut self, trigger: ExternalTrigger, edge: TriggerEdge) {
+        T::regs().cfgr1().modify(|reg| {
+            reg.set_extsel(Extsel::from_bits(trigger as u8));
+            reg.set_exten(edge.into());
+        });
+    }
+
+    /// Starts the conversions by software, this is the default.
+    pub fn set_software_trigger(&mut self) {
+        T::regs()
+            .cfgr1()
+            .modify(|reg| reg.set_exten(Exten::DISABLED));
+    }
+
+    /// Sets up the analog watchdog, it flags the conversions of `channels` outside of
+    /// `low..=high`.
+    ///
+    /// The thresholds are compared with 12-bit values, whatever the resolution.
+    pub fn set_watchdog(&mut self, channels: WatchdogChannels, low: u16, high: u16) {
+        assert!(low <= high && high <= 0xFFF, "Invalid watchdog thresholds");
+
+        T::regs().tr().write(|reg| {
+            reg.set_lt(low);
+            reg.set_ht(high);
+        });
+        T::regs().cfgr1().modify(|reg| {
+            match channels {
+                WatchdogChannels::All => reg.set_awdsgl(false),
+                WatchdogChannels::Single(ch) => {
+                    reg.set_awdsgl(true);
+                    reg.set_awdch(ch);
+                }
+            }
+            reg.set_awden(true);
+        });
+    }
+
+    /// Turns the analog watchdog off.
+    pub fn disable_watchdog(&mut self) {
+        T::regs().ier().modify(|w| w.set_awdie(false));
+        T::regs().cfgr1().modify(|reg| reg.set_awden(false));
+    }
+
+    /// Converts `channel` until the analog watchdog flags a conversion.
+    ///
+    /// The conversions run back to back, or at the pace of the external trigger if one is set
+    /// with [`set_external_trigger`](Self::set_external_trigger). The watchdog must be set up
+    /// with [`set_watchdog`](Self::set_watchdog) to watch `channel`.
+    pub async fn wait_for_watchdog(&mut self, channel: &mut impl AdcChannel<T>) {
+        let ch_num = channel.channel();
+        channel.setup();
+
+        #[cfg(adc_v1b)]
+        T::regs().cr().modify(|reg| reg.set_addis(true));
+
+        T::regs()
+            .chselr()
+            .write(|reg| reg.set_chselx(ch_num as usize, true));
+        T::regs()
+            .smpr()
+            .modify(|reg| reg.set_smp(self.sample_time.into()));
+        T::regs()
+            .cfgr1()
+            .modify(|reg| reg.set_cont(reg.exten() == Exten::DISABLED));
+
+        T::regs().isr().modify(|reg| {
+            reg.set_awd(true);
+            reg.set_ovr(true);
+        });
+        T::regs().ier().modify(|w| w.set_awdie(true));
+
+        // See `convert`, the ADC is re-enabled before each start.
+        T::regs().cr().modify(|reg| reg.set_aden(true));
+        blocking_delay_us(1);
+        T::regs().cr().modify(|reg| reg.set_adstart(true));
+
+        // Stop the conversions, also if the future is dropped before the watchdog fires.
+        let _guard = StopWatchdogOnDrop::<T>(PhantomData);
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if T::regs().isr().read().awd() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+
     pub async fn read(&mut self, channel: &mut impl AdcChannel<T>) -> u16 {
         let ch_num = channel.channel();
         channel.setup();
@@ -218,7 +371,92 @@ impl<'d, T: Instance> Adc<'d, T> {
             .chselr()
             .write(|reg| reg.set_chselx(ch_num as usize, true));
 
-        self.convert().await
+        let mut sum = 0;
+        for _ in 0..self.oversampling.ratio {
+            sum += self.convert().await as u32;
+        }
+        self.oversampling.apply(sum)
+    }
+
+    /// Converts a sequence of channels, the results are stored in `readings` by DMA.
+    ///
+    /// The ADC scans the selected channels in the order of their channel numbers, so `channels`
+    /// must be sorted by strictly ascending or descending channel number. The order picks the
+    /// scan direction. `readings` holds one value per channel, or a multiple of that to convert
+    /// the sequence several times back to back.
+    ///
+    /// Example
+    /// ```rust,ignore
+    /// let mut channels = [p.PA0.degrade_adc(), p.PA1.degrade_adc(), p.PA4.degrade_adc()];
+    /// let mut readings = [0u16; 3];
+    /// adc.read_sequence(&mut p.DMA1_CH1, &mut channels, &mut readings).await;
+    /// ```
+    #[cfg(dma)]
+    pub async fn read_sequence(
+        &mut self,
+        rx_dma: &mut impl RxDma<T>,
+        channels: &mut [AnyAdcChannel<T>],
+        readings: &mut [u16],
+    ) {
+        assert!(
+            !channels.is_empty() && !readings.is_empty() && readings.len() % channels.len() == 0,
+            "Readings length must be a multiple of the channel count"
+        );
+
+        #[cfg(adc_v1b)]
+        T::regs().cr().modify(|reg| reg.set_addis(true));
+
+        let scandir = set_channel_sequence(channels);
+
+        T::regs().isr().modify(|reg| {
+            reg.set_eoc(true);
+            reg.set_eosmp(true);
+            reg.set_eoseq(true);
+            reg.set_ovr(true);
+        });
+        T::regs()
+            .smpr()
+            .modify(|reg| reg.set_smp(self.sample_time.into()));
+
+        // Several passes over the sequence run as continuous conversions, unless each pass
+        // waits for the external trigger.
+        T::regs().cfgr1().modify(|reg| {
+            reg.set_scandir(scandir);
+            reg.set_cont(readings.len() > channels.len() && reg.exten() == Exten::DISABLED);
+            reg.set_dmacfg(Dmacfg::ONESHOT);
+            reg.set_dmaen(true);
+        });
+
+        let request = rx_dma.request();
+        let transfer = unsafe {
+            Transfer::new_read(
+                rx_dma,
+                request,
+                T::regs().dr().as_ptr() as *mut u16,
+                readings,
+                Default::default(),
+            )
+        };
+
+        // See `convert`, the ADC is re-enabled before each start.
+        T::regs().cr().modify(|reg| reg.set_aden(true));
+        blocking_delay_us(1);
+        T::regs().cr().modify(|reg| reg.set_adstart(true));
+
+        // Continuous or triggered conversions don't stop by themselves when the DMA transfer
+        // is done, or when the future is dropped.
+        let _on_drop = OnDrop::new(|| {
+            T::regs().cr().modify(|reg| reg.set_adstp(true));
+            while T::regs().cr().read().adstp() {}
+
+            T::regs().cfgr1().modify(|reg| {
+                reg.set_cont(false);
+                reg.set_dmaen(false);
+                reg.set_scandir(Scandir::UPWARD);
+            });
+        });
+
+        transfer.await;
     }
 
     async fn convert(&mut self) -> u16 {
@@ -254,6 +492,50 @@ impl<'d, T: Instance> Adc<'d, T> {
     }
 }
 
+/// Selects `channels` in CHSELR and returns the scan direction matching their order.
+#[cfg(dma)]
+fn set_channel_sequence<T: Instance>(channels: &[AnyAdcChannel<T>]) -> Scandir {
+    assert!(!channels.is_empty(), "Channel sequence is empty");
+
+    let backward = channels.len() > 1 && channels[0].channel() > channels[1].channel();
+    for pair in channels.windows(2) {
+        let (a, b) = (pair[0].channel(), pair[1].channel());
+        assert!(
+            if backward { a > b } else { a < b },
+            "Channels must be sorted by channel number"
+        );
+    }
+
+    T::regs().chselr().write(|reg| {
+        for channel in channels.iter() {
+            reg.set_chselx(channel.channel() as usize, true);
+        }
+    });
+
+    if backward {
+        Scandir::BACKWARD
+    } else {
+        Scandir::UPWARD
+    }
+}
+
+/// Stops the conversions started by [`Adc::wait_for_watchdog`].
+struct StopWatchdogOnDrop<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Drop for StopWatchdogOnDrop<T> {
+    fn drop(&mut self) {
+        T::regs().ier().modify(|w| w.set_awdie(false));
+        T::regs().cr().modify(|reg| reg.set_adstp(true));
+        while T::regs().cr().read().adstp() {}
+
+        T::regs().cfgr1().modify(|reg| reg.set_cont(false));
+        T::regs().isr().modify(|reg| {
+            reg.set_awd(true);
+            reg.set_ovr(true);
+        });
+    }
+}
+
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
         // A.7.3 ADC disable code example
@@ -2,24 +2,57 @@
 // https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
 // Special thanks to the Embassy Project and its contributors for their work!
 
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
 use embassy_hal_internal::into_ref;
 
 use super::blocking_delay_us;
-use crate::adc::{Adc, AdcChannel, Instance, Resolution, SampleTime};
+use crate::adc::{
+    Adc, AdcChannel, Instance, Oversampling, Resolution, SampleTime, WatchdogChannels,
+};
+use crate::interrupt::typelevel::Interrupt;
 use crate::pac::adc::vals::Extsel;
 use crate::pac::RCC;
 use crate::peripherals::ADC1;
 use crate::time::Hertz;
-use crate::{rcc, Peripheral};
+use crate::{interrupt, rcc, Peripheral};
 
 mod ringbuffered_v2;
-pub use ringbuffered_v2::{RingBufferedAdc, Sequence};
+pub use ringbuffered_v2::{OverrunError, RingBufferedAdc, Sequence};
 
 /// Default VREF voltage used for sample conversion to millivolts.
 pub const VREF_DEFAULT_MV: u32 = 3300;
 /// VREF voltage used for factory calibration of VREFINTCAL register.
 pub const VREF_CALIB_MV: u32 = 3300;
 
+/// Interrupt handler, needed by the analog watchdog and bound in [`Adc::new`].
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let sr = r.sr().read();
+        let cr1 = r.cr1().read();
+
+        // `RingBufferedAdc` enables the EOC and overrun interrupts, but polls their flags.
+        if cr1.eocie() || cr1.ovrie() {
+            r.cr1().modify(|w| {
+                w.set_eocie(false);
+                w.set_ovrie(false);
+            });
+        }
+
+        if cr1.awdie() && sr.awd() {
+            r.cr1().modify(|w| w.set_awdie(false));
+            T::state().waker.wake();
+        }
+    }
+}
+
 pub struct VrefInt;
 impl AdcChannel<ADC1> for VrefInt {}
 impl super::SealedAdcChannel<ADC1> for VrefInt {
@@ -58,6 +91,30 @@ impl Temperature {
 //     }
 // }
 
+/// External trigger for the regular conversions.
+///
+/// Conversions start on the rising edge of the trigger, the edge is fixed on this ADC.
+// TODO: move to py32-metapac
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ExternalTrigger {
+    /// TIM1 CC1
+    Tim1Cc1 = 0,
+    /// TIM1 CC2
+    Tim1Cc2 = 1,
+    /// TIM1 CC3
+    Tim1Cc3 = 2,
+    /// TIM2 CC2
+    Tim2Cc2 = 3,
+    /// TIM3 TRGO
+    Tim3Trgo = 4,
+    /// TIM15 TRGO
+    Tim15Trgo = 5,
+    /// EXTI line 11, the pin must be set up with [`ExtiInput`](crate::exti::ExtiInput).
+    Exti11 = 6,
+}
+
 pub enum Prescaler {
     Div2,
     Div4,
@@ -95,13 +152,20 @@ impl<'d, T> Adc<'d, T>
 where
     T: Instance,
 {
-    pub fn new(adc: impl Peripheral<P = T> + 'd) -> Self {
+    pub fn new(
+        adc: impl Peripheral<P = T> + 'd,
+        irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+    ) -> Self {
         let presc = Prescaler::from_pclk(T::frequency());
-        Self::new_with_prediv(adc, presc)
+        Self::new_with_prediv(adc, irq, presc)
     }
 
     /// adc_div: The PCLK division factor
-    pub fn new_with_prediv(adc: impl Peripheral<P = T> + 'd, adc_div: Prescaler) -> Self {
+    pub fn new_with_prediv(
+        adc: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        adc_div: Prescaler,
+    ) -> Self {
         into_ref!(adc);
         rcc::enable_and_reset::<T>();
 
@@ -120,9 +184,15 @@ where
 
         blocking_delay_us(3);
 
+        T::Interrupt::unpend();
+        unsafe {
+            T::Interrupt::enable();
+        }
+
         Self {
             adc,
             sample_time: SampleTime::from_bits(0),
+            oversampling: Oversampling::NONE,
         }
     }
 
@@ -134,6 +204,96 @@ where
         T::regs().cr1().modify(|reg| reg.set_res(resolution.into()));
     }
 
+    /// Starts the regular conversions on `trigger` instead of software.
+    ///
+    /// Each trigger event converts the regular sequence once. [`blocking_read`](Self::blocking_read)
+    /// waits for the trigger, and [`RingBufferedAdc`] samples at the pace of the trigger.
+    pub fn set_external_trigger(&mut self, trigger: ExternalTrigger) {
+        T::regs().cr2().modify(|reg| {
+            reg.set_extsel(Extsel::from_bits(trigger as u8));
+            reg.set_exttrig(true);
+        });
+    }
+
+    /// Starts the regular conversions by software, this is the default.
+    pub fn set_software_trigger(&mut self) {
+        T::regs().cr2().modify(|reg| {
+            reg.set_extsel(Extsel::SWSTART);
+        });
+    }
+
+    /// Sets up the analog watchdog, it flags the regular conversions of `channels` outside of
+    /// `low..=high`.
+    ///
+    /// The thresholds are compared with 12-bit values, whatever the resolution.
+    pub fn set_watchdog(&mut self, channels: WatchdogChannels, low: u16, high: u16) {
+        assert!(low <= high && high <= 0xFFF, "Invalid watchdog thresholds");
+
+        T::regs().ltr().write(|reg| reg.set_lt(low));
+        T::regs().htr().write(|reg| reg.set_ht(high));
+        T::regs().cr1().modify(|reg| {
+            match channels {
+                WatchdogChannels::All => reg.set_awdsgl(false),
+                WatchdogChannels::Single(ch) => {
+                    reg.set_awdsgl(true);
+                    reg.set_awdch(ch);
+                }
+            }
+            reg.set_awden(true);
+        });
+    }
+
+    /// Turns the analog watchdog off.
+    pub fn disable_watchdog(&mut self) {
+        T::regs().cr1().modify(|reg| {
+            reg.set_awdie(false);
+            reg.set_awden(false);
+        });
+    }
+
+    /// Converts `channel` until the analog watchdog flags a conversion.
+    ///
+    /// The conversions run back to back, or at the pace of the external trigger if one is set
+    /// with [`set_external_trigger`](Self::set_external_trigger). The watchdog must be set up
+    /// with [`set_watchdog`](Self::set_watchdog) to watch `channel`.
+    pub async fn wait_for_watchdog(&mut self, channel: &mut impl AdcChannel<T>) {
+        channel.setup();
+        let channel = channel.channel();
+
+        let r = T::regs();
+        r.sqr1().modify(|reg| reg.set_l(0));
+        r.sqr3().write(|reg| reg.set_sq(0, channel));
+        Self::set_channel_sample_time(channel, self.sample_time);
+
+        r.sr().modify(|reg| {
+            reg.set_awd(false);
+            reg.set_eoc(false);
+        });
+        r.cr1().modify(|reg| reg.set_awdie(true));
+        r.cr2()
+            .modify(|reg| reg.set_cont(reg.extsel() == Extsel::SWSTART));
+
+        // Start conversion
+        r.cr2().modify(|reg| {
+            reg.set_swstart(true);
+            reg.set_exttrig(true);
+        });
+
+        // Stop the conversions, also if the future is dropped before the watchdog fires.
+        let _guard = StopWatchdogOnDrop::<T>(PhantomData);
+
+        poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if r.sr().read().awd() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+
     /// Enables internal voltage reference and returns [VrefInt], which can be used in
     /// [Adc::read_internal()] to perform conversion.
     pub fn enable_vrefint(&self) -> VrefInt {
@@ -202,7 +362,11 @@ where
         // Configure channel
         Self::set_channel_sample_time(channel, self.sample_time);
 
-        self.convert()
+        let mut sum = 0;
+        for _ in 0..self.oversampling.ratio {
+            sum += self.convert() as u32;
+        }
+        self.oversampling.apply(sum)
     }
 
     fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
@@ -262,6 +426,26 @@ where
     }
 }
 
+/// Stops the conversions started by [`Adc::wait_for_watchdog`].
+struct StopWatchdogOnDrop<T: Instance>(PhantomData<T>);
+
+impl<T: Instance> Drop for StopWatchdogOnDrop<T> {
+    fn drop(&mut self) {
+        let r = T::regs();
+        r.cr1().modify(|reg| reg.set_awdie(false));
+        // There is no stop bit: without CONT and the trigger, the conversion in progress is the
+        // last one. The next conversion start enables the trigger again.
+        r.cr2().modify(|reg| {
+            reg.set_cont(false);
+            reg.set_exttrig(false);
+        });
+        r.sr().modify(|reg| {
+            reg.set_awd(false);
+            reg.set_eoc(false);
+        });
+    }
+}
+
 impl<'d, T: Instance> Drop for Adc<'d, T> {
     fn drop(&mut self) {
         T::regs().cr2().modify(|reg| {
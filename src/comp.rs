@@ -0,0 +1,409 @@
+//! Analog comparator (COMP)
+#![macro_use]
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::gpio::{AfType, AnyPin, Level, OutputType, SealedPin as _, Speed};
+use crate::interrupt::typelevel::Interrupt as _;
+use crate::pac::EXTI;
+use crate::rcc::{self, RccPeripheral};
+use crate::{interrupt, Peripheral};
+
+/// Fraction of VREFINT used as the inverting input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VrefFraction {
+    /// VREFINT / 4
+    Quarter,
+    /// VREFINT / 2
+    Half,
+    /// VREFINT * 3 / 4
+    ThreeQuarters,
+    /// VREFINT
+    Full,
+}
+
+impl VrefFraction {
+    // TODO: move to py32-metapac
+    fn inmsel(&self) -> u8 {
+        match self {
+            VrefFraction::Quarter => 0,
+            VrefFraction::Half => 1,
+            VrefFraction::ThreeQuarters => 2,
+            VrefFraction::Full => 3,
+        }
+    }
+}
+
+/// Inverting (minus) input of the comparator.
+pub enum InvertingInput<'d, T: Instance> {
+    /// A fraction of the internal reference voltage.
+    Vrefint(VrefFraction),
+    /// A GPIO pin.
+    Pin(PeripheralRef<'d, AnyPin>, u8, PhantomData<T>),
+}
+
+impl<'d, T: Instance> InvertingInput<'d, T> {
+    /// Use a GPIO pin as the inverting input.
+    pub fn pin(pin: impl Peripheral<P = impl InmPin<T>> + 'd) -> Self {
+        into_ref!(pin);
+        pin.set_as_analog();
+        let ch = pin.channel();
+        Self::Pin(pin.map_into(), ch, PhantomData)
+    }
+
+    /// Use a fraction of VREFINT as the inverting input.
+    pub fn vrefint(fraction: VrefFraction) -> Self {
+        Self::Vrefint(fraction)
+    }
+
+    fn inmsel(&self) -> u8 {
+        match self {
+            Self::Vrefint(fraction) => fraction.inmsel(),
+            Self::Pin(_, ch, _) => *ch,
+        }
+    }
+}
+
+/// Output polarity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Polarity {
+    /// The output is high when the non-inverting input is above the inverting input.
+    NotInverted,
+    /// The output is low when the non-inverting input is above the inverting input.
+    Inverted,
+}
+
+/// Speed and consumption of the comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerMode {
+    /// Shortest propagation delay, highest consumption.
+    HighSpeed,
+    /// Longer propagation delay, lower consumption.
+    MediumSpeed,
+}
+
+/// Comparator configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Enable the input hysteresis.
+    pub hysteresis: bool,
+    /// Output polarity.
+    pub polarity: Polarity,
+    /// Speed and consumption.
+    pub power_mode: PowerMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            hysteresis: false,
+            polarity: Polarity::NotInverted,
+            power_mode: PowerMode::HighSpeed,
+        }
+    }
+}
+
+/// Comparator interrupt handler, for the comparator EXTI line.
+///
+/// The comparators share their interrupt with the ADC, bind both handlers to it if needed.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let line = T::EXTI_LINE;
+        if !EXTI.imr().read().line(line) || !EXTI.pr().read().line(line) {
+            return;
+        }
+
+        // The line is left masked for the future to see that it fired, like `ExtiInput`.
+        EXTI.imr().modify(|w| w.set_line(line, false));
+        EXTI.pr().write(|w| w.set_line(line, true));
+        T::waker().wake();
+
+        #[cfg(feature = "low-power")]
+        crate::low_power::on_wakeup_irq();
+    }
+}
+
+/// Analog comparator driver.
+///
+/// The comparator keeps running in Stop mode, and its EXTI line can wake the chip up.
+pub struct Comparator<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+    _inp: Option<PeripheralRef<'d, AnyPin>>,
+    _inm: InvertingInput<'d, T>,
+    _out: Option<PeripheralRef<'d, AnyPin>>,
+}
+
+impl<'d, T: Instance> Comparator<'d, T> {
+    /// Create a new comparator comparing `inp` to `inm`.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        inp: impl Peripheral<P = impl InpPin<T>> + 'd,
+        inm: InvertingInput<'d, T>,
+        config: Config,
+    ) -> Self {
+        into_ref!(inp);
+        inp.set_as_analog();
+        let inpsel = inp.channel();
+
+        Self::new_inner(peri, Some(inp.map_into()), inpsel, false, inm, config)
+    }
+
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        inp: Option<PeripheralRef<'d, AnyPin>>,
+        inpsel: u8,
+        window: bool,
+        inm: InvertingInput<'d, T>,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri);
+
+        rcc::enable_and_reset::<T>();
+
+        T::regs().csr().modify(|w| {
+            w.set_inpsel(inpsel);
+            w.set_inmsel(inm.inmsel());
+            w.set_winmode(window);
+            w.set_hyst(config.hysteresis);
+            w.set_polarity(config.polarity == Polarity::Inverted);
+            w.set_pwrmode(match config.power_mode {
+                PowerMode::HighSpeed => 0,
+                PowerMode::MediumSpeed => 1,
+            });
+            w.set_en(true);
+        });
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _peri: peri,
+            _inp: inp,
+            _inm: inm,
+            _out: None,
+        }
+    }
+
+    /// Current comparator output, after the polarity is applied.
+    pub fn output_level(&self) -> Level {
+        T::regs().csr().read().comp_out().into()
+    }
+
+    /// Route the comparator output to a pin.
+    pub fn set_output_pin(&mut self, pin: impl Peripheral<P = impl OutputPin<T>> + 'd) {
+        into_ref!(pin);
+        critical_section::with(|_| {
+            pin.set_as_af(
+                pin.af_num(),
+                AfType::output(OutputType::PushPull, Speed::VeryHigh),
+            );
+        });
+        self._out = Some(pin.map_into());
+    }
+
+    /// Route the comparator output to the TIM1 break input.
+    ///
+    /// The break input must also be enabled in the timer.
+    pub fn set_timer_break(&mut self, enabled: bool) {
+        // TODO: move to py32-metapac
+        critical_section::with(|_| {
+            crate::pac::SYSCFG
+                .cfgr2()
+                .modify(|w| w.set_comp_brk_tim1(T::INDEX, enabled))
+        });
+    }
+
+    /// Wait for a rising edge of the output.
+    pub async fn wait_for_rising_edge(&mut self) {
+        self.wait(true, false, None).await
+    }
+
+    /// Wait for a falling edge of the output.
+    pub async fn wait_for_falling_edge(&mut self) {
+        self.wait(false, true, None).await
+    }
+
+    /// Wait for any edge of the output.
+    pub async fn wait_for_any_edge(&mut self) {
+        self.wait(true, true, None).await
+    }
+
+    /// Wait until the output is high, returns immediately if it already is.
+    pub async fn wait_for_high(&mut self) {
+        self.wait(true, false, Some(Level::High)).await
+    }
+
+    /// Wait until the output is low, returns immediately if it already is.
+    pub async fn wait_for_low(&mut self) {
+        self.wait(false, true, Some(Level::Low)).await
+    }
+
+    /// Wait for an edge, or until the output equals `target`.
+    async fn wait(&mut self, rising: bool, falling: bool, target: Option<Level>) {
+        let line = T::EXTI_LINE;
+        // The line is armed before the level is checked, so that no edge is missed.
+        critical_section::with(|_| {
+            EXTI.rtsr().modify(|w| w.set_line(line, rising));
+            EXTI.ftsr().modify(|w| w.set_line(line, falling));
+            EXTI.pr().write(|w| w.set_line(line, true));
+            EXTI.imr().modify(|w| w.set_line(line, true));
+        });
+
+        // Mask the line again if the future is dropped before it completes.
+        let _guard = MaskOnDrop(line);
+
+        poll_fn(|cx| {
+            T::waker().register(cx.waker());
+
+            if target.is_some_and(|target| self.output_level() == target)
+                || !EXTI.imr().read().line(line)
+            {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<'d> Comparator<'d, crate::peripherals::COMP2> {
+    /// Create a new comparator in window mode.
+    ///
+    /// The non-inverting input of COMP2 is connected to the non-inverting input of COMP1,
+    /// so that both comparators watch the same signal against two thresholds.
+    pub fn new_window(
+        peri: impl Peripheral<P = crate::peripherals::COMP2> + 'd,
+        _irq: impl interrupt::typelevel::Binding<
+                <crate::peripherals::COMP2 as Instance>::Interrupt,
+                InterruptHandler<crate::peripherals::COMP2>,
+            > + 'd,
+        inm: InvertingInput<'d, crate::peripherals::COMP2>,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(peri, None, 0, true, inm, config)
+    }
+}
+
+impl<'d, T: Instance> Drop for Comparator<'d, T> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            EXTI.imr().modify(|w| w.set_line(T::EXTI_LINE, false));
+        });
+        T::regs().csr().modify(|w| w.set_en(false));
+        rcc::disable::<T>();
+    }
+}
+
+struct MaskOnDrop(usize);
+
+impl Drop for MaskOnDrop {
+    fn drop(&mut self) {
+        critical_section::with(|_| EXTI.imr().modify(|w| w.set_line(self.0, false)));
+    }
+}
+
+trait SealedInstance {
+    /// Index of the comparator, 0 for COMP1.
+    const INDEX: usize;
+    /// EXTI line of the comparator output.
+    const EXTI_LINE: usize;
+
+    fn regs() -> crate::pac::comp::Comp;
+    fn waker() -> &'static AtomicWaker;
+}
+
+/// Comparator instance trait.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + RccPeripheral + Peripheral<P = Self> + 'static {
+    /// Interrupt for this comparator.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+pub(crate) trait SealedInpPin<T: Instance> {
+    fn channel(&self) -> u8;
+}
+
+pub(crate) trait SealedInmPin<T: Instance> {
+    fn channel(&self) -> u8;
+}
+
+/// Non-inverting input pin.
+#[allow(private_bounds)]
+pub trait InpPin<T: Instance>: crate::gpio::Pin + SealedInpPin<T> {}
+
+/// Inverting input pin.
+#[allow(private_bounds)]
+pub trait InmPin<T: Instance>: crate::gpio::Pin + SealedInmPin<T> {}
+
+pin_trait!(OutputPin, Instance);
+
+#[allow(unused_macros)]
+macro_rules! impl_comp_inp_pin {
+    ($inst:ident, $pin:ident, $ch:expr) => {
+        impl crate::comp::InpPin<peripherals::$inst> for crate::peripherals::$pin {}
+        impl crate::comp::SealedInpPin<peripherals::$inst> for crate::peripherals::$pin {
+            fn channel(&self) -> u8 {
+                $ch
+            }
+        }
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! impl_comp_inm_pin {
+    ($inst:ident, $pin:ident, $ch:expr) => {
+        impl crate::comp::InmPin<peripherals::$inst> for crate::peripherals::$pin {}
+        impl crate::comp::SealedInmPin<peripherals::$inst> for crate::peripherals::$pin {
+            fn channel(&self) -> u8 {
+                $ch
+            }
+        }
+    };
+}
+
+// TODO: move the EXTI lines to py32-metapac
+macro_rules! impl_comp {
+    ($inst:ident, $index:expr, $line:expr) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            const INDEX: usize = $index;
+            const EXTI_LINE: usize = $line;
+
+            fn regs() -> crate::pac::comp::Comp {
+                crate::pac::$inst
+            }
+
+            fn waker() -> &'static AtomicWaker {
+                static WAKER: AtomicWaker = AtomicWaker::new();
+                &WAKER
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {
+            type Interrupt = crate::_generated::peripheral_interrupts::$inst::GLOBAL;
+        }
+    };
+}
+
+foreach_peripheral!(
+    (comp, COMP1) => {
+        impl_comp!(COMP1, 0, 17);
+    };
+    (comp, COMP2) => {
+        impl_comp!(COMP2, 1, 18);
+    };
+);
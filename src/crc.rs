@@ -0,0 +1,255 @@
+//! Cyclic Redundancy Check (CRC)
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use crate::pac::CRC as PAC_CRC;
+use crate::peripherals::CRC;
+use crate::{rcc, Peripheral};
+
+/// CRC-32 polynomial used by the unit.
+// TODO: move to py32-metapac
+pub const POLYNOMIAL: u32 = 0x04C1_1DB7;
+/// Value of the data register after a reset.
+pub const INIT: u32 = 0xFFFF_FFFF;
+
+/// CRC driver.
+///
+/// The PY32 CRC unit has a fixed polynomial ([`POLYNOMIAL`]) and initial value ([`INIT`]),
+/// takes 32-bit words MSB first and doesn't reflect the input or the output. Feeding data as
+/// bytes computes CRC-32/MPEG-2: the result of [`feed_words`](Crc::feed_words) on big-endian
+/// words is the same as [`feed_bytes`](Crc::feed_bytes) on their bytes.
+///
+/// For the reflected CRC-32 (zlib, Ethernet), reverse the bits of each input word with
+/// [`u32::reverse_bits`] and take the bitwise NOT of the reversed result.
+///
+/// With the `digest` feature, `Crc` implements the `digest` traits for CRC-32/MPEG-2 (check
+/// value `0x0376E6E7`), the output being the CRC in big-endian byte order. It is not the
+/// reflected CRC-32 that the `crc32` name usually refers to.
+pub struct Crc<'d> {
+    _peri: PeripheralRef<'d, CRC>,
+    tail: Tail,
+}
+
+/// Bytes of an incomplete word, the unit only takes whole words.
+struct Tail {
+    buf: [u8; 4],
+    len: usize,
+}
+
+impl Tail {
+    const fn new() -> Self {
+        Self {
+            buf: [0; 4],
+            len: 0,
+        }
+    }
+
+    /// Packs `bytes` MSB first after the buffered ones, passing each complete word to
+    /// `write_word` and keeping the remaining bytes.
+    fn push(&mut self, mut bytes: &[u8], mut write_word: impl FnMut(u32)) {
+        // Complete the buffered word first.
+        if self.len != 0 {
+            let n = bytes.len().min(4 - self.len);
+            self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+            self.len += n;
+            bytes = &bytes[n..];
+            if self.len < 4 {
+                return;
+            }
+            write_word(u32::from_be_bytes(self.buf));
+            self.len = 0;
+        }
+
+        let mut chunks = bytes.chunks_exact(4);
+        for chunk in &mut chunks {
+            write_word(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        }
+
+        let rem = chunks.remainder();
+        self.buf[..rem.len()].copy_from_slice(rem);
+        self.len = rem.len();
+    }
+
+    /// Adds the buffered bytes to `crc` in software.
+    fn fold(&self, crc: u32) -> u32 {
+        self.buf[..self.len]
+            .iter()
+            .fold(crc, |crc, byte| update_byte(crc, *byte))
+    }
+}
+
+impl<'d> Crc<'d> {
+    /// Instantiates the CRC32 peripheral and initializes it to default values.
+    pub fn new(peripheral: impl Peripheral<P = CRC> + 'd) -> Self {
+        into_ref!(peripheral);
+
+        rcc::enable_and_reset::<CRC>();
+        let mut instance = Self {
+            _peri: peripheral,
+            tail: Tail::new(),
+        };
+        instance.reset();
+        instance
+    }
+
+    /// Resets the CRC unit to [`INIT`] and drops buffered bytes.
+    pub fn reset(&mut self) {
+        PAC_CRC.cr().write(|w| w.set_reset(true));
+        self.tail.len = 0;
+    }
+
+    /// Feeds a word into the CRC peripheral. Returns the computed CRC.
+    pub fn feed_word(&mut self, word: u32) -> u32 {
+        self.feed_words(&[word])
+    }
+
+    /// Feeds a slice of words into the CRC peripheral. Returns the computed CRC.
+    pub fn feed_words(&mut self, words: &[u32]) -> u32 {
+        if self.tail.len != 0 {
+            // Keep the byte order of the stream, the words are not aligned to the unit anymore.
+            for word in words {
+                self.push_bytes(&word.to_be_bytes());
+            }
+        } else {
+            for word in words {
+                PAC_CRC.dr().write_value(*word);
+            }
+        }
+        self.read()
+    }
+
+    /// Feeds a slice of bytes into the CRC peripheral. Returns the computed CRC.
+    ///
+    /// Bytes are packed MSB first into words. A trailing incomplete word is kept in the driver
+    /// until more bytes arrive, [`read`](Crc::read) takes it into account.
+    pub fn feed_bytes(&mut self, bytes: &[u8]) -> u32 {
+        self.push_bytes(bytes);
+        self.read()
+    }
+
+    /// Reads the CRC of the data fed since the last reset.
+    pub fn read(&self) -> u32 {
+        self.tail.fold(PAC_CRC.dr().read())
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        self.tail.push(bytes, |word| PAC_CRC.dr().write_value(word));
+    }
+}
+
+impl<'d> Drop for Crc<'d> {
+    fn drop(&mut self) {
+        rcc::disable::<CRC>();
+    }
+}
+
+/// Software step of the unit, for bytes that don't fill a word.
+fn update_byte(mut crc: u32, byte: u8) -> u32 {
+    crc ^= (byte as u32) << 24;
+    for _ in 0..8 {
+        crc = if crc & 0x8000_0000 != 0 {
+            (crc << 1) ^ POLYNOMIAL
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+#[cfg(feature = "digest")]
+mod digest_impl {
+    use digest::consts::U4;
+    use digest::{
+        FixedOutput, FixedOutputReset, HashMarker, Output, OutputSizeUser, Reset, Update,
+    };
+
+    use super::Crc;
+
+    impl<'d> HashMarker for Crc<'d> {}
+
+    impl<'d> OutputSizeUser for Crc<'d> {
+        type OutputSize = U4;
+    }
+
+    impl<'d> Update for Crc<'d> {
+        fn update(&mut self, data: &[u8]) {
+            self.push_bytes(data);
+        }
+    }
+
+    impl<'d> FixedOutput for Crc<'d> {
+        fn finalize_into(self, out: &mut Output<Self>) {
+            out.copy_from_slice(&self.read().to_be_bytes());
+        }
+    }
+
+    impl<'d> Reset for Crc<'d> {
+        fn reset(&mut self) {
+            Crc::reset(self);
+        }
+    }
+
+    impl<'d> FixedOutputReset for Crc<'d> {
+        fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+            out.copy_from_slice(&self.read().to_be_bytes());
+            Crc::reset(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_byte, Tail, INIT};
+
+    /// CRC-32/MPEG-2 check value, the CRC of `b"123456789"`.
+    const CHECK: u32 = 0x0376_E6E7;
+
+    /// Software model of the unit: a word is its four bytes, MSB first.
+    fn update_word(crc: u32, word: u32) -> u32 {
+        word.to_be_bytes()
+            .iter()
+            .fold(crc, |crc, byte| update_byte(crc, *byte))
+    }
+
+    #[test]
+    fn test_update_byte() {
+        assert_eq!(update_byte(0, 0), 0);
+        assert_eq!(update_byte(0, 0x01), super::POLYNOMIAL);
+        let crc = b"123456789"
+            .iter()
+            .fold(INIT, |crc, byte| update_byte(crc, *byte));
+        assert_eq!(crc, CHECK);
+    }
+
+    #[test]
+    fn test_tail_split() {
+        let data = b"123456789";
+        for split in 0..=data.len() {
+            let mut tail = Tail::new();
+            let mut crc = INIT;
+            tail.push(&data[..split], |word| crc = update_word(crc, word));
+            tail.push(&data[split..], |word| crc = update_word(crc, word));
+            assert_eq!(tail.len, data.len() % 4);
+            assert_eq!(tail.fold(crc), CHECK, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn test_tail_bytewise() {
+        let mut tail = Tail::new();
+        let mut crc = INIT;
+        let mut words = 0;
+        for byte in b"123456789" {
+            tail.push(&[*byte], |word| {
+                crc = update_word(crc, word);
+                words += 1;
+            });
+        }
+        assert_eq!(words, 2);
+        assert_eq!(tail.fold(crc), CHECK);
+    }
+}
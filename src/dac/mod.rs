@@ -0,0 +1,590 @@
+//! Digital to Analog Converter (DAC)
+#![macro_use]
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use core::marker::PhantomData;
+use core::mem;
+
+use embassy_hal_internal::into_ref;
+
+use crate::dma::{ChannelAndRequest, TransferOptions, WritableRingBuffer};
+use crate::gpio::SealedPin as _;
+use crate::mode::{Async, Blocking, Mode as PeriMode};
+use crate::rcc::{self, RccPeripheral};
+use crate::Peripheral;
+
+mod tsel;
+pub use tsel::TriggerSel;
+
+/// Single 8 or 12 bit value that can be output by the DAC.
+///
+/// 12-bit values outside the permitted range are silently truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Value {
+    /// 8 bit value
+    Bit8(u8),
+    /// 12 bit value stored in a u16, left-aligned
+    Bit12Left(u16),
+    /// 12 bit value stored in a u16, right-aligned
+    Bit12Right(u16),
+}
+
+/// Dual 8 or 12 bit values that can be output by the DAC channels 1 and 2 simultaneously.
+///
+/// 12-bit values outside the permitted range are silently truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DualValue {
+    /// 8 bit value
+    Bit8(u8, u8),
+    /// 12 bit value stored in a u16, left-aligned
+    Bit12Left(u16, u16),
+    /// 12 bit value stored in a u16, right-aligned
+    Bit12Right(u16, u16),
+}
+
+/// Array variant of [`Value`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ValueArray<'a> {
+    /// 8 bit values
+    Bit8(&'a [u8]),
+    /// 12 bit value stored in a u16, left-aligned
+    Bit12Left(&'a [u16]),
+    /// 12 bit values stored in a u16, right-aligned
+    Bit12Right(&'a [u16]),
+}
+
+/// Alignment of the 12-bit samples of a [`RingBufferedDacChannel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alignment {
+    /// The 12 bits are the most significant bits of the u16.
+    Left,
+    /// The 12 bits are the least significant bits of the u16.
+    Right,
+}
+
+/// Wave added to the data holding register on every trigger.
+///
+/// The wave generator needs the trigger to be enabled, see [`DacChannel::set_triggering`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wave {
+    /// No wave generation.
+    Disabled,
+    /// Pseudo-random noise, with the given number of unmasked LFSR bits (1 to 12).
+    Noise(u8),
+    /// Triangle wave with an amplitude of `2^n - 1`, for `n` from 1 to 12.
+    Triangle(u8),
+}
+
+/// Driver for a single DAC channel.
+///
+/// If you want to use both channels, either together or independently,
+/// create a [`Dac`] first and use it to access each channel.
+pub struct DacChannel<'d, T: Instance, const N: u8, M: PeriMode> {
+    phantom: PhantomData<&'d mut (T, M)>,
+    dma: Option<ChannelAndRequest<'d>>,
+}
+
+/// DAC channel 1 type alias.
+pub type DacCh1<'d, T, M> = DacChannel<'d, T, 1, M>;
+/// DAC channel 2 type alias.
+pub type DacCh2<'d, T, M> = DacChannel<'d, T, 2, M>;
+
+impl<'d, T: Instance, const N: u8> DacChannel<'d, T, N, Async> {
+    /// Create a new `DacChannel` instance, consuming the underlying DAC peripheral.
+    ///
+    /// The channel is enabled on creation and begins to drive the output pin.
+    /// Note that some methods, such as `set_trigger()` and `set_output_buffer()`, will
+    /// disable the channel; you must re-enable it with `enable()`.
+    ///
+    /// By default, triggering is disabled.
+    pub fn new(
+        _peri: impl Peripheral<P = T> + 'd,
+        dma: impl Peripheral<P = impl Dma<T, N>> + 'd,
+        pin: impl Peripheral<P = impl DacPin<T, N>> + 'd,
+    ) -> Self {
+        into_ref!(dma, pin);
+        pin.set_as_analog();
+        rcc::enable_and_reset::<T>();
+        let request = dma.request();
+        let dma = Some(ChannelAndRequest {
+            channel: dma.map_into(),
+            request,
+        });
+        Self::new_inner(dma)
+    }
+
+    /// Write `data` to this channel via DMA, one value per trigger.
+    ///
+    /// With `circular`, the data is output repeatedly and this function only returns when
+    /// the future is dropped. For a waveform that can be updated while it plays, see
+    /// [`into_ring_buffered`](Self::into_ring_buffered).
+    ///
+    /// Note that the channel is disabled when this function returns.
+    pub async fn write(&mut self, data: ValueArray<'_>, circular: bool) {
+        let idx = Self::IDX;
+        let dma = unwrap!(self.dma.as_mut());
+
+        T::regs().cr().modify(|w| w.set_dmaen(idx, true));
+        self.enable();
+
+        let options = TransferOptions {
+            circular,
+            complete_transfer_ir: !circular,
+            ..Default::default()
+        };
+
+        let tx_f = match data {
+            ValueArray::Bit8(buf) => unsafe {
+                dma.write(buf, T::regs().dhr8r(idx).as_ptr() as *mut u8, options)
+            },
+            ValueArray::Bit12Left(buf) => unsafe {
+                dma.write(buf, T::regs().dhr12l(idx).as_ptr() as *mut u16, options)
+            },
+            ValueArray::Bit12Right(buf) => unsafe {
+                dma.write(buf, T::regs().dhr12r(idx).as_ptr() as *mut u16, options)
+            },
+        };
+
+        tx_f.await;
+
+        T::regs().cr().modify(|w| {
+            w.set_en(idx, false);
+            w.set_dmaen(idx, false);
+        });
+    }
+
+    /// Output the samples written to a DMA ring buffer, one per trigger.
+    ///
+    /// The trigger must be configured beforehand, with [`set_trigger`](Self::set_trigger) and
+    /// [`set_triggering`](Self::set_triggering).
+    pub fn into_ring_buffered(
+        mut self,
+        dma_buf: &'d mut [u16],
+        alignment: Alignment,
+    ) -> RingBufferedDacChannel<'d, T, N> {
+        assert!(!dma_buf.is_empty() && dma_buf.len() <= 0xFFFF);
+        let idx = Self::IDX;
+        let dma = unwrap!(self.dma.take());
+
+        let options = TransferOptions {
+            half_transfer_ir: true,
+            ..Default::default()
+        };
+
+        let tx_dst = match alignment {
+            Alignment::Left => T::regs().dhr12l(idx).as_ptr() as *mut u16,
+            Alignment::Right => T::regs().dhr12r(idx).as_ptr() as *mut u16,
+        };
+
+        let ring_buf =
+            unsafe { WritableRingBuffer::new(dma.channel, dma.request, tx_dst, dma_buf, options) };
+
+        // Keep the channel configuration and clock.
+        mem::forget(self);
+
+        RingBufferedDacChannel {
+            _phantom: PhantomData,
+            ring_buf,
+        }
+    }
+}
+
+impl<'d, T: Instance, const N: u8> DacChannel<'d, T, N, Blocking> {
+    /// Create a new `DacChannel` instance, consuming the underlying DAC peripheral.
+    ///
+    /// The channel is enabled on creation and begins to drive the output pin.
+    /// Note that some methods, such as `set_trigger()` and `set_output_buffer()`, will
+    /// disable the channel; you must re-enable it with `enable()`.
+    ///
+    /// By default, triggering is disabled.
+    pub fn new_blocking(
+        _peri: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl DacPin<T, N>> + 'd,
+    ) -> Self {
+        into_ref!(pin);
+        pin.set_as_analog();
+        rcc::enable_and_reset::<T>();
+        Self::new_inner(None)
+    }
+}
+
+impl<'d, T: Instance, const N: u8, M: PeriMode> DacChannel<'d, T, N, M> {
+    const IDX: usize = (N - 1) as usize;
+
+    fn new_inner(dma: Option<ChannelAndRequest<'d>>) -> Self {
+        let mut dac = Self {
+            phantom: PhantomData,
+            dma,
+        };
+        dac.enable();
+        dac
+    }
+
+    /// Enable or disable this channel.
+    pub fn set_enable(&mut self, on: bool) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| w.set_en(Self::IDX, on));
+        });
+    }
+
+    /// Enable this channel.
+    pub fn enable(&mut self) {
+        self.set_enable(true)
+    }
+
+    /// Disable this channel.
+    pub fn disable(&mut self) {
+        self.set_enable(false)
+    }
+
+    /// Set the trigger source for this channel.
+    ///
+    /// This method disables the channel, so you may need to re-enable afterwards.
+    pub fn set_trigger(&mut self, source: TriggerSel) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| {
+                w.set_en(Self::IDX, false);
+                w.set_tsel(Self::IDX, source as u8);
+            });
+        });
+    }
+
+    /// Enable or disable triggering for this channel.
+    ///
+    /// When disabled, a value written to the channel is output one APB clock later. When
+    /// enabled, it is output on the next trigger.
+    pub fn set_triggering(&mut self, on: bool) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| w.set_ten(Self::IDX, on));
+        });
+    }
+
+    /// Software trigger this channel.
+    pub fn trigger(&mut self) {
+        T::regs().swtrigr().write(|w| w.set_swtrig(Self::IDX, true));
+    }
+
+    /// Enable or disable the output buffer of this channel.
+    ///
+    /// The buffer lowers the output impedance, at the cost of a higher consumption.
+    /// This method disables the channel, so you may need to re-enable afterwards.
+    pub fn set_output_buffer(&mut self, on: bool) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| {
+                w.set_en(Self::IDX, false);
+                w.set_boff(Self::IDX, !on);
+            });
+        });
+    }
+
+    /// Set the wave generated on top of the output value.
+    ///
+    /// Panics if the noise or triangle parameter is not in `1..=12`.
+    pub fn set_wave(&mut self, wave: Wave) {
+        // TODO: move to py32-metapac
+        let (wave, mamp) = match wave {
+            Wave::Disabled => (0, 0),
+            Wave::Noise(n) => (1, n),
+            Wave::Triangle(n) => (2, n),
+        };
+        if wave != 0 {
+            assert!((1..=12).contains(&mamp));
+        }
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| {
+                w.set_wave(Self::IDX, wave);
+                w.set_mamp(Self::IDX, mamp.saturating_sub(1));
+            });
+        });
+    }
+
+    /// Write a new value to this channel.
+    ///
+    /// If triggering is not enabled, the new value is immediately output; otherwise,
+    /// it will be output after the next trigger.
+    pub fn set(&mut self, value: Value) {
+        match value {
+            Value::Bit8(v) => T::regs().dhr8r(Self::IDX).write(|w| w.set_dhr(v)),
+            Value::Bit12Left(v) => T::regs().dhr12l(Self::IDX).write(|w| w.set_dhr(v)),
+            Value::Bit12Right(v) => T::regs().dhr12r(Self::IDX).write(|w| w.set_dhr(v)),
+        }
+    }
+
+    /// Read the current output value of the DAC.
+    pub fn read(&self) -> u16 {
+        T::regs().dor(Self::IDX).read().dor()
+    }
+}
+
+impl<'d, T: Instance, const N: u8, M: PeriMode> Drop for DacChannel<'d, T, N, M> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| {
+                w.set_en(Self::IDX, false);
+                w.set_dmaen(Self::IDX, false);
+            });
+        });
+        rcc::disable::<T>();
+    }
+}
+
+/// DAC driver.
+///
+/// Use this struct when you want to use both channels, either together or independently.
+pub struct Dac<'d, T: Instance, M: PeriMode> {
+    ch1: DacChannel<'d, T, 1, M>,
+    ch2: DacChannel<'d, T, 2, M>,
+}
+
+impl<'d, T: Instance> Dac<'d, T, Async> {
+    /// Create a new `Dac` instance, consuming the underlying DAC peripheral.
+    ///
+    /// This struct allows you to access both channels of the DAC, where available. You can
+    /// either call `split()` to obtain separate `DacChannel`s, or use methods on `Dac` to use
+    /// the two channels together.
+    ///
+    /// The channels are enabled on creation and begin to drive their output pins.
+    pub fn new(
+        _peri: impl Peripheral<P = T> + 'd,
+        dma_ch1: impl Peripheral<P = impl Dma<T, 1>> + 'd,
+        dma_ch2: impl Peripheral<P = impl Dma<T, 2>> + 'd,
+        pin_ch1: impl Peripheral<P = impl DacPin<T, 1>> + 'd,
+        pin_ch2: impl Peripheral<P = impl DacPin<T, 2>> + 'd,
+    ) -> Self {
+        into_ref!(dma_ch1, dma_ch2, pin_ch1, pin_ch2);
+        pin_ch1.set_as_analog();
+        pin_ch2.set_as_analog();
+
+        // Enable twice to increment the DAC refcount for each channel.
+        rcc::enable_and_reset::<T>();
+        rcc::enable_and_reset::<T>();
+
+        let request = dma_ch1.request();
+        let dma_ch1 = Some(ChannelAndRequest {
+            channel: dma_ch1.map_into(),
+            request,
+        });
+        let request = dma_ch2.request();
+        let dma_ch2 = Some(ChannelAndRequest {
+            channel: dma_ch2.map_into(),
+            request,
+        });
+
+        Self {
+            ch1: DacChannel::new_inner(dma_ch1),
+            ch2: DacChannel::new_inner(dma_ch2),
+        }
+    }
+}
+
+impl<'d, T: Instance> Dac<'d, T, Blocking> {
+    /// Create a new `Dac` instance, consuming the underlying DAC peripheral.
+    ///
+    /// The channels are enabled on creation and begin to drive their output pins.
+    pub fn new_blocking(
+        _peri: impl Peripheral<P = T> + 'd,
+        pin_ch1: impl Peripheral<P = impl DacPin<T, 1>> + 'd,
+        pin_ch2: impl Peripheral<P = impl DacPin<T, 2>> + 'd,
+    ) -> Self {
+        into_ref!(pin_ch1, pin_ch2);
+        pin_ch1.set_as_analog();
+        pin_ch2.set_as_analog();
+
+        // Enable twice to increment the DAC refcount for each channel.
+        rcc::enable_and_reset::<T>();
+        rcc::enable_and_reset::<T>();
+
+        Self {
+            ch1: DacChannel::new_inner(None),
+            ch2: DacChannel::new_inner(None),
+        }
+    }
+}
+
+impl<'d, T: Instance, M: PeriMode> Dac<'d, T, M> {
+    /// Split this `Dac` into separate channels.
+    ///
+    /// You can access and move the channels around separately after splitting.
+    pub fn split(self) -> (DacCh1<'d, T, M>, DacCh2<'d, T, M>) {
+        (self.ch1, self.ch2)
+    }
+
+    /// Temporarily access channel 1.
+    pub fn ch1(&mut self) -> &mut DacCh1<'d, T, M> {
+        &mut self.ch1
+    }
+
+    /// Temporarily access channel 2.
+    pub fn ch2(&mut self) -> &mut DacCh2<'d, T, M> {
+        &mut self.ch2
+    }
+
+    /// Simultaneously update channels 1 and 2 with a new value.
+    ///
+    /// If triggering is not enabled, the new values are immediately output;
+    /// otherwise, they will be output after the next trigger.
+    pub fn set(&mut self, values: DualValue) {
+        match values {
+            DualValue::Bit8(v1, v2) => T::regs().dhr8rd().write(|reg| {
+                reg.set_dhr(0, v1);
+                reg.set_dhr(1, v2);
+            }),
+            DualValue::Bit12Left(v1, v2) => T::regs().dhr12ld().write(|reg| {
+                reg.set_dhr(0, v1);
+                reg.set_dhr(1, v2);
+            }),
+            DualValue::Bit12Right(v1, v2) => T::regs().dhr12rd().write(|reg| {
+                reg.set_dhr(0, v1);
+                reg.set_dhr(1, v2);
+            }),
+        }
+    }
+
+    /// Software trigger both channels at the same time.
+    pub fn trigger(&mut self) {
+        T::regs().swtrigr().write(|w| {
+            w.set_swtrig(0, true);
+            w.set_swtrig(1, true);
+        });
+    }
+}
+
+/// The DMA read past the last written sample, the output repeated old samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UnderrunError;
+
+/// DAC channel fed from a DMA ring buffer, for continuous waveform output.
+///
+/// The DMA moves one sample to the channel on every trigger, so the trigger sets the
+/// sample rate. Call [`start`](Self::start) after filling the buffer with
+/// [`write_immediate`](Self::write_immediate), then keep it filled with
+/// [`write_exact`](Self::write_exact).
+pub struct RingBufferedDacChannel<'d, T: Instance, const N: u8> {
+    _phantom: PhantomData<T>,
+    ring_buf: WritableRingBuffer<'d, u16>,
+}
+
+impl<'d, T: Instance, const N: u8> RingBufferedDacChannel<'d, T, N> {
+    const IDX: usize = (N - 1) as usize;
+
+    /// Start the output.
+    pub fn start(&mut self) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| {
+                w.set_dmaen(Self::IDX, true);
+                w.set_en(Self::IDX, true);
+            });
+        });
+        self.ring_buf.start();
+    }
+
+    /// Write samples to the buffer before [`start`](Self::start).
+    ///
+    /// Returns the number of samples written.
+    pub fn write_immediate(&mut self, buf: &[u16]) -> Result<usize, UnderrunError> {
+        self.ring_buf
+            .write_immediate(buf)
+            .map(|(written, _)| written)
+            .map_err(|_| UnderrunError)
+    }
+
+    /// Write as many samples as fit in the free part of the buffer.
+    ///
+    /// Returns the number of samples written.
+    pub fn write(&mut self, buf: &[u16]) -> Result<usize, UnderrunError> {
+        self.ring_buf
+            .write(buf)
+            .map(|(written, _)| written)
+            .map_err(|_| UnderrunError)
+    }
+
+    /// Write all the samples, waiting for the DMA to free space in the buffer.
+    pub async fn write_exact(&mut self, buf: &[u16]) -> Result<usize, UnderrunError> {
+        self.ring_buf
+            .write_exact(buf)
+            .await
+            .map_err(|_| UnderrunError)
+    }
+
+    /// The capacity of the buffer, in samples.
+    pub const fn capacity(&self) -> usize {
+        self.ring_buf.capacity()
+    }
+
+    /// Output the remaining samples, then stop the DMA.
+    pub async fn stop(&mut self) {
+        self.ring_buf.stop().await;
+        T::regs().cr().modify(|w| w.set_dmaen(Self::IDX, false));
+    }
+}
+
+impl<'d, T: Instance, const N: u8> Drop for RingBufferedDacChannel<'d, T, N> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            T::regs().cr().modify(|w| {
+                w.set_en(Self::IDX, false);
+                w.set_dmaen(Self::IDX, false);
+            });
+        });
+        rcc::disable::<T>();
+    }
+}
+
+trait SealedInstance {
+    fn regs() -> crate::pac::dac::Dac;
+}
+
+/// DAC instance.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance + RccPeripheral + Peripheral<P = Self> + 'static {}
+
+dma_trait!(DacDma1, Instance);
+dma_trait!(DacDma2, Instance);
+
+/// DMA channel usable by the DAC channel `N`.
+pub trait Dma<T: Instance, const N: u8>: crate::dma::Channel {
+    /// Get the DMA request number needed to use this channel.
+    fn request(&self) -> crate::dma::Request;
+}
+
+impl<T: Instance, D: DacDma1<T>> Dma<T, 1> for D {
+    fn request(&self) -> crate::dma::Request {
+        DacDma1::request(self)
+    }
+}
+
+impl<T: Instance, D: DacDma2<T>> Dma<T, 2> for D {
+    fn request(&self) -> crate::dma::Request {
+        DacDma2::request(self)
+    }
+}
+
+/// Marks a pin that can be used with the DAC channel `N`.
+pub trait DacPin<T: Instance, const N: u8>: crate::gpio::Pin + 'static {}
+
+foreach_peripheral!(
+    (dac, $inst:ident) => {
+        impl crate::dac::SealedInstance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::dac::Dac {
+                crate::pac::$inst
+            }
+        }
+
+        impl crate::dac::Instance for crate::peripherals::$inst {}
+    };
+);
+
+macro_rules! impl_dac_pin {
+    ($inst:ident, $pin:ident, $ch:expr) => {
+        impl crate::dac::DacPin<peripherals::$inst, $ch> for crate::peripherals::$pin {}
+    };
+}
@@ -0,0 +1,24 @@
+/// Trigger selection for the DAC channels.
+///
+/// The trigger starts a conversion: the data holding register is moved to the output, and
+/// the wave generator steps.
+// TODO: move to py32-metapac
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum TriggerSel {
+    /// TIM6 TRGO
+    Tim6 = 0,
+    /// TIM3 TRGO
+    Tim3 = 1,
+    /// TIM7 TRGO
+    Tim7 = 2,
+    /// TIM15 TRGO
+    Tim15 = 3,
+    /// TIM2 TRGO
+    Tim2 = 4,
+    /// EXTI line 9, the pin must be set up with [`ExtiInput`](crate::exti::ExtiInput).
+    Exti9 = 6,
+    /// Software trigger, see [`DacChannel::trigger`](super::DacChannel::trigger).
+    Software = 7,
+}
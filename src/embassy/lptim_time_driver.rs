@@ -0,0 +1,373 @@
+//! Time driver based on the LPTIM
+//!
+//! Unlike the TIM and SysTick drivers, the LPTIM is clocked from the LSI or LSE and keeps
+//! counting in Stop mode, so timers keep running and wake the chip up from Stop mode.
+//!
+//! The LPTIM clock must be selected with `config.rcc.mux.lptimsel = Lptimsel::LSI` (or `LSE`),
+//! with that oscillator enabled in `config.rcc.ls`. PCLK stops in Stop mode, so initialization
+//! panics if the LPTIM is clocked from it.
+//!
+//! The 16-bit counter is extended to 64 bits by counting its overflows, and alarms use the
+//! compare register.
+//!
+//! # Tick rate
+//!
+//! The counter clock is the LPTIM clock divided by a power of two up to 128, so the
+//! `embassy-time` tick rate (`tick-hz-*` feature) must be one of these divisions:
+//!
+//! | `tick-hz-*` (32.768 kHz clock) | resolution | overflow interrupt every |
+//! |--------------------------------|------------|--------------------------|
+//! | 32_768                         | 30.5 µs    | 2 s                      |
+//! | 16_384                         | 61 µs      | 4 s                      |
+//! | 4_096                          | 244 µs     | 16 s                     |
+//! | 1_024                          | 977 µs     | 64 s                     |
+//! | 256                            | 3.9 ms     | 256 s                    |
+//!
+//! Lower tick rates wake the chip up less often to count overflows, at the cost of a
+//! coarser timer resolution. The overflow interrupt may block for up to one tick. Each compare write is synchronized to the LPTIM clock and
+//! blocks for a few LPTIM clock cycles (about 100 µs at 32.768 kHz).
+//!
+//! The LSI is less accurate than the LSE crystal, prefer the LSE when long delays
+//! must be precise.
+
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use critical_section::CriticalSection;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time_driver::{Driver, TICK_HZ};
+use embassy_time_queue_utils::Queue;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::lptim::vals::Presc;
+use crate::pac::lptim::Lptim;
+use crate::pac::rcc::vals::Lptimsel;
+use crate::rcc::{self, SealedRccPeripheral};
+use crate::{interrupt, peripherals};
+
+#[cfg(time_driver_lptim)]
+type T = peripherals::LPTIM;
+#[cfg(time_driver_lptim1)]
+type T = peripherals::LPTIM1;
+
+#[cfg(time_driver_lptim)]
+fn regs() -> Lptim {
+    crate::pac::LPTIM
+}
+#[cfg(time_driver_lptim1)]
+fn regs() -> Lptim {
+    crate::pac::LPTIM1
+}
+
+/// The compare register must be lower than the autoreload register.
+const CMP_MAX: u16 = u16::MAX - 1;
+
+foreach_interrupt! {
+    (LPTIM, lptim, $block:ident, GLOBAL, $irq:ident) => {
+        #[cfg(time_driver_lptim)]
+        type Irq = crate::interrupt::typelevel::$irq;
+
+        #[cfg(time_driver_lptim)]
+        #[cfg(feature = "rt")]
+        #[interrupt]
+        fn $irq() {
+            DRIVER.on_interrupt()
+        }
+    };
+    (LPTIM1, lptim, $block:ident, GLOBAL, $irq:ident) => {
+        #[cfg(time_driver_lptim1)]
+        type Irq = crate::interrupt::typelevel::$irq;
+
+        #[cfg(time_driver_lptim1)]
+        #[cfg(feature = "rt")]
+        #[interrupt]
+        fn $irq() {
+            DRIVER.on_interrupt()
+        }
+    };
+}
+
+// The counter runs from 0 to ARR = 0xFFFF, and ARRM is set when it reaches 0xFFFF.
+// To line the overflow flag up with the wrap of the low 16 bits of the timestamp, the timestamp is
+// `(period << 16) + (counter + 1) % 0x10000`: counter 0xFFFF is the first tick of the next period.
+//
+// `period` is only incremented in the interrupt, so `now()` adds one period when ARRM is pending.
+// It runs in a critical section, so the interrupt can't clear the flag between the reads.
+// ARRM may also lag the counter reaching 0xFFFF, so that count is always in the next period. The
+// interrupt waits for the counter to move on, so that the new period isn't counted twice.
+fn calc_now(period: u32, overflow_pending: bool, counter: u16) -> u64 {
+    let next_period = overflow_pending || counter == u16::MAX;
+    ((period as u64 + next_period as u64) << 16) + counter.wrapping_add(1) as u64
+}
+
+/// Counter value at which the low 16 bits of the timestamp are `low`.
+fn counter_for(low: u16) -> u16 {
+    low.wrapping_sub(1)
+}
+
+/// Read the counter, which runs from an asynchronous clock.
+fn read_counter(r: Lptim) -> u16 {
+    loop {
+        let cnt = r.cnt().read().cnt();
+        if r.cnt().read().cnt() == cnt {
+            return cnt;
+        }
+    }
+}
+
+pub(crate) struct LptimDriver {
+    /// Number of counter overflows since boot.
+    period: AtomicU32,
+    alarm: Mutex<CriticalSectionRawMutex, Cell<u64>>,
+    /// Last value written to the compare register.
+    cmp: Mutex<CriticalSectionRawMutex, Cell<u16>>,
+    queue: Mutex<CriticalSectionRawMutex, RefCell<Queue>>,
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: LptimDriver = LptimDriver {
+    period: AtomicU32::new(0),
+    alarm: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(u64::MAX)),
+    cmp: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(CMP_MAX)),
+    queue: Mutex::new(RefCell::new(Queue::new()))
+});
+
+impl LptimDriver {
+    fn init(&'static self, cs: CriticalSection) {
+        let r = regs();
+
+        // PCLK stops in Stop mode, only the LSI and LSE keep the counter running.
+        assert!(
+            matches!(
+                crate::pac::RCC.ccipr().read().lptimsel(),
+                Lptimsel::LSI | Lptimsel::LSE
+            ),
+            "time-driver-lptim needs the LSI or LSE as LPTIM clock, set config.rcc.mux.lptimsel"
+        );
+
+        rcc::enable_and_reset_with_cs::<T>(cs);
+
+        let clk = T::frequency().0 as u64;
+        let div = clk / TICK_HZ;
+        assert!(
+            div * TICK_HZ == clk && div.is_power_of_two() && div <= 128,
+            "LPTIM clock {} Hz can not be divided down to TICK_HZ",
+            clk
+        );
+
+        // CFGR and IER can only be written while the LPTIM is disabled.
+        r.cr().modify(|w| w.set_enable(false));
+        r.cfgr()
+            .modify(|w| w.set_presc(Presc::from_bits(div.trailing_zeros() as u8)));
+        r.ier().write(|w| {
+            w.set_arrmie(true);
+            w.set_cmpmie(true);
+        });
+
+        // ARR and CMP can only be written while the LPTIM is enabled.
+        r.cr().modify(|w| w.set_enable(true));
+        r.arr().write(|w| w.set_arr(u16::MAX));
+        while !r.isr().read().arrok() {}
+        r.icr().write(|w| w.set_arrokcf(true));
+        r.cmp().write(|w| w.set_cmp(CMP_MAX));
+        while !r.isr().read().cmpok() {}
+        r.icr().write(|w| w.set_cmpokcf(true));
+
+        // The LPTIM event wakes the chip up from Stop mode through its EXTI line.
+        crate::pac::EXTI
+            .imr()
+            .modify(|w| w.set_line(crate::lptim::WAKEUP_EXTI_LINE, true));
+
+        Irq::unpend();
+        unsafe { Irq::enable() };
+
+        r.cr().modify(|w| w.set_cntstrt(true));
+    }
+
+    fn on_interrupt(&self) {
+        let r = regs();
+
+        critical_section::with(|cs| {
+            let isr = r.isr().read();
+            r.icr().write(|w| {
+                w.set_arrmcf(isr.arrm());
+                w.set_cmpmcf(isr.cmpm());
+            });
+
+            if isr.arrm() {
+                // We only modify the period from the interrupt, so we know this can't race.
+                let period = self.period.load(Ordering::Relaxed) + 1;
+                self.period.store(period, Ordering::Relaxed);
+
+                // See `calc_now`, the counter must not read 0xFFFF with the new period.
+                while read_counter(r) == u16::MAX {}
+
+                // An alarm in the new period may not be armed yet.
+                self.arm(cs, self.alarm.borrow(cs).get());
+            }
+
+            if (isr.arrm() || isr.cmpm()) && self.alarm.borrow(cs).get() <= self.now() {
+                self.trigger_alarm(cs);
+            }
+        });
+
+        #[cfg(feature = "low-power")]
+        unsafe {
+            crate::low_power::on_wakeup_irq()
+        };
+    }
+
+    /// Point the compare register at `timestamp` if it is less than a period away, otherwise park it.
+    fn arm(&self, cs: CriticalSection, timestamp: u64) {
+        let now = self.now();
+        let cmp = if timestamp > now && timestamp - now < 0x1_0000 {
+            // The compare register can't match ARR, the overflow interrupt fires at that count.
+            counter_for(timestamp as u16).min(CMP_MAX)
+        } else {
+            CMP_MAX
+        };
+
+        let last = self.cmp.borrow(cs);
+        if last.get() != cmp {
+            let r = regs();
+            r.cmp().write(|w| w.set_cmp(cmp));
+            // The write only takes effect once synchronized to the LPTIM clock.
+            while !r.isr().read().cmpok() {}
+            r.icr().write(|w| w.set_cmpokcf(true));
+            last.set(cmp);
+        }
+    }
+
+    fn trigger_alarm(&self, cs: CriticalSection) {
+        let mut next = self
+            .queue
+            .borrow(cs)
+            .borrow_mut()
+            .next_expiration(self.now());
+        while !self.set_alarm(cs, next) {
+            next = self
+                .queue
+                .borrow(cs)
+                .borrow_mut()
+                .next_expiration(self.now());
+        }
+    }
+
+    fn set_alarm(&self, cs: CriticalSection, timestamp: u64) -> bool {
+        self.alarm.borrow(cs).set(timestamp);
+
+        if timestamp <= self.now() {
+            // If alarm timestamp has passed the alarm will not fire.
+            // Disarm the alarm and return `false` to indicate that.
+            self.alarm.borrow(cs).set(u64::MAX);
+            self.arm(cs, u64::MAX);
+            return false;
+        }
+
+        self.arm(cs, timestamp);
+
+        // Reevaluate if the alarm timestamp is still in the future, the counter may have
+        // gone past the compare value while it was being synchronized.
+        if timestamp <= self.now() {
+            self.alarm.borrow(cs).set(u64::MAX);
+            self.arm(cs, u64::MAX);
+            return false;
+        }
+
+        // We're confident the alarm will ring in the future.
+        true
+    }
+
+    /*
+        Low-power functions, used by the low-power executor
+    */
+
+    #[cfg(feature = "low-power")]
+    /// The LPTIM keeps counting in Stop mode, so time never needs to be paused.
+    pub(crate) fn pause_time(&self) -> Result<(), ()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "low-power")]
+    /// The LPTIM keeps counting in Stop mode, so there is nothing to resume.
+    pub(crate) fn resume_time(&self) {}
+}
+
+impl Driver for LptimDriver {
+    fn now(&self) -> u64 {
+        let r = regs();
+
+        critical_section::with(|_| {
+            let period = self.period.load(Ordering::Relaxed);
+            loop {
+                let pending = r.isr().read().arrm();
+                let counter = read_counter(r);
+                // Retry if the counter overflowed between the reads.
+                if r.isr().read().arrm() == pending {
+                    return calc_now(period, pending, counter);
+                }
+            }
+        })
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &core::task::Waker) {
+        critical_section::with(|cs| {
+            let mut queue = self.queue.borrow(cs).borrow_mut();
+
+            if queue.schedule_wake(at, waker) {
+                let mut next = queue.next_expiration(self.now());
+                while !self.set_alarm(cs, next) {
+                    next = queue.next_expiration(self.now());
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "low-power")]
+pub(crate) fn get_driver() -> &'static LptimDriver {
+    &DRIVER
+}
+
+pub(crate) fn init(cs: CriticalSection) {
+    DRIVER.init(cs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calc_now_around_wrap() {
+        let period = 5;
+        let base = (period as u64) << 16;
+
+        // Counter 0xFFFE, then 0xFFFF before and after ARRM is set, then the interrupt.
+        let sequence = [
+            calc_now(period, false, 0xFFFE),
+            calc_now(period, false, 0xFFFF),
+            calc_now(period, true, 0xFFFF),
+            calc_now(period, true, 0),
+            calc_now(period + 1, false, 0),
+            calc_now(period + 1, false, 1),
+        ];
+        assert_eq!(
+            sequence,
+            [
+                base + 0xFFFF,
+                base + 0x1_0000,
+                base + 0x1_0000,
+                base + 0x1_0001,
+                base + 0x1_0001,
+                base + 0x1_0002,
+            ]
+        );
+    }
+
+    #[test]
+    fn counter_for_matches_calc_now() {
+        for low in [0u16, 1, 0x7FFF, 0xFFFE, 0xFFFF] {
+            assert_eq!(calc_now(0, false, counter_for(low)) as u16, low);
+        }
+    }
+}
@@ -1,5 +1,11 @@
-#[cfg(all(feature = "_time-driver", not(feature = "time-driver-systick")))]
+#[cfg(all(
+    feature = "_time-driver",
+    not(any(feature = "time-driver-systick", feature = "time-driver-lptim"))
+))]
 pub mod time_driver;
 
 #[cfg(feature = "time-driver-systick")]
-pub mod systick_time_driver;
\ No newline at end of file
+pub mod systick_time_driver;
+
+#[cfg(feature = "time-driver-lptim")]
+pub mod lptim_time_driver;
@@ -17,8 +17,8 @@ use py32_metapac::timer::{regs, TimGp16};
 use crate::interrupt::typelevel::Interrupt;
 use crate::pac::timer::vals;
 use crate::rcc::{self, SealedRccPeripheral};
-// #[cfg(feature = "low-power")]
-// use crate::rtc::Rtc;
+#[cfg(feature = "low-power")]
+use crate::rtc::Rtc;
 use crate::timer::{CoreInstance, GeneralInstance1Channel};
 use crate::{interrupt, peripherals};
 
@@ -216,16 +216,16 @@ pub(crate) struct RtcDriver {
     /// Number of 2^15 periods elapsed since boot.
     period: AtomicU32,
     alarm: Mutex<CriticalSectionRawMutex, AlarmState>,
-    // #[cfg(feature = "low-power")]
-    // rtc: Mutex<CriticalSectionRawMutex, Cell<Option<&'static Rtc>>>,
+    #[cfg(feature = "low-power")]
+    rtc: Mutex<CriticalSectionRawMutex, Cell<Option<&'static Rtc<'static>>>>,
     queue: Mutex<CriticalSectionRawMutex, RefCell<Queue>>,
 }
 
 embassy_time_driver::time_driver_impl!(static DRIVER: RtcDriver = RtcDriver {
     period: AtomicU32::new(0),
     alarm: Mutex::const_new(CriticalSectionRawMutex::new(), AlarmState::new()),
-    // #[cfg(feature = "low-power")]
-    // rtc: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None)),
+    #[cfg(feature = "low-power")]
+    rtc: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None)),
     queue: Mutex::new(RefCell::new(Queue::new()))
 });
 
@@ -267,6 +267,12 @@ impl RtcDriver {
         unsafe { <T as GeneralInstance1Channel>::CaptureCompareInterrupt::enable() };
 
         r.cr1().modify(|w| w.set_cen(true));
+
+        // The timer is paused while in Stop mode, so it doesn't keep the executor out of it.
+        #[cfg(feature = "low-power")]
+        unsafe {
+            rcc::REFCOUNT_STOP1 -= 1;
+        }
     }
 
     fn on_interrupt(&self) {
@@ -339,123 +345,110 @@ impl RtcDriver {
         Low-power private functions: all operate within a critical seciton
     */
 
-    // #[cfg(feature = "low-power")]
-    // /// Compute the approximate amount of time until the next alarm
-    // fn time_until_next_alarm(&self, cs: CriticalSection) -> embassy_time::Duration {
-    //     let now = self.now() + 32;
-
-    //     embassy_time::Duration::from_ticks(self.alarm.borrow(cs).timestamp.get().saturating_sub(now))
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// Add the given offset to the current time
-    // fn add_time(&self, offset: embassy_time::Duration, cs: CriticalSection) {
-    //     let offset = offset.as_ticks();
-    //     let cnt = regs_gp16().cnt().read().cnt() as u32;
-    //     let period = self.period.load(Ordering::SeqCst);
-
-    //     // Correct the race, if it exists
-    //     let period = if period & 1 == 1 && cnt < u16::MAX as u32 / 2 {
-    //         period + 1
-    //     } else {
-    //         period
-    //     };
-
-    //     // Normalize to the full overflow
-    //     let period = (period / 2) * 2;
-
-    //     // Add the offset
-    //     let period = period + 2 * (offset / u16::MAX as u64) as u32;
-    //     let cnt = cnt + (offset % u16::MAX as u64) as u32;
-
-    //     let (cnt, period) = if cnt > u16::MAX as u32 {
-    //         (cnt - u16::MAX as u32, period + 2)
-    //     } else {
-    //         (cnt, period)
-    //     };
-
-    //     let period = if cnt > u16::MAX as u32 / 2 { period + 1 } else { period };
-
-    //     self.period.store(period, Ordering::SeqCst);
-    //     regs_gp16().cnt().write(|w| w.set_cnt(cnt as u16));
-
-    //     // Now, recompute alarm
-    //     let alarm = self.alarm.borrow(cs);
-
-    //     if !self.set_alarm(cs, alarm.timestamp.get()) {
-    //         // If the alarm timestamp has passed, we need to trigger it
-    //         self.trigger_alarm(cs);
-    //     }
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// Stop the wakeup alarm, if enabled, and add the appropriate offset
-    // fn stop_wakeup_alarm(&self, cs: CriticalSection) {
-    //     if let Some(offset) = self.rtc.borrow(cs).get().unwrap().stop_wakeup_alarm(cs) {
-    //         self.add_time(offset, cs);
-    //     }
-    // }
-
-    // /*
-    //     Low-power public functions: all create a critical section
-    // */
-    // #[cfg(feature = "low-power")]
-    // /// Set the rtc but panic if it's already been set
-    // pub(crate) fn set_rtc(&self, rtc: &'static Rtc) {
-    //     critical_section::with(|cs| {
-    //         rtc.stop_wakeup_alarm(cs);
-
-    //         assert!(self.rtc.borrow(cs).replace(Some(rtc)).is_none())
-    //     });
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// The minimum pause time beyond which the executor will enter a low-power state.
-    // pub(crate) const MIN_STOP_PAUSE: embassy_time::Duration = embassy_time::Duration::from_millis(250);
-
-    // #[cfg(feature = "low-power")]
-    // /// Pause the timer if ready; return err if not
-    // pub(crate) fn pause_time(&self) -> Result<(), ()> {
-    //     critical_section::with(|cs| {
-    //         /*
-    //             If the wakeup timer is currently running, then we need to stop it and
-    //             add the elapsed time to the current time, as this will impact the result
-    //             of `time_until_next_alarm`.
-    //         */
-    //         self.stop_wakeup_alarm(cs);
-
-    //         let time_until_next_alarm = self.time_until_next_alarm(cs);
-    //         if time_until_next_alarm < Self::MIN_STOP_PAUSE {
-    //             Err(())
-    //         } else {
-    //             self.rtc
-    //                 .borrow(cs)
-    //                 .get()
-    //                 .unwrap()
-    //                 .start_wakeup_alarm(time_until_next_alarm, cs);
-
-    //             regs_gp16().cr1().modify(|w| w.set_cen(false));
-
-    //             Ok(())
-    //         }
-    //     })
-    // }
-
-    // #[cfg(feature = "low-power")]
-    // /// Resume the timer with the given offset
-    // pub(crate) fn resume_time(&self) {
-    //     if regs_gp16().cr1().read().cen() {
-    //         // Time isn't currently stopped
-
-    //         return;
-    //     }
-
-    //     critical_section::with(|cs| {
-    //         self.stop_wakeup_alarm(cs);
-
-    //         regs_gp16().cr1().modify(|w| w.set_cen(true));
-    //     })
-    // }
+    #[cfg(feature = "low-power")]
+    /// Compute the approximate amount of time until the next alarm
+    fn time_until_next_alarm(&self, cs: CriticalSection) -> embassy_time::Duration {
+        let now = self.now() + 32;
+
+        embassy_time::Duration::from_ticks(
+            self.alarm.borrow(cs).timestamp.get().saturating_sub(now),
+        )
+    }
+
+    #[cfg(feature = "low-power")]
+    /// Add the given offset to the current time
+    fn add_time(&self, offset: embassy_time::Duration, cs: CriticalSection) {
+        let r = regs_gp16();
+
+        // The timer is stopped, so `now` is stable and the period interrupts can't race us.
+        let t = self.now() + offset.as_ticks();
+
+        self.period.store((t >> 15) as u32, Ordering::Relaxed);
+        r.cnt().write(|w| w.set_cnt(t as u16));
+
+        // Now, recompute alarm
+        let alarm = self.alarm.borrow(cs);
+
+        if !self.set_alarm(cs, alarm.timestamp.get()) {
+            // If the alarm timestamp has passed, we need to trigger it
+            self.trigger_alarm(cs);
+        }
+    }
+
+    #[cfg(feature = "low-power")]
+    /// Stop the wakeup alarm, if enabled, and add the appropriate offset
+    fn stop_wakeup_alarm(&self, cs: CriticalSection) {
+        if let Some(offset) = self
+            .rtc
+            .borrow(cs)
+            .get()
+            .and_then(|rtc| rtc.stop_wakeup_alarm(cs))
+        {
+            self.add_time(offset, cs);
+        }
+    }
+
+    /*
+        Low-power public functions: all create a critical section
+    */
+    #[cfg(feature = "low-power")]
+    /// Set the rtc but panic if it's already been set
+    pub(crate) fn set_rtc(&self, rtc: &'static Rtc<'static>) {
+        critical_section::with(|cs| {
+            rtc.stop_wakeup_alarm(cs);
+
+            assert!(self.rtc.borrow(cs).replace(Some(rtc)).is_none())
+        });
+    }
+
+    #[cfg(feature = "low-power")]
+    /// The minimum pause time beyond which the executor will enter a low-power state.
+    ///
+    /// The RTC alarm has a resolution of one second, shorter pauses can't be timed.
+    pub(crate) const MIN_STOP_PAUSE: embassy_time::Duration =
+        embassy_time::Duration::from_millis(1000);
+
+    #[cfg(feature = "low-power")]
+    /// Pause the timer if ready; return err if not
+    pub(crate) fn pause_time(&self) -> Result<(), ()> {
+        critical_section::with(|cs| {
+            /*
+                If the wakeup timer is currently running, then we need to stop it and
+                add the elapsed time to the current time, as this will impact the result
+                of `time_until_next_alarm`.
+            */
+            self.stop_wakeup_alarm(cs);
+
+            let time_until_next_alarm = self.time_until_next_alarm(cs);
+            if time_until_next_alarm < Self::MIN_STOP_PAUSE {
+                return Err(());
+            }
+
+            // Without an RTC to wake up from Stop mode, only Sleep mode can be used.
+            let rtc = self.rtc.borrow(cs).get().ok_or(())?;
+            rtc.start_wakeup_alarm(time_until_next_alarm, cs)?;
+
+            regs_gp16().cr1().modify(|w| w.set_cen(false));
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "low-power")]
+    /// Resume the timer with the given offset
+    pub(crate) fn resume_time(&self) {
+        if regs_gp16().cr1().read().cen() {
+            // Time isn't currently stopped
+
+            return;
+        }
+
+        critical_section::with(|cs| {
+            self.stop_wakeup_alarm(cs);
+
+            regs_gp16().cr1().modify(|w| w.set_cen(true));
+        })
+    }
 
     fn set_alarm(&self, cs: CriticalSection, timestamp: u64) -> bool {
         let r = regs_gp16();
@@ -525,10 +518,10 @@ impl Driver for RtcDriver {
     }
 }
 
-// #[cfg(feature = "low-power")]
-// pub(crate) fn get_driver() -> &'static RtcDriver {
-//     &DRIVER
-// }
+#[cfg(feature = "low-power")]
+pub(crate) fn get_driver() -> &'static RtcDriver {
+    &DRIVER
+}
 
 pub(crate) fn init(cs: CriticalSection) {
     DRIVER.init(cs)
@@ -1,27 +1,40 @@
 //! External Interrupts (EXTI)
 
+#![macro_use]
+
 // The following code is modified from embassy-stm32
 // https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
 // Special thanks to the Embassy Project and its contributors for their work!
 
 use core::convert::Infallible;
-use core::future::Future;
+use core::future::{poll_fn, Future};
 use core::marker::PhantomData;
 use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, Ordering};
 use core::task::{Context, Poll};
 
-use embassy_hal_internal::{impl_peripheral, into_ref};
+use embassy_hal_internal::{impl_peripheral, into_ref, PeripheralRef};
 use embassy_sync::waitqueue::AtomicWaker;
 
 use crate::gpio::{AnyPin, Input, Level, Pin as GpioPin, Pull};
+use crate::interrupt::typelevel::Interrupt as _;
 use crate::pac::exti::regs::Lines;
 use crate::pac::EXTI;
 use crate::{interrupt, pac, peripherals, Peripheral};
 
-const EXTI_COUNT: usize = 16;
+const EXTI_COUNT: usize = 32;
+/// Lines 0 to 15, connected to the GPIO pins.
+const GPIO_LINES: u32 = 0x0000_FFFF;
 const NEW_AW: AtomicWaker = AtomicWaker::new();
 static EXTI_WAKERS: [AtomicWaker; EXTI_COUNT] = [NEW_AW; EXTI_COUNT];
 
+/// GPIO lines in listen mode, see [`ExtiInput::listen`].
+static LISTENING: AtomicU32 = AtomicU32::new(0);
+/// Internal lines handled by [`InterruptHandler`], see [`ExtiLine::new`].
+static INTERNAL_LINES: AtomicU32 = AtomicU32::new(0);
+const NEW_COUNT: AtomicU32 = AtomicU32::new(0);
+static EDGE_COUNTS: [AtomicU32; 16] = [NEW_COUNT; 16];
+
 fn cpu_regs() -> pac::exti::Exti {
     EXTI
 }
@@ -30,14 +43,27 @@ fn exticr_regs() -> pac::exti::Exti {
     EXTI
 }
 
-unsafe fn on_irq() {
-    let bits = EXTI.pr().read().0;
-
-    // We don't handle or change any EXTI lines above 16.
-    let bits = bits & 0x0000FFFF;
-
-    // Mask all the channels that fired.
-    cpu_regs().imr().modify(|w| w.0 &= !bits);
+/// Handle the fired `lines` that are unmasked.
+///
+/// The lines are masked for their futures to complete, and their pending bits cleared.
+/// Lines in listen mode stay unmasked and count their edges instead.
+/// The pending bits of masked GPIO lines are cleared too.
+unsafe fn on_irq(lines: u32) {
+    let pending = EXTI.pr().read().0 & lines;
+    let bits = pending & cpu_regs().imr().read().0;
+    let listening = LISTENING.load(Ordering::Relaxed) & bits;
+
+    // Mask all the channels that fired, except the listening ones.
+    cpu_regs().imr().modify(|w| w.0 &= !(bits & !listening));
+
+    // No fetch_add on Cortex-M0+, the thread mode only resets the counts in a critical section.
+    for pin in BitIter(listening) {
+        let count = &EDGE_COUNTS[pin as usize];
+        count.store(
+            count.load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Relaxed,
+        );
+    }
 
     // Wake the tasks
     for pin in BitIter(bits) {
@@ -45,10 +71,10 @@ unsafe fn on_irq() {
     }
 
     // Clear pending
-    EXTI.pr().write_value(Lines(bits));
+    EXTI.pr().write_value(Lines(bits | (pending & GPIO_LINES)));
 
-    // #[cfg(feature = "low-power")]
-    // crate::low_power::on_wakeup_irq();
+    #[cfg(feature = "low-power")]
+    crate::low_power::on_wakeup_irq();
 }
 
 struct BitIter(u32);
@@ -74,6 +100,10 @@ impl Iterator for BitIter {
 /// EXTI channel, which is a limited resource.
 ///
 /// Pins PA5, PB5, PC5... all use EXTI channel 5, so you can't use EXTI on, say, PA5 and PC5 at the same time.
+///
+/// Besides the `wait_for_*` futures, which arm the line for a single edge, the line can
+/// [`listen`](Self::listen) and count every edge in the interrupt, or generate
+/// [wakeup events](Self::enable_wakeup_event) for WFE.
 pub struct ExtiInput<'d> {
     pin: Input<'d>,
 }
@@ -154,6 +184,129 @@ impl<'d> ExtiInput<'d> {
     pub async fn wait_for_any_edge(&mut self) {
         ExtiInputFuture::new(self.pin.pin.pin.pin(), self.pin.pin.pin.port(), true, true).await
     }
+
+    fn line(&self) -> usize {
+        self.pin.pin.pin.pin() as usize
+    }
+
+    /// Start counting the edges in the interrupt, resetting the count.
+    ///
+    /// The line stays armed between the awaits, so fast pulses are not lost: read them with
+    /// [`edge_count`](Self::edge_count), [`take_edge_count`](Self::take_edge_count) or
+    /// [`wait_for_edges`](Self::wait_for_edges). The `wait_for_*` methods end the listen mode.
+    pub fn listen(&mut self, edge: Edge) {
+        let line = self.line();
+        critical_section::with(|_| {
+            set_line_source(line, self.pin.pin.pin.port(), edge.rising(), edge.falling());
+            EDGE_COUNTS[line].store(0, Ordering::Relaxed);
+            LISTENING.store(
+                LISTENING.load(Ordering::Relaxed) | (1 << line),
+                Ordering::Relaxed,
+            );
+
+            // clear pending bit
+            EXTI.pr().write(|w| w.set_line(line, true));
+
+            cpu_regs().imr().modify(|w| w.set_line(line, true));
+        });
+    }
+
+    /// Stop counting the edges.
+    pub fn unlisten(&mut self) {
+        stop_listening(self.line());
+    }
+
+    /// Returns `true` if the line is in listen mode.
+    pub fn is_listening(&self) -> bool {
+        (LISTENING.load(Ordering::Relaxed) & (1 << self.line())) != 0
+    }
+
+    /// Number of edges since [`listen`](Self::listen) or the last
+    /// [`take_edge_count`](Self::take_edge_count).
+    pub fn edge_count(&self) -> u32 {
+        EDGE_COUNTS[self.line()].load(Ordering::Relaxed)
+    }
+
+    /// Get the number of edges and reset it to 0.
+    pub fn take_edge_count(&mut self) -> u32 {
+        let count = &EDGE_COUNTS[self.line()];
+        critical_section::with(|_| {
+            let n = count.load(Ordering::Relaxed);
+            count.store(0, Ordering::Relaxed);
+            n
+        })
+    }
+
+    /// Asynchronously wait for at least one edge, then take the edge count.
+    ///
+    /// Returns immediately if edges were counted since the last take. The line must be in
+    /// listen mode.
+    pub async fn wait_for_edges(&mut self) -> u32 {
+        debug_assert!(self.is_listening());
+
+        let line = self.line();
+        poll_fn(|cx| {
+            EXTI_WAKERS[line].register(cx.waker());
+
+            match self.take_edge_count() {
+                0 => Poll::Pending,
+                n => Poll::Ready(n),
+            }
+        })
+        .await
+    }
+
+    /// Generate an event on the edges, which wakes the core up from WFE (for example
+    /// [`pwr::enter_stop`](crate::pwr::enter_stop) with
+    /// [`WakeupOn::Event`](crate::pwr::WakeupOn::Event)) without an interrupt.
+    ///
+    /// The event is independent of the interrupt: it can be combined with [`listen`](Self::listen)
+    /// or the `wait_for_*` methods, which set the edges again.
+    pub fn enable_wakeup_event(&mut self, edge: Edge) {
+        let line = self.line();
+        critical_section::with(|_| {
+            set_line_source(line, self.pin.pin.pin.port(), edge.rising(), edge.falling());
+            EXTI.emr().modify(|w| w.set_line(line, true));
+        });
+    }
+
+    /// Stop generating wakeup events.
+    pub fn disable_wakeup_event(&mut self) {
+        let line = self.line();
+        critical_section::with(|_| EXTI.emr().modify(|w| w.set_line(line, false)));
+    }
+}
+
+impl<'d> Drop for ExtiInput<'d> {
+    fn drop(&mut self) {
+        let line = self.line();
+        stop_listening(line);
+        critical_section::with(|_| EXTI.emr().modify(|w| w.set_line(line, false)));
+    }
+}
+
+/// Route the GPIO `port` to the EXTI `line`, and set the edges.
+///
+/// Must be called in a critical section.
+fn set_line_source(line: usize, port: u8, rising: bool, falling: bool) {
+    // The port_sel of GPIOF is 2, but embassy seems to handle this automatically, requiring no extra processing.
+    exticr_regs()
+        .exticr(line / 4)
+        .modify(|w| w.set_exti(line % 4, port));
+    EXTI.rtsr().modify(|w| w.set_line(line, rising));
+    EXTI.ftsr().modify(|w| w.set_line(line, falling));
+}
+
+fn stop_listening(line: usize) {
+    critical_section::with(|_| {
+        if (LISTENING.load(Ordering::Relaxed) & (1 << line)) != 0 {
+            LISTENING.store(
+                LISTENING.load(Ordering::Relaxed) & !(1 << line),
+                Ordering::Relaxed,
+            );
+            cpu_regs().imr().modify(|w| w.set_line(line, false));
+        }
+    });
 }
 
 impl<'d> embedded_hal_02::digital::v2::InputPin for ExtiInput<'d> {
@@ -217,15 +370,12 @@ struct ExtiInputFuture<'a> {
 
 impl<'a> ExtiInputFuture<'a> {
     fn new(pin: u8, port: u8, rising: bool, falling: bool) -> Self {
+        stop_listening(pin as usize);
+
         critical_section::with(|_| {
             let pin = pin as usize;
 
-            // The port_sel of GPIOF is 2, but embassy seems to handle this automatically, requiring no extra processing.
-            exticr_regs()
-                .exticr(pin / 4)
-                .modify(|w| w.set_exti(pin % 4, port));
-            EXTI.rtsr().modify(|w| w.set_line(pin, rising));
-            EXTI.ftsr().modify(|w| w.set_line(pin, falling));
+            set_line_source(pin, port, rising, falling);
 
             // clear pending bit
             EXTI.pr().write(|w| w.set_line(pin, true));
@@ -264,6 +414,196 @@ impl<'a> Future for ExtiInputFuture<'a> {
     }
 }
 
+/// Edges that trigger an EXTI line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// Trigger on rising edges.
+    Rising,
+    /// Trigger on falling edges.
+    Falling,
+    /// Trigger on both edges.
+    Both,
+}
+
+impl Edge {
+    fn rising(&self) -> bool {
+        matches!(self, Edge::Rising | Edge::Both)
+    }
+
+    fn falling(&self) -> bool {
+        matches!(self, Edge::Falling | Edge::Both)
+    }
+}
+
+/// How an EXTI line reports its edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LineMode {
+    /// Raise the peripheral interrupt, which wakes the tasks waiting on the line.
+    Interrupt,
+    /// Generate an event, which only wakes the core from WFE.
+    Event,
+}
+
+/// Interrupt handler for the internal EXTI lines.
+///
+/// Bind it to the interrupt of the peripheral driving the line, see [`InternalLine`].
+/// Only the lines of an [`ExtiLine`] in interrupt mode are handled, so it can share an interrupt
+/// with the peripheral driver.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl<I: interrupt::typelevel::Interrupt> interrupt::typelevel::Handler<I> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        on_irq(INTERNAL_LINES.load(Ordering::Relaxed))
+    }
+}
+
+/// EXTI line driver, for the lines that are not connected to a GPIO pin.
+///
+/// The internal lines are driven by peripherals that keep running in Stop mode (PVD, COMP,
+/// RTC alarm, LPTIM), so they can wake the chip up. The peripheral must be configured to
+/// generate its output or event separately.
+///
+/// [`Pvd`](crate::pwr::Pvd) and [`Comparator`](crate::comp::Comparator) use their line
+/// for their own async waits, don't use them at the same time as an `ExtiLine` on that line.
+pub struct ExtiLine<'d, L: InternalLine> {
+    _line: PeripheralRef<'d, L>,
+}
+
+impl<'d, L: InternalLine> ExtiLine<'d, L> {
+    /// Create an EXTI line in interrupt mode, for async waits.
+    pub fn new(
+        line: impl Peripheral<P = L> + 'd,
+        _irq: impl interrupt::typelevel::Binding<L::Interrupt, InterruptHandler> + 'd,
+        edge: Edge,
+    ) -> Self {
+        let this = Self::new_inner(line, edge);
+        // No fetch_or on Cortex-M0+.
+        critical_section::with(|_| {
+            let lines = INTERNAL_LINES.load(Ordering::Relaxed);
+            INTERNAL_LINES.store(lines | 1 << L::LINE, Ordering::Relaxed);
+        });
+
+        L::Interrupt::unpend();
+        unsafe { L::Interrupt::enable() };
+
+        this
+    }
+
+    /// Create an EXTI line in event mode.
+    ///
+    /// The line wakes the core up from WFE (for example [`pwr::enter_stop`](crate::pwr::enter_stop)
+    /// with [`WakeupOn::Event`](crate::pwr::WakeupOn::Event)) without running an interrupt handler.
+    pub fn new_event(line: impl Peripheral<P = L> + 'd, edge: Edge) -> Self {
+        let this = Self::new_inner(line, edge);
+        critical_section::with(|_| EXTI.emr().modify(|w| w.set_line(L::LINE, true)));
+        this
+    }
+
+    fn new_inner(line: impl Peripheral<P = L> + 'd, edge: Edge) -> Self {
+        into_ref!(line);
+        let mut this = Self { _line: line };
+        this.set_edge(edge);
+        this
+    }
+
+    /// Change the edges that trigger the line.
+    ///
+    /// The LPTIM line is not edge-configurable, the setting is ignored for it.
+    pub fn set_edge(&mut self, edge: Edge) {
+        critical_section::with(|_| {
+            EXTI.rtsr().modify(|w| w.set_line(L::LINE, edge.rising()));
+            EXTI.ftsr().modify(|w| w.set_line(L::LINE, edge.falling()));
+        });
+    }
+
+    /// Mode of the line.
+    pub fn mode(&self) -> LineMode {
+        if EXTI.emr().read().line(L::LINE) {
+            LineMode::Event
+        } else {
+            LineMode::Interrupt
+        }
+    }
+
+    /// Returns `true` if an edge happened since the last [`clear_pending`](Self::clear_pending).
+    ///
+    /// Useful in event mode, to find the line that woke the core up.
+    pub fn is_pending(&self) -> bool {
+        EXTI.pr().read().line(L::LINE)
+    }
+
+    /// Clear the pending edge.
+    pub fn clear_pending(&mut self) {
+        EXTI.pr().write(|w| w.set_line(L::LINE, true));
+    }
+
+    /// Asynchronously wait for the next edge.
+    ///
+    /// Must be in [`LineMode::Interrupt`], the future never completes in event mode.
+    pub async fn wait(&mut self) {
+        debug_assert!(self.mode() == LineMode::Interrupt);
+        ExtiLineFuture::new(L::LINE).await
+    }
+}
+
+impl<'d, L: InternalLine> Drop for ExtiLine<'d, L> {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            let lines = INTERNAL_LINES.load(Ordering::Relaxed);
+            INTERNAL_LINES.store(lines & !(1 << L::LINE), Ordering::Relaxed);
+            cpu_regs().imr().modify(|w| w.set_line(L::LINE, false));
+            EXTI.emr().modify(|w| w.set_line(L::LINE, false));
+            EXTI.rtsr().modify(|w| w.set_line(L::LINE, false));
+            EXTI.ftsr().modify(|w| w.set_line(L::LINE, false));
+        });
+    }
+}
+
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+struct ExtiLineFuture {
+    line: usize,
+}
+
+impl ExtiLineFuture {
+    fn new(line: usize) -> Self {
+        critical_section::with(|_| {
+            // clear pending bit
+            EXTI.pr().write(|w| w.set_line(line, true));
+
+            cpu_regs().imr().modify(|w| w.set_line(line, true));
+        });
+
+        Self { line }
+    }
+}
+
+impl Drop for ExtiLineFuture {
+    fn drop(&mut self) {
+        critical_section::with(|_| {
+            cpu_regs().imr().modify(|w| w.set_line(self.line, false));
+        });
+    }
+}
+
+impl Future for ExtiLineFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        EXTI_WAKERS[self.line].register(cx.waker());
+
+        let imr = cpu_regs().imr().read();
+        if !imr.line(self.line) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
 macro_rules! foreach_exti_irq {
     ($action:ident) => {
         foreach_interrupt!(
@@ -304,7 +644,7 @@ macro_rules! impl_irq {
         #[cfg(feature = "rt")]
         #[interrupt]
         unsafe fn $e() {
-            on_irq()
+            on_irq(GPIO_LINES)
         }
     };
 }
@@ -374,6 +714,36 @@ impl_exti!(EXTI13, 13);
 impl_exti!(EXTI14, 14);
 impl_exti!(EXTI15, 15);
 
+trait SealedInternalLine {
+    const LINE: usize;
+}
+
+/// Internal EXTI line, not connected to a GPIO pin.
+///
+/// | Line     | Source              | Interrupt        |
+/// |----------|---------------------|------------------|
+/// | `EXTI16` | PVD output          | `PVD`            |
+/// | `EXTI17` | COMP1 output        | COMP1 interrupt  |
+/// | `EXTI18` | COMP2 output        | COMP2 interrupt  |
+/// | `EXTI19` | RTC alarm           | `RTC`            |
+/// | `EXTI29` | LPTIM wakeup        | LPTIM interrupt  |
+#[allow(private_bounds)]
+pub trait InternalLine: SealedInternalLine + Peripheral<P = Self> + 'static {
+    /// Interrupt of the peripheral driving the line.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+macro_rules! impl_exti_internal_line {
+    ($type:ident, $number:expr, $irq:ident) => {
+        impl crate::exti::SealedInternalLine for peripherals::$type {
+            const LINE: usize = $number as usize;
+        }
+        impl crate::exti::InternalLine for peripherals::$type {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+}
+
 macro_rules! enable_irq {
     ($e:ident) => {
         crate::interrupt::typelevel::$e::enable();
@@ -382,7 +752,5 @@ macro_rules! enable_irq {
 
 /// safety: must be called only once
 pub(crate) unsafe fn init(_cs: critical_section::CriticalSection) {
-    use crate::interrupt::typelevel::Interrupt;
-
     foreach_exti_irq!(enable_irq);
 }
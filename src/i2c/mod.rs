@@ -5,6 +5,7 @@
 // https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
 // Special thanks to the Embassy Project and its contributors for their work!
 
+pub mod smbus;
 mod v1;
 
 use core::future::Future;
@@ -62,6 +63,13 @@ pub struct Config {
     /// Timeout.
     #[cfg(feature = "time")]
     pub timeout: embassy_time::Duration,
+    /// Automatically recover the bus and reset the peripheral when a transfer
+    /// times out or the BUSY flag stays set, see [`I2c::recover_bus`].
+    ///
+    /// Async transfers only have a timeout with this enabled. Lockups are detected with the
+    /// timeout; without the `time` feature, only a BUSY flag stuck for a large number of polls
+    /// is detected.
+    pub auto_recovery: bool,
 }
 
 impl Default for Config {
@@ -71,6 +79,7 @@ impl Default for Config {
             // scl_pullup: false,
             #[cfg(feature = "time")]
             timeout: embassy_time::Duration::from_millis(1000),
+            auto_recovery: false,
         }
     }
 }
@@ -113,6 +122,10 @@ pub struct I2c<'d, M: Mode> {
     #[cfg(dma)] rx_dma: Option<ChannelAndRequest<'d>>,
     #[cfg(feature = "time")]
     timeout: Duration,
+    auto_recovery: bool,
+    /// Pin configuration, restored after [`I2c::recover_bus`].
+    scl_af: AfType,
+    sda_af: AfType,
     _phantom: PhantomData<M>,
 }
 
@@ -184,6 +197,9 @@ impl<'d, M: Mode> I2c<'d, M> {
             #[cfg(dma)] rx_dma,
             #[cfg(feature = "time")]
             timeout: config.timeout,
+            auto_recovery: config.auto_recovery,
+            scl_af: config.scl_af(),
+            sda_af: config.sda_af(),
             _phantom: PhantomData,
         };
         this.enable_and_init(freq, config);
@@ -212,6 +228,86 @@ impl<'d, M: Mode> Drop for I2c<'d, M> {
     }
 }
 
+/// Own address configuration for target (slave) mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlaveAddrConfig {
+    /// Primary 7-bit address (OAR1).
+    pub addr: u8,
+    /// Optional second 7-bit address (OAR2, dual addressing mode).
+    pub dual_addr: Option<u8>,
+    /// Acknowledge the general call address `0x00`.
+    pub general_call: bool,
+}
+
+impl SlaveAddrConfig {
+    /// Respond to a single 7-bit address, without dual addressing or general call.
+    pub const fn basic(addr: u8) -> Self {
+        Self {
+            addr,
+            dual_addr: None,
+            general_call: false,
+        }
+    }
+}
+
+/// Direction of a transfer requested by the controller (master), seen from the target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CommandKind {
+    /// The controller wants to read from us, answer with [`I2cSlave::respond_to_read`].
+    Read,
+    /// The controller wants to write to us, answer with [`I2cSlave::respond_to_write`].
+    Write,
+    /// The controller wrote to us and then issued a repeated START to read from us.
+    ///
+    /// The data received by the previous [`I2cSlave::respond_to_write`] is the request,
+    /// answer with [`I2cSlave::respond_to_read`].
+    WriteRead,
+}
+
+/// Which of our addresses the controller matched.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MatchedAddress {
+    /// Primary address (OAR1).
+    Primary,
+    /// Dual address (OAR2).
+    Dual,
+    /// General call address `0x00`.
+    GeneralCall,
+}
+
+/// Address matched event returned by [`I2cSlave::listen`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Command {
+    /// Requested transfer.
+    pub kind: CommandKind,
+    /// Matched address.
+    pub address: MatchedAddress,
+}
+
+/// Result of [`I2cSlave::respond_to_read`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendStatus {
+    /// The whole buffer was sent. If the controller clocked out more bytes, `0xFF` was sent for them.
+    Done,
+    /// The controller ended the transfer before the buffer was fully sent.
+    LeftoverBytes(usize),
+}
+
+/// I2C target (slave) driver.
+///
+/// Created with [`I2c::into_slave`]. The peripheral acknowledges its own addresses
+/// in hardware and stretches the clock until the task handles the transfer.
+pub struct I2cSlave<'d> {
+    i2c: I2c<'d, Async>,
+    /// The previous write phase was terminated by a repeated START instead of a STOP.
+    restarted: bool,
+}
+
 #[derive(Copy, Clone)]
 struct Timeout {
     #[cfg(feature = "time")]
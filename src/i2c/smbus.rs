@@ -0,0 +1,427 @@
+//! System Management Bus (SMBus) protocols on top of [`I2c`].
+//!
+//! Packet error checking (PEC) is computed in software: the I2C v1 hardware PEC needs the
+//! PEC byte to be requested in the middle of a transfer, which the transaction API used here
+//! does not expose. The resulting bytes on the wire are identical.
+
+use super::{Error, FrameOptions, I2c, Timeout};
+#[cfg(all(dma, feature = "exti"))]
+use crate::exti::ExtiInput;
+#[cfg(dma)]
+use crate::mode::Async;
+use crate::mode::Mode;
+
+/// Maximum number of data bytes in a block transfer (SMBus 3.0).
+pub const MAX_BLOCK_LEN: usize = 255;
+
+/// Alert Response Address, read by the host to find the device asserting SMBALERT#.
+pub const ALERT_RESPONSE_ADDRESS: u8 = 0x0C;
+
+/// Command code and byte count, a full block and the PEC byte.
+const WRITE_BUF_LEN: usize = 2 + MAX_BLOCK_LEN + 1;
+
+/// Update an SMBus packet error code (CRC-8, polynomial `x^8 + x^2 + x + 1`) with `data`.
+///
+/// Start with `crc = 0`.
+pub fn pec_update(mut crc: u8, data: &[u8]) -> u8 {
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// SMBus driver.
+pub struct Smbus<'d, M: Mode> {
+    i2c: I2c<'d, M>,
+    pec: bool,
+}
+
+impl<'d, M: Mode> Smbus<'d, M> {
+    /// Create a new SMBus driver. When `pec` is true every transfer carries a PEC byte.
+    pub fn new(i2c: I2c<'d, M>, pec: bool) -> Self {
+        Self { i2c, pec }
+    }
+
+    /// Enable or disable packet error checking.
+    pub fn set_pec(&mut self, pec: bool) {
+        self.pec = pec;
+    }
+
+    /// Get back the underlying I2C driver.
+    pub fn into_inner(self) -> I2c<'d, M> {
+        self.i2c
+    }
+
+    /// Copy `prefix`, `data` and the PEC byte, if enabled, into `buf` to be sent as one write.
+    ///
+    /// Returns the number of bytes to send.
+    fn write_buf(
+        &self,
+        addr: u8,
+        prefix: &[u8],
+        data: &[u8],
+        buf: &mut [u8; WRITE_BUF_LEN],
+    ) -> usize {
+        let len = prefix.len() + data.len();
+        buf[..prefix.len()].copy_from_slice(prefix);
+        buf[prefix.len()..len].copy_from_slice(data);
+        if !self.pec {
+            return len;
+        }
+
+        let crc = pec_update(0, &[addr << 1]);
+        buf[len] = pec_update(crc, &buf[..len]);
+        len + 1
+    }
+
+    fn check_read_pec(&self, addr: u8, cmd: &[u8], data: &[u8]) -> Result<(), Error> {
+        if !self.pec {
+            return Ok(());
+        }
+
+        let (received, data) = unwrap!(data.split_last());
+        let mut crc = 0;
+        if !cmd.is_empty() {
+            crc = pec_update(crc, &[addr << 1]);
+            crc = pec_update(crc, cmd);
+        }
+        crc = pec_update(crc, &[(addr << 1) | 1]);
+        crc = pec_update(crc, data);
+
+        if crc == *received {
+            Ok(())
+        } else {
+            Err(Error::Crc)
+        }
+    }
+
+    fn blocking_write_cmd(&mut self, addr: u8, prefix: &[u8], data: &[u8]) -> Result<(), Error> {
+        let mut buf = [0u8; WRITE_BUF_LEN];
+        let len = self.write_buf(addr, prefix, data, &mut buf);
+        self.i2c.blocking_write(addr, &buf[..len])
+    }
+
+    fn blocking_read_cmd(&mut self, addr: u8, cmd: &[u8], data: &mut [u8]) -> Result<(), Error> {
+        let mut buf = [0u8; 3];
+        let buf = &mut buf[..data.len() + self.pec as usize];
+        if cmd.is_empty() {
+            self.i2c.blocking_read(addr, buf)?;
+        } else {
+            self.i2c.blocking_write_read(addr, cmd, buf)?;
+        }
+        self.check_read_pec(addr, cmd, buf)?;
+        data.copy_from_slice(&buf[..data.len()]);
+        Ok(())
+    }
+
+    /// Quick command: address the device with the write bit and no data.
+    pub fn blocking_quick_command(&mut self, addr: u8) -> Result<(), Error> {
+        self.i2c.blocking_write(addr, &[])
+    }
+
+    /// Send byte.
+    pub fn blocking_send_byte(&mut self, addr: u8, byte: u8) -> Result<(), Error> {
+        self.blocking_write_cmd(addr, &[byte], &[])
+    }
+
+    /// Receive byte.
+    pub fn blocking_receive_byte(&mut self, addr: u8) -> Result<u8, Error> {
+        let mut data = [0u8];
+        self.blocking_read_cmd(addr, &[], &mut data)?;
+        Ok(data[0])
+    }
+
+    /// Write byte to command code `cmd`.
+    pub fn blocking_write_byte(&mut self, addr: u8, cmd: u8, byte: u8) -> Result<(), Error> {
+        self.blocking_write_cmd(addr, &[cmd], &[byte])
+    }
+
+    /// Read byte from command code `cmd`.
+    pub fn blocking_read_byte(&mut self, addr: u8, cmd: u8) -> Result<u8, Error> {
+        let mut data = [0u8];
+        self.blocking_read_cmd(addr, &[cmd], &mut data)?;
+        Ok(data[0])
+    }
+
+    /// Write word (little endian) to command code `cmd`.
+    pub fn blocking_write_word(&mut self, addr: u8, cmd: u8, word: u16) -> Result<(), Error> {
+        self.blocking_write_cmd(addr, &[cmd], &word.to_le_bytes())
+    }
+
+    /// Read word (little endian) from command code `cmd`.
+    pub fn blocking_read_word(&mut self, addr: u8, cmd: u8) -> Result<u16, Error> {
+        let mut data = [0u8; 2];
+        self.blocking_read_cmd(addr, &[cmd], &mut data)?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    /// Process call: write a word to command code `cmd` and read back a word.
+    pub fn blocking_process_call(&mut self, addr: u8, cmd: u8, word: u16) -> Result<u16, Error> {
+        let [lo, hi] = word.to_le_bytes();
+        let mut data = [0u8; 2];
+        self.blocking_read_cmd(addr, &[cmd, lo, hi], &mut data)?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    /// Block write to command code `cmd`.
+    pub fn blocking_block_write(&mut self, addr: u8, cmd: u8, data: &[u8]) -> Result<(), Error> {
+        if data.len() > MAX_BLOCK_LEN {
+            return Err(Error::Overrun);
+        }
+        self.blocking_write_cmd(addr, &[cmd, data.len() as u8], data)
+    }
+
+    /// Block read from command code `cmd`.
+    ///
+    /// Returns the number of bytes the device sent, which are stored at the start of `buf`.
+    /// Returns [`Error::Overrun`] if they do not fit into `buf`.
+    pub fn blocking_block_read(
+        &mut self,
+        addr: u8,
+        cmd: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let timeout = self.i2c.timeout();
+        let mut count = [0u8];
+        let mut data = [0u8; MAX_BLOCK_LEN + 1];
+
+        let result = self.blocking_block_read_frames(addr, cmd, &mut count, &mut data, timeout);
+        self.i2c.recover_on_timeout(result)?;
+
+        self.finish_block_read(addr, cmd, count[0], &data, buf)
+    }
+
+    fn blocking_block_read_frames(
+        &mut self,
+        addr: u8,
+        cmd: u8,
+        count: &mut [u8; 1],
+        data: &mut [u8; MAX_BLOCK_LEN + 1],
+        timeout: Timeout,
+    ) -> Result<(), Error> {
+        // The byte count is read in the same frame as the data, ACKing it so the device continues.
+        self.i2c
+            .write_bytes(addr, &[cmd], timeout, FrameOptions::FirstFrame)?;
+        self.i2c
+            .blocking_read_timeout(addr, count, timeout, FrameOptions::FirstAndNextFrame)?;
+
+        let tail = count[0] as usize + self.pec as usize;
+        // A zero length block without PEC still needs a NACKed byte to end the read.
+        let tail = &mut data[..tail.max(1)];
+        self.i2c
+            .blocking_read_timeout(addr, tail, timeout, FrameOptions::LastFrame)
+    }
+
+    fn finish_block_read(
+        &self,
+        addr: u8,
+        cmd: u8,
+        count: u8,
+        data: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let len = count as usize;
+
+        if self.pec {
+            let crc = pec_update(0, &[addr << 1, cmd, (addr << 1) | 1, count]);
+            if pec_update(crc, &data[..len]) != data[len] {
+                return Err(Error::Crc);
+            }
+        }
+
+        if len > buf.len() {
+            return Err(Error::Overrun);
+        }
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    /// Read the Alert Response Address after SMBALERT# was asserted.
+    ///
+    /// Returns the 7-bit address of the alerting device. If several devices assert the alert,
+    /// the one with the lowest address wins the arbitration and releases its alert.
+    pub fn blocking_alert_response(&mut self) -> Result<u8, Error> {
+        Ok(self.blocking_receive_byte(ALERT_RESPONSE_ADDRESS)? >> 1)
+    }
+}
+
+#[cfg(dma)]
+impl<'d> Smbus<'d, Async> {
+    async fn write_cmd(&mut self, addr: u8, prefix: &[u8], data: &[u8]) -> Result<(), Error> {
+        let mut buf = [0u8; WRITE_BUF_LEN];
+        let len = self.write_buf(addr, prefix, data, &mut buf);
+        self.i2c.write(addr, &buf[..len]).await
+    }
+
+    async fn read_cmd(&mut self, addr: u8, cmd: &[u8], data: &mut [u8]) -> Result<(), Error> {
+        let mut buf = [0u8; 3];
+        let buf = &mut buf[..data.len() + self.pec as usize];
+        if cmd.is_empty() {
+            self.i2c.read(addr, buf).await?;
+        } else {
+            self.i2c.write_read(addr, cmd, buf).await?;
+        }
+        self.check_read_pec(addr, cmd, buf)?;
+        data.copy_from_slice(&buf[..data.len()]);
+        Ok(())
+    }
+
+    /// Quick command: address the device with the write bit and no data.
+    ///
+    /// There is no data to move with DMA, so this is the same as [`Self::blocking_quick_command`].
+    pub async fn quick_command(&mut self, addr: u8) -> Result<(), Error> {
+        self.blocking_quick_command(addr)
+    }
+
+    /// Send byte.
+    pub async fn send_byte(&mut self, addr: u8, byte: u8) -> Result<(), Error> {
+        self.write_cmd(addr, &[byte], &[]).await
+    }
+
+    /// Receive byte.
+    pub async fn receive_byte(&mut self, addr: u8) -> Result<u8, Error> {
+        let mut data = [0u8];
+        self.read_cmd(addr, &[], &mut data).await?;
+        Ok(data[0])
+    }
+
+    /// Write byte to command code `cmd`.
+    pub async fn write_byte(&mut self, addr: u8, cmd: u8, byte: u8) -> Result<(), Error> {
+        self.write_cmd(addr, &[cmd], &[byte]).await
+    }
+
+    /// Read byte from command code `cmd`.
+    pub async fn read_byte(&mut self, addr: u8, cmd: u8) -> Result<u8, Error> {
+        let mut data = [0u8];
+        self.read_cmd(addr, &[cmd], &mut data).await?;
+        Ok(data[0])
+    }
+
+    /// Write word (little endian) to command code `cmd`.
+    pub async fn write_word(&mut self, addr: u8, cmd: u8, word: u16) -> Result<(), Error> {
+        self.write_cmd(addr, &[cmd], &word.to_le_bytes()).await
+    }
+
+    /// Read word (little endian) from command code `cmd`.
+    pub async fn read_word(&mut self, addr: u8, cmd: u8) -> Result<u16, Error> {
+        let mut data = [0u8; 2];
+        self.read_cmd(addr, &[cmd], &mut data).await?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    /// Process call: write a word to command code `cmd` and read back a word.
+    pub async fn process_call(&mut self, addr: u8, cmd: u8, word: u16) -> Result<u16, Error> {
+        let [lo, hi] = word.to_le_bytes();
+        let mut data = [0u8; 2];
+        self.read_cmd(addr, &[cmd, lo, hi], &mut data).await?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    /// Block write to command code `cmd`.
+    pub async fn block_write(&mut self, addr: u8, cmd: u8, data: &[u8]) -> Result<(), Error> {
+        if data.len() > MAX_BLOCK_LEN {
+            return Err(Error::Overrun);
+        }
+        self.write_cmd(addr, &[cmd, data.len() as u8], data).await
+    }
+
+    /// Block read from command code `cmd`.
+    ///
+    /// Returns the number of bytes the device sent, which are stored at the start of `buf`.
+    /// Returns [`Error::Overrun`] if they do not fit into `buf`.
+    pub async fn block_read(&mut self, addr: u8, cmd: u8, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut count = [0u8];
+        let mut data = [0u8; MAX_BLOCK_LEN + 1];
+
+        // Like the other async transfers, only time out with `Config::auto_recovery`.
+        if self.i2c.auto_recovery {
+            let timeout = self.i2c.timeout();
+            let result = timeout
+                .with(self.block_read_frames(addr, cmd, &mut count, &mut data))
+                .await;
+            self.i2c.recover_on_timeout(result)?;
+        } else {
+            self.block_read_frames(addr, cmd, &mut count, &mut data)
+                .await?;
+        }
+
+        self.finish_block_read(addr, cmd, count[0], &data, buf)
+    }
+
+    async fn block_read_frames(
+        &mut self,
+        addr: u8,
+        cmd: u8,
+        count: &mut [u8; 1],
+        data: &mut [u8; MAX_BLOCK_LEN + 1],
+    ) -> Result<(), Error> {
+        // The byte count is read in the same frame as the data, ACKing it so the device continues.
+        self.i2c
+            .write_frame(addr, &[cmd], FrameOptions::FirstFrame)
+            .await?;
+        self.i2c
+            .read_frame(addr, count, FrameOptions::FirstAndNextFrame)
+            .await?;
+
+        let tail = count[0] as usize + self.pec as usize;
+        // A zero length block without PEC still needs a NACKed byte to end the read.
+        let tail = &mut data[..tail.max(1)];
+        self.i2c
+            .read_frame(addr, tail, FrameOptions::LastFrame)
+            .await
+    }
+
+    /// Read the Alert Response Address after SMBALERT# was asserted.
+    ///
+    /// Returns the 7-bit address of the alerting device. If several devices assert the alert,
+    /// the one with the lowest address wins the arbitration and releases its alert.
+    pub async fn alert_response(&mut self) -> Result<u8, Error> {
+        Ok(self.receive_byte(ALERT_RESPONSE_ADDRESS).await? >> 1)
+    }
+
+    /// Wait for the SMBALERT# line to be asserted (low), then read the Alert Response Address.
+    ///
+    /// PY32 I2C has no dedicated SMBA pin, so the alert line is connected to any EXTI capable GPIO.
+    #[cfg(feature = "exti")]
+    pub async fn wait_for_alert(&mut self, alert: &mut ExtiInput<'_>) -> Result<u8, Error> {
+        alert.wait_for_low().await;
+        self.alert_response().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pec_update;
+
+    #[test]
+    fn test_pec_check_value() {
+        // CRC-8/SMBUS check value.
+        assert_eq!(pec_update(0, b"123456789"), 0xF4);
+    }
+
+    #[test]
+    fn test_pec_single_bytes() {
+        assert_eq!(pec_update(0, &[]), 0x00);
+        assert_eq!(pec_update(0, &[0x00]), 0x00);
+        assert_eq!(pec_update(0, &[0x01]), 0x07);
+        assert_eq!(pec_update(0, &[0xFF]), 0xF3);
+    }
+
+    #[test]
+    fn test_pec_incremental() {
+        // Write word 0x1234 to command 0x06 of the device at 0x5A.
+        let frame = [0x5A << 1, 0x06, 0x34, 0x12];
+        assert_eq!(pec_update(0, &frame), 0x6E);
+
+        let crc = pec_update(0, &frame[..1]);
+        let crc = pec_update(crc, &frame[1..2]);
+        assert_eq!(pec_update(crc, &frame[2..]), 0x6E);
+    }
+}
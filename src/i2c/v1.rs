@@ -4,9 +4,7 @@
 // https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
 // Special thanks to the Embassy Project and its contributors for their work!
 
-#[cfg(dma)]
 use core::future::poll_fn;
-#[cfg(dma)]
 use core::task::Poll;
 
 use embassy_embedded_hal::SetConfig;
@@ -17,6 +15,7 @@ use embassy_hal_internal::drop::OnDrop;
 use embedded_hal_1::i2c::Operation;
 
 use super::*;
+use crate::gpio::{Flex, SealedPin as _};
 use crate::mode::Mode as PeriMode;
 use crate::pac::i2c;
 
@@ -41,6 +40,9 @@ pub unsafe fn on_interrupt<T: Instance>() {
         regs.cr2().modify(|w| {
             w.set_itevten(false);
             w.set_iterren(false);
+            // Buffer interrupts are only used in target (slave) mode. RXNE/TXE stay set until the
+            // task reads or writes DR, so they must be masked here as well.
+            w.set_itbufen(false);
         });
     });
 }
@@ -65,9 +67,8 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         // The workaround for this is to trigger the SWRST line AFTER power is
         // enabled, AFTER PE is disabled and BEFORE making any other configuration.
         //
-        // It COULD be possible to apply this workaround at runtime, instead of
-        // only on initialization, however this would require detecting the timeout
-        // or BUSY lockup condition, and re-configuring the peripheral after reset.
+        // The workaround is also applied at runtime by `recover_bus`, which
+        // `Config::auto_recovery` triggers on a timeout or BUSY lockup condition.
         //
         // This presents as an ~infinite hang on read or write, as the START condition
         // is never generated, meaning the start event is never generated.
@@ -154,7 +155,7 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         Ok(sr1)
     }
 
-    fn write_bytes(
+    pub(super) fn write_bytes(
         &mut self,
         addr: u8,
         bytes: &[u8],
@@ -243,7 +244,7 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         Ok(value)
     }
 
-    fn blocking_read_timeout(
+    pub(super) fn blocking_read_timeout(
         &mut self,
         addr: u8,
         buffer: &mut [u8],
@@ -308,15 +309,21 @@ impl<'d, M: PeriMode> I2c<'d, M> {
 
     /// Blocking read.
     pub fn blocking_read(&mut self, addr: u8, read: &mut [u8]) -> Result<(), Error> {
-        self.blocking_read_timeout(addr, read, self.timeout(), FrameOptions::FirstAndLastFrame)
+        let timeout = self.timeout();
+        self.wait_bus_free(timeout)?;
+
+        let result =
+            self.blocking_read_timeout(addr, read, timeout, FrameOptions::FirstAndLastFrame);
+        self.recover_on_timeout(result)
     }
 
     /// Blocking write.
     pub fn blocking_write(&mut self, addr: u8, write: &[u8]) -> Result<(), Error> {
-        self.write_bytes(addr, write, self.timeout(), FrameOptions::FirstAndLastFrame)?;
+        let timeout = self.timeout();
+        self.wait_bus_free(timeout)?;
 
-        // Fallthrough is success
-        Ok(())
+        let result = self.write_bytes(addr, write, timeout, FrameOptions::FirstAndLastFrame);
+        self.recover_on_timeout(result)
     }
 
     /// Blocking write, restart, read.
@@ -333,11 +340,14 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         }
 
         let timeout = self.timeout();
+        self.wait_bus_free(timeout)?;
 
-        self.write_bytes(addr, write, timeout, FrameOptions::FirstFrame)?;
-        self.blocking_read_timeout(addr, read, timeout, FrameOptions::FirstAndLastFrame)?;
-
-        Ok(())
+        let result = self
+            .write_bytes(addr, write, timeout, FrameOptions::FirstFrame)
+            .and_then(|()| {
+                self.blocking_read_timeout(addr, read, timeout, FrameOptions::FirstAndLastFrame)
+            });
+        self.recover_on_timeout(result)
     }
 
     /// Blocking transaction with operations.
@@ -351,7 +361,18 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         operations: &mut [Operation<'_>],
     ) -> Result<(), Error> {
         let timeout = self.timeout();
+        self.wait_bus_free(timeout)?;
 
+        let result = self.blocking_transaction_timeout(addr, operations, timeout);
+        self.recover_on_timeout(result)
+    }
+
+    fn blocking_transaction_timeout(
+        &mut self,
+        addr: u8,
+        operations: &mut [Operation<'_>],
+        timeout: Timeout,
+    ) -> Result<(), Error> {
         for (op, frame) in operation_frames(operations)? {
             match op {
                 Operation::Read(read) => self.blocking_read_timeout(addr, read, timeout, frame)?,
@@ -362,6 +383,143 @@ impl<'d, M: PeriMode> I2c<'d, M> {
         Ok(())
     }
 
+    /// Recover a bus that is held low by a target (slave) stuck in the middle of a transfer.
+    ///
+    /// SCL and SDA are temporarily taken over as open-drain GPIOs. Up to 9 clock pulses are
+    /// sent until the target releases SDA, followed by a STOP condition. The peripheral is then
+    /// reset with SWRST and its current configuration is restored.
+    ///
+    /// Returns [`Error::Bus`] if SDA is still held low afterwards.
+    pub fn recover_bus(&mut self) -> Result<(), Error> {
+        self.info.regs.cr1().modify(|reg| reg.set_pe(false));
+
+        let released = {
+            let scl = unwrap!(self.scl.as_mut());
+            let sda = unwrap!(self.sda.as_mut());
+            let scl_af = af_num(scl);
+            let sda_af = af_num(sda);
+
+            let released = {
+                let mut scl = Flex::new(scl.reborrow());
+                let mut sda = Flex::new(sda.reborrow());
+                scl.set_high();
+                sda.set_high();
+                scl.set_as_input_output(Speed::Medium);
+                sda.set_as_input_output(Speed::Medium);
+                half_bit_delay();
+
+                // Clock out the byte the target is stuck in, until it releases SDA.
+                for _ in 0..9 {
+                    if sda.is_high() {
+                        break;
+                    }
+                    scl.set_low();
+                    half_bit_delay();
+                    scl.set_high();
+                    half_bit_delay();
+                }
+
+                // STOP condition: SDA rising while SCL is high.
+                scl.set_low();
+                half_bit_delay();
+                sda.set_low();
+                half_bit_delay();
+                scl.set_high();
+                half_bit_delay();
+                sda.set_high();
+                half_bit_delay();
+
+                sda.is_high()
+            };
+
+            scl.set_as_af(scl_af, self.scl_af);
+            sda.set_as_af(sda_af, self.sda_af);
+
+            released
+        };
+
+        self.reset_peripheral();
+
+        if released {
+            Ok(())
+        } else {
+            Err(Error::Bus)
+        }
+    }
+
+    /// Apply the SWRST errata workaround at runtime, keeping the current configuration.
+    fn reset_peripheral(&mut self) {
+        let regs = self.info.regs;
+
+        let mut cr1 = regs.cr1().read();
+        let mut cr2 = regs.cr2().read();
+        let ccr = regs.ccr().read();
+        let trise = regs.trise().read();
+        let oar1 = regs.oar1().read();
+        let oar2 = regs.oar2().read();
+
+        regs.cr1().modify(|reg| reg.set_pe(false));
+        regs.cr1().modify(|reg| reg.set_swrst(true));
+        regs.cr1().modify(|reg| reg.set_swrst(false));
+
+        cr2.set_dmaen(false);
+        cr2.set_itevten(false);
+        cr2.set_iterren(false);
+        cr2.set_itbufen(false);
+        regs.cr2().write_value(cr2);
+        regs.ccr().write_value(ccr);
+        regs.trise().write_value(trise);
+        regs.oar1().write_value(oar1);
+        regs.oar2().write_value(oar2);
+
+        cr1.set_start(false);
+        cr1.set_stop(false);
+        cr1.set_swrst(false);
+        cr1.set_pe(true);
+        regs.cr1().write_value(cr1);
+    }
+
+    /// Wait for the BUSY flag to clear before starting a transfer.
+    ///
+    /// Only done with [`Config::auto_recovery`], where a BUSY flag that is still set when the
+    /// timeout expires is treated as a lockup and triggers [`recover_bus`](Self::recover_bus).
+    fn wait_bus_free(&mut self, timeout: Timeout) -> Result<(), Error> {
+        if !self.auto_recovery {
+            return Ok(());
+        }
+
+        let mut spins = 0;
+        while self.info.regs.sr2().read().busy() {
+            if Self::bus_stuck(timeout, &mut spins) {
+                warn!("i2c: BUSY stuck, recovering bus");
+                return self.recover_bus();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the BUSY flag has been set for too long, after `spins` polls so far.
+    ///
+    /// Without the `time` feature the timeout never expires, the number of polls is bounded
+    /// instead.
+    fn bus_stuck(timeout: Timeout, spins: &mut u32) -> bool {
+        *spins += 1;
+        timeout.check().is_err() || (cfg!(not(feature = "time")) && *spins > BUSY_MAX_SPINS)
+    }
+
+    pub(super) fn recover_on_timeout<R>(&mut self, result: Result<R, Error>) -> Result<R, Error> {
+        if self.auto_recovery {
+            if let Err(Error::Timeout) = result {
+                warn!("i2c: transfer timed out, recovering bus");
+                // The transfer failed anyway, report the timeout rather than the recovery result.
+                let _ = self.recover_bus();
+            }
+        }
+
+        result
+    }
+
     // Async
     #[cfg(dma)]
     #[inline] // pretty sure this should always be inlined
@@ -375,7 +533,7 @@ impl<'d, M: PeriMode> I2c<'d, M> {
 
 #[cfg(dma)] 
 impl<'d> I2c<'d, Async> {
-    async fn write_frame(
+    pub(super) async fn write_frame(
         &mut self,
         address: u8,
         write: &[u8],
@@ -527,23 +685,61 @@ impl<'d> I2c<'d, Async> {
         Ok(())
     }
 
+    /// Like [`wait_bus_free`](Self::wait_bus_free), but yields to the executor while the BUSY
+    /// flag is set instead of spinning.
+    async fn wait_bus_free_async(&mut self, timeout: Timeout) -> Result<(), Error> {
+        let mut spins = 0;
+        while self.info.regs.sr2().read().busy() {
+            if Self::bus_stuck(timeout, &mut spins) {
+                warn!("i2c: BUSY stuck, recovering bus");
+                return self.recover_bus();
+            }
+            embassy_futures::yield_now().await;
+        }
+
+        Ok(())
+    }
+
     /// Write.
+    ///
+    /// With [`Config::auto_recovery`], the transfer is aborted after the timeout and the bus is
+    /// recovered, see [`recover_bus`](Self::recover_bus). Otherwise there is no timeout.
     pub async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Error> {
-        self.write_frame(address, write, FrameOptions::FirstAndLastFrame)
-            .await?;
+        if !self.auto_recovery {
+            return self
+                .write_frame(address, write, FrameOptions::FirstAndLastFrame)
+                .await;
+        }
 
-        Ok(())
+        let timeout = self.timeout();
+        self.wait_bus_free_async(timeout).await?;
+
+        let result = timeout
+            .with(self.write_frame(address, write, FrameOptions::FirstAndLastFrame))
+            .await;
+        self.recover_on_timeout(result)
     }
 
     /// Read.
+    ///
+    /// See [`write`](Self::write) for the timeout.
     pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
-        self.read_frame(address, buffer, FrameOptions::FirstAndLastFrame)
-            .await?;
+        if !self.auto_recovery {
+            return self
+                .read_frame(address, buffer, FrameOptions::FirstAndLastFrame)
+                .await;
+        }
 
-        Ok(())
+        let timeout = self.timeout();
+        self.wait_bus_free_async(timeout).await?;
+
+        let result = timeout
+            .with(self.read_frame(address, buffer, FrameOptions::FirstAndLastFrame))
+            .await;
+        self.recover_on_timeout(result)
     }
 
-    async fn read_frame(
+    pub(super) async fn read_frame(
         &mut self,
         address: u8,
         buffer: &mut [u8],
@@ -711,6 +907,8 @@ impl<'d> I2c<'d, Async> {
     }
 
     /// Write, restart, read.
+    ///
+    /// See [`write`](Self::write) for the timeout.
     pub async fn write_read(
         &mut self,
         address: u8,
@@ -723,6 +921,25 @@ impl<'d> I2c<'d, Async> {
             return Err(Error::Overrun);
         }
 
+        if !self.auto_recovery {
+            return self.write_read_frames(address, write, read).await;
+        }
+
+        let timeout = self.timeout();
+        self.wait_bus_free_async(timeout).await?;
+
+        let result = timeout
+            .with(self.write_read_frames(address, write, read))
+            .await;
+        self.recover_on_timeout(result)
+    }
+
+    async fn write_read_frames(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Error> {
         self.write_frame(address, write, FrameOptions::FirstFrame)
             .await?;
         self.read_frame(address, read, FrameOptions::FirstAndLastFrame)
@@ -733,11 +950,31 @@ impl<'d> I2c<'d, Async> {
     ///
     /// Consecutive operations of same type are merged. See [transaction contract] for details.
     ///
+    /// See [`write`](Self::write) for the timeout.
+    ///
     /// [transaction contract]: embedded_hal_1::i2c::I2c::transaction
     pub async fn transaction(
         &mut self,
         addr: u8,
         operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        if !self.auto_recovery {
+            return self.transaction_frames(addr, operations).await;
+        }
+
+        let timeout = self.timeout();
+        self.wait_bus_free_async(timeout).await?;
+
+        let result = timeout
+            .with(self.transaction_frames(addr, operations))
+            .await;
+        self.recover_on_timeout(result)
+    }
+
+    async fn transaction_frames(
+        &mut self,
+        addr: u8,
+        operations: &mut [Operation<'_>],
     ) -> Result<(), Error> {
         for (op, frame) in operation_frames(operations)? {
             match op {
@@ -750,6 +987,237 @@ impl<'d> I2c<'d, Async> {
     }
 }
 
+impl<'d> I2c<'d, Async> {
+    /// Turn this driver into an I2C target (slave) responding to the addresses in `config`.
+    pub fn into_slave(self, config: SlaveAddrConfig) -> I2cSlave<'d> {
+        let regs = self.info.regs;
+
+        regs.oar1().write(|reg| {
+            reg.set_addmode(i2c::vals::Addmode::BIT7);
+            reg.set_add((config.addr as u16) << 1);
+        });
+        regs.oar2().write(|reg| {
+            if let Some(dual_addr) = config.dual_addr {
+                reg.set_add2(dual_addr);
+                reg.set_endual(i2c::vals::Endual::DUAL);
+            } else {
+                reg.set_endual(i2c::vals::Endual::SINGLE);
+            }
+        });
+        regs.cr1().modify(|reg| {
+            reg.set_engc(config.general_call);
+            reg.set_ack(true);
+        });
+
+        I2cSlave {
+            i2c: self,
+            restarted: false,
+        }
+    }
+}
+
+impl<'d> I2cSlave<'d> {
+    /// Wait for an event in target mode.
+    ///
+    /// `AF` is not treated as an error here: in slave transmitter mode it is how the controller
+    /// ends a read. The event flags stay set when the interrupt fires, `on_interrupt` only
+    /// masks the interrupts and wakes the task, which then clears the flags itself.
+    async fn wait_event(
+        &mut self,
+        f: impl Fn(i2c::regs::Sr1) -> bool,
+    ) -> Result<i2c::regs::Sr1, Error> {
+        let info = self.i2c.info;
+
+        poll_fn(|cx| {
+            self.i2c.state.waker.register(cx.waker());
+
+            let sr1 = info.regs.sr1().read();
+
+            if sr1.ovr() {
+                info.regs.sr1().write(|reg| {
+                    reg.0 = !0;
+                    reg.set_ovr(false);
+                });
+                return Poll::Ready(Err(Error::Overrun));
+            }
+
+            if sr1.berr() {
+                info.regs.sr1().write(|reg| {
+                    reg.0 = !0;
+                    reg.set_berr(false);
+                });
+                return Poll::Ready(Err(Error::Bus));
+            }
+
+            if f(sr1) {
+                Poll::Ready(Ok(sr1))
+            } else {
+                // When pending, (re-)enable interrupts to wake us up.
+                info.regs.cr2().modify(|w| {
+                    w.set_iterren(true);
+                    w.set_itevten(true);
+                    w.set_itbufen(true);
+                });
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    fn clear_stopf(regs: i2c::I2c) {
+        // STOPF is cleared by reading SR1 followed by a write to CR1.
+        let _ = regs.sr1().read();
+        regs.cr1().modify(|_| {});
+    }
+
+    fn clear_af(regs: i2c::I2c) {
+        regs.sr1().write(|reg| {
+            reg.0 = !0;
+            reg.set_af(false);
+        });
+    }
+
+    /// Wait until the controller addresses us.
+    ///
+    /// Stale STOP and NACK events from a previous transfer are discarded.
+    pub async fn listen(&mut self) -> Result<Command, Error> {
+        let regs = self.i2c.info.regs;
+        let restarted = core::mem::replace(&mut self.restarted, false);
+
+        // A controller read ends with a NACK then a STOP, after `respond_to_read` returned.
+        // Both flags raise the event or error interrupt, so they are cleared every time before
+        // `wait_event` enables the interrupts, or the task would be woken up in a loop.
+        self.wait_event(|sr1| {
+            if sr1.stopf() {
+                Self::clear_stopf(regs);
+            }
+            if sr1.af() {
+                Self::clear_af(regs);
+            }
+            sr1.addr()
+        })
+        .await?;
+
+        // Reading SR2 after SR1 clears ADDR and releases SCL.
+        let sr2 = self.i2c.info.regs.sr2().read();
+
+        let kind = match (sr2.tra(), restarted) {
+            (false, _) => CommandKind::Write,
+            (true, false) => CommandKind::Read,
+            (true, true) => CommandKind::WriteRead,
+        };
+        let address = if sr2.gencall() {
+            MatchedAddress::GeneralCall
+        } else if sr2.dualf() {
+            MatchedAddress::Dual
+        } else {
+            MatchedAddress::Primary
+        };
+
+        Ok(Command { kind, address })
+    }
+
+    /// Receive the data written by the controller after a [`CommandKind::Write`].
+    ///
+    /// Returns the number of bytes received. Bytes beyond the size of `buffer` are NACKed.
+    /// The transfer ends with either a STOP or a repeated START; in the latter case the next
+    /// [`listen`](Self::listen) returns [`CommandKind::WriteRead`].
+    pub async fn respond_to_write(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let regs = self.i2c.info.regs;
+        let mut len = 0;
+
+        regs.cr1().modify(|w| w.set_ack(!buffer.is_empty()));
+
+        let result = loop {
+            let sr1 = match self
+                .wait_event(|sr1| sr1.rxne() || sr1.stopf() || sr1.addr())
+                .await
+            {
+                Ok(sr1) => sr1,
+                Err(e) => break Err(e),
+            };
+
+            // Drain received data before looking at the end conditions.
+            if sr1.rxne() {
+                let byte = regs.dr().read().dr();
+                if let Some(slot) = buffer.get_mut(len) {
+                    *slot = byte;
+                    len += 1;
+                }
+                if len == buffer.len() {
+                    // NACK anything that does not fit.
+                    regs.cr1().modify(|w| w.set_ack(false));
+                }
+                continue;
+            }
+
+            if sr1.stopf() {
+                Self::clear_stopf(regs);
+                break Ok(len);
+            }
+
+            // Repeated START: ADDR is left set for the next `listen`.
+            self.restarted = true;
+            break Ok(len);
+        };
+
+        regs.cr1().modify(|w| w.set_ack(true));
+        result
+    }
+
+    /// Send `buffer` to the controller after a [`CommandKind::Read`] or [`CommandKind::WriteRead`].
+    ///
+    /// The controller ends the transfer by NACKing the last byte it wants.
+    pub async fn respond_to_read(&mut self, buffer: &[u8]) -> Result<SendStatus, Error> {
+        let regs = self.i2c.info.regs;
+        let mut written = 0;
+
+        loop {
+            let sr1 = self.wait_event(|sr1| sr1.txe() || sr1.af()).await?;
+
+            if sr1.af() {
+                Self::clear_af(regs);
+                // If DR was loaded but not shifted out yet, that byte was not sent.
+                let sent = if sr1.txe() {
+                    written
+                } else {
+                    written.saturating_sub(1)
+                };
+                return Ok(if sent >= buffer.len() {
+                    SendStatus::Done
+                } else {
+                    SendStatus::LeftoverBytes(buffer.len() - sent)
+                });
+            }
+
+            let byte = buffer.get(written).copied().unwrap_or(0xFF);
+            regs.dr().write(|reg| reg.set_dr(byte));
+            written += 1;
+        }
+    }
+}
+
+/// Read back the AF number currently selected for `pin`.
+fn af_num(pin: &PeripheralRef<'_, AnyPin>) -> u8 {
+    let n = pin._pin() as usize;
+    pin.block().afr(n / 8).read().afr(n % 8)
+}
+
+/// Number of BUSY polls before a lockup is assumed, when there is no `time` feature for the
+/// timeout.
+const BUSY_MAX_SPINS: u32 = 100_000;
+
+/// Half of an SCL period at 100kHz, used while bit-banging the bus during recovery.
+fn half_bit_delay() {
+    #[cfg(feature = "time")]
+    embassy_time::block_for(embassy_time::Duration::from_micros(5));
+    #[cfg(not(feature = "time"))]
+    {
+        let freq = unsafe { crate::rcc::get_freqs() }.sys.to_hertz().unwrap().0;
+        cortex_m::asm::delay(freq / 200_000);
+    }
+}
+
 enum Mode {
     Fast,
     Standard,
@@ -0,0 +1,514 @@
+//! Inter-IC Sound (I2S)
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use embassy_futures::yield_now;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use crate::dma::{ReadableRingBuffer, TransferOptions, WritableRingBuffer};
+use crate::gpio::{AfType, AnyPin, OutputType, Pull, SealedPin as _, Speed};
+use crate::pac::spi::vals;
+use crate::rcc::SealedRccPeripheral;
+use crate::spi::{
+    CkPin, Info, Instance, MckPin, MosiPin, RxDma, SealedInstance as _, TxDma, WsPin,
+};
+use crate::time::Hertz;
+use crate::Peripheral;
+
+/// I2S error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The driver was created in RX mode, it can't write.
+    NotATransmitter,
+    /// The driver was created in TX mode, it can't read.
+    NotAReceiver,
+    /// The DMA caught up with the samples, the stream has a gap.
+    Overrun,
+}
+
+/// I2S standard.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Standard {
+    /// Philips (standard I2S).
+    Philips,
+    /// Most significant bit first, left justified.
+    MsbFirst,
+    /// Least significant bit first, right justified.
+    LsbFirst,
+    /// PCM with short frame synchronization.
+    PcmShortSync,
+    /// PCM with long frame synchronization.
+    PcmLongSync,
+}
+
+impl Standard {
+    const fn i2sstd(&self) -> vals::I2sstd {
+        match self {
+            Standard::Philips => vals::I2sstd::PHILIPS,
+            Standard::MsbFirst => vals::I2sstd::MSB,
+            Standard::LsbFirst => vals::I2sstd::LSB,
+            Standard::PcmShortSync => vals::I2sstd::PCM,
+            Standard::PcmLongSync => vals::I2sstd::PCM,
+        }
+    }
+
+    const fn pcmsync(&self) -> vals::Pcmsync {
+        match self {
+            Standard::PcmLongSync => vals::Pcmsync::LONG,
+            _ => vals::Pcmsync::SHORT,
+        }
+    }
+}
+
+/// Data and channel length.
+///
+/// The samples are 16-bit halfwords: 24 and 32-bit data take two halfwords per channel,
+/// most significant halfword first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Format {
+    /// 16 bit data length on 16 bit wide channel
+    Data16Channel16,
+    /// 16 bit data length on 32 bit wide channel
+    Data16Channel32,
+    /// 24 bit data length on 32 bit wide channel
+    Data24Channel32,
+    /// 32 bit data length on 32 bit wide channel
+    Data32Channel32,
+}
+
+impl Format {
+    const fn datlen(&self) -> vals::Datlen {
+        match self {
+            Format::Data16Channel16 => vals::Datlen::BITS16,
+            Format::Data16Channel32 => vals::Datlen::BITS16,
+            Format::Data24Channel32 => vals::Datlen::BITS24,
+            Format::Data32Channel32 => vals::Datlen::BITS32,
+        }
+    }
+
+    const fn chlen(&self) -> vals::Chlen {
+        match self {
+            Format::Data16Channel16 => vals::Chlen::BITS16,
+            _ => vals::Chlen::BITS32,
+        }
+    }
+
+    /// Bit clock cycles per frame (two channels).
+    const fn frame_bits(&self) -> u32 {
+        match self {
+            Format::Data16Channel16 => 32,
+            _ => 64,
+        }
+    }
+}
+
+/// Clock polarity
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockPolarity {
+    /// Low on idle.
+    IdleLow,
+    /// High on idle.
+    IdleHigh,
+}
+
+impl ClockPolarity {
+    const fn ckpol(&self) -> vals::Ckpol {
+        match self {
+            ClockPolarity::IdleHigh => vals::Ckpol::IDLEHIGH,
+            ClockPolarity::IdleLow => vals::Ckpol::IDLELOW,
+        }
+    }
+}
+
+/// [`I2S`] configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// Standard.
+    pub standard: Standard,
+    /// Format.
+    pub format: Format,
+    /// Clock polarity.
+    pub clock_polarity: ClockPolarity,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            standard: Standard::Philips,
+            format: Format::Data16Channel16,
+            clock_polarity: ClockPolarity::IdleLow,
+        }
+    }
+}
+
+/// Prescaler settings for an audio frequency.
+struct Clocks {
+    odd: bool,
+    div: u8,
+    frequency: Hertz,
+}
+
+/// Compute the prescaler for `freq`, and the audio frequency actually reached.
+fn compute_clocks(i2s_clock: Hertz, freq: Hertz, master_clock: bool, format: Format) -> Clocks {
+    // The bit clock is divided from the master clock (256 * fs) when it is output.
+    let coef = if master_clock {
+        256
+    } else {
+        format.frame_bits()
+    };
+    let (n, d) = (i2s_clock.0, coef * freq.0);
+    let division = (n + (d >> 1)) / d;
+    let division = division.clamp(4, 511);
+
+    Clocks {
+        odd: division & 1 == 1,
+        div: (division >> 1) as u8,
+        frequency: Hertz(i2s_clock.0 / (coef * division)),
+    }
+}
+
+/// I2S driver, streaming samples over DMA.
+///
+/// The samples are written to or read from a DMA ring buffer, so the stream keeps going
+/// while the task is busy, as long as it keeps up on average. Stereo samples are
+/// interleaved, left channel first.
+pub struct I2S<'d> {
+    info: &'static Info,
+    _sd: Option<PeripheralRef<'d, AnyPin>>,
+    _ws: Option<PeripheralRef<'d, AnyPin>>,
+    _ck: Option<PeripheralRef<'d, AnyPin>>,
+    _mck: Option<PeripheralRef<'d, AnyPin>>,
+    tx_ring_buffer: Option<WritableRingBuffer<'d, u16>>,
+    rx_ring_buffer: Option<ReadableRingBuffer<'d, u16>>,
+    requested_frequency: Hertz,
+    frequency: Hertz,
+}
+
+impl<'d> I2S<'d> {
+    /// Create a transmitter driver, with a master clock output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_txonly<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        ws: impl Peripheral<P = impl WsPin<T>> + 'd,
+        ck: impl Peripheral<P = impl CkPin<T>> + 'd,
+        mck: impl Peripheral<P = impl MckPin<T>> + 'd,
+        txdma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        txdma_buf: &'d mut [u16],
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        let mut this = Self::new_inner(
+            peri,
+            new_pin!(sd, output_af()),
+            new_pin!(ws, output_af()),
+            new_pin!(ck, output_af()),
+            new_pin!(mck, output_af()),
+            vals::I2scfg::MASTERTX,
+            freq,
+            config,
+        );
+        this.new_tx_ring_buffer(txdma, txdma_buf);
+        this
+    }
+
+    /// Create a transmitter driver, without master clock output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_txonly_nomck<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        ws: impl Peripheral<P = impl WsPin<T>> + 'd,
+        ck: impl Peripheral<P = impl CkPin<T>> + 'd,
+        txdma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        txdma_buf: &'d mut [u16],
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        let mut this = Self::new_inner(
+            peri,
+            new_pin!(sd, output_af()),
+            new_pin!(ws, output_af()),
+            new_pin!(ck, output_af()),
+            None,
+            vals::I2scfg::MASTERTX,
+            freq,
+            config,
+        );
+        this.new_tx_ring_buffer(txdma, txdma_buf);
+        this
+    }
+
+    /// Create a receiver driver, with a master clock output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_rxonly<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        ws: impl Peripheral<P = impl WsPin<T>> + 'd,
+        ck: impl Peripheral<P = impl CkPin<T>> + 'd,
+        mck: impl Peripheral<P = impl MckPin<T>> + 'd,
+        rxdma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        rxdma_buf: &'d mut [u16],
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        let mut this = Self::new_inner(
+            peri,
+            new_pin!(sd, AfType::input(Pull::None)),
+            new_pin!(ws, output_af()),
+            new_pin!(ck, output_af()),
+            new_pin!(mck, output_af()),
+            vals::I2scfg::MASTERRX,
+            freq,
+            config,
+        );
+        this.new_rx_ring_buffer(rxdma, rxdma_buf);
+        this
+    }
+
+    /// Create a receiver driver, without master clock output.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_rxonly_nomck<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sd: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        ws: impl Peripheral<P = impl WsPin<T>> + 'd,
+        ck: impl Peripheral<P = impl CkPin<T>> + 'd,
+        rxdma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        rxdma_buf: &'d mut [u16],
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        let mut this = Self::new_inner(
+            peri,
+            new_pin!(sd, AfType::input(Pull::None)),
+            new_pin!(ws, output_af()),
+            new_pin!(ck, output_af()),
+            None,
+            vals::I2scfg::MASTERRX,
+            freq,
+            config,
+        );
+        this.new_rx_ring_buffer(rxdma, rxdma_buf);
+        this
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new_inner<T: Instance>(
+        _peri: impl Peripheral<P = T> + 'd,
+        sd: Option<PeripheralRef<'d, AnyPin>>,
+        ws: Option<PeripheralRef<'d, AnyPin>>,
+        ck: Option<PeripheralRef<'d, AnyPin>>,
+        mck: Option<PeripheralRef<'d, AnyPin>>,
+        i2scfg: vals::I2scfg,
+        freq: Hertz,
+        config: Config,
+    ) -> Self {
+        // The master clock runs at 256 times the audio frequency.
+        let master_clock = mck.is_some();
+
+        let info = T::info();
+        info.rcc.enable_and_reset();
+
+        let clocks = compute_clocks(T::frequency(), freq, master_clock, config.format);
+        if clocks.frequency != freq {
+            warn!(
+                "I2S: requested {} Hz, got {} Hz",
+                freq.0, clocks.frequency.0
+            );
+        }
+
+        let regs = info.regs;
+        regs.i2spr().modify(|w| {
+            w.set_i2sdiv(clocks.div);
+            w.set_odd(match clocks.odd {
+                true => vals::Odd::ODD,
+                false => vals::Odd::EVEN,
+            });
+            w.set_mckoe(master_clock);
+        });
+
+        regs.i2scfgr().modify(|w| {
+            w.set_ckpol(config.clock_polarity.ckpol());
+            w.set_i2smod(true);
+            w.set_i2scfg(i2scfg);
+            w.set_i2sstd(config.standard.i2sstd());
+            w.set_pcmsync(config.standard.pcmsync());
+            w.set_datlen(config.format.datlen());
+            w.set_chlen(config.format.chlen());
+        });
+
+        Self {
+            info,
+            _sd: sd,
+            _ws: ws,
+            _ck: ck,
+            _mck: mck,
+            tx_ring_buffer: None,
+            rx_ring_buffer: None,
+            requested_frequency: freq,
+            frequency: clocks.frequency,
+        }
+    }
+
+    fn new_tx_ring_buffer<T: Instance>(
+        &mut self,
+        txdma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        txdma_buf: &'d mut [u16],
+    ) {
+        into_ref!(txdma);
+        let request = txdma.request();
+        let tx_dst = self.info.regs.tx_ptr::<u16>();
+        self.tx_ring_buffer = Some(unsafe {
+            WritableRingBuffer::new(txdma, request, tx_dst, txdma_buf, dma_options())
+        });
+    }
+
+    fn new_rx_ring_buffer<T: Instance>(
+        &mut self,
+        rxdma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        rxdma_buf: &'d mut [u16],
+    ) {
+        into_ref!(rxdma);
+        let request = rxdma.request();
+        let rx_src = self.info.regs.rx_ptr::<u16>();
+        self.rx_ring_buffer = Some(unsafe {
+            ReadableRingBuffer::new(rxdma, request, rx_src, rxdma_buf, dma_options())
+        });
+    }
+
+    /// Audio frequency actually reached with the prescaler.
+    pub fn audio_frequency(&self) -> Hertz {
+        self.frequency
+    }
+
+    /// Difference between the actual and the requested audio frequency, in parts per million.
+    pub fn audio_frequency_error_ppm(&self) -> i32 {
+        let requested = self.requested_frequency.0 as i64;
+        let actual = self.frequency.0 as i64;
+        ((actual - requested) * 1_000_000 / requested) as i32
+    }
+
+    /// Start the I2S stream.
+    ///
+    /// For a transmitter, fill the buffer with [`write_immediate`](Self::write_immediate) first.
+    pub fn start(&mut self) {
+        let regs = self.info.regs;
+
+        if let Some(tx) = self.tx_ring_buffer.as_mut() {
+            tx.start();
+            regs.cr2().modify(|w| w.set_txdmaen(true));
+        }
+        if let Some(rx) = self.rx_ring_buffer.as_mut() {
+            rx.start();
+            regs.cr2().modify(|w| w.set_rxdmaen(true));
+        }
+
+        regs.i2scfgr().modify(|w| w.set_i2se(true));
+    }
+
+    /// Stop the I2S stream.
+    ///
+    /// A transmitter outputs the samples left in the buffer first.
+    pub async fn stop(&mut self) {
+        let regs = self.info.regs;
+
+        if let Some(tx) = self.tx_ring_buffer.as_mut() {
+            tx.stop().await;
+            // Wait for the last sample to be shifted out, a few bit clock periods.
+            while !regs.sr().read().txe() || regs.sr().read().bsy() {
+                yield_now().await;
+            }
+            regs.cr2().modify(|w| w.set_txdmaen(false));
+        }
+        if let Some(rx) = self.rx_ring_buffer.as_mut() {
+            rx.request_stop();
+            // No interrupt signals the end of the transfer, poll until the channel is disabled.
+            while rx.is_running() {
+                yield_now().await;
+            }
+            regs.cr2().modify(|w| w.set_rxdmaen(false));
+        }
+
+        regs.i2scfgr().modify(|w| w.set_i2se(false));
+    }
+
+    /// Discard the samples in the buffer.
+    pub fn clear(&mut self) {
+        if let Some(tx) = self.tx_ring_buffer.as_mut() {
+            tx.clear();
+        }
+        if let Some(rx) = self.rx_ring_buffer.as_mut() {
+            rx.clear();
+        }
+    }
+
+    /// Write samples to the buffer before [`start`](Self::start).
+    ///
+    /// Returns the number of samples written.
+    pub fn write_immediate(&mut self, data: &[u16]) -> Result<usize, Error> {
+        match self.tx_ring_buffer.as_mut() {
+            Some(tx) => tx
+                .write_immediate(data)
+                .map(|(written, _)| written)
+                .map_err(|_| Error::Overrun),
+            None => Err(Error::NotATransmitter),
+        }
+    }
+
+    /// Write all the samples, waiting for the DMA to free space in the buffer.
+    pub async fn write(&mut self, data: &[u16]) -> Result<(), Error> {
+        match self.tx_ring_buffer.as_mut() {
+            Some(tx) => tx
+                .write_exact(data)
+                .await
+                .map(|_| ())
+                .map_err(|_| Error::Overrun),
+            None => Err(Error::NotATransmitter),
+        }
+    }
+
+    /// Read samples, waiting for the DMA to receive them.
+    pub async fn read(&mut self, data: &mut [u16]) -> Result<(), Error> {
+        match self.rx_ring_buffer.as_mut() {
+            Some(rx) => rx
+                .read_exact(data)
+                .await
+                .map(|_| ())
+                .map_err(|_| Error::Overrun),
+            None => Err(Error::NotAReceiver),
+        }
+    }
+}
+
+impl<'d> Drop for I2S<'d> {
+    fn drop(&mut self) {
+        // Stop the DMA before the peripheral.
+        self.tx_ring_buffer = None;
+        self.rx_ring_buffer = None;
+
+        self._sd.as_ref().map(|x| x.set_as_disconnected());
+        self._ws.as_ref().map(|x| x.set_as_disconnected());
+        self._ck.as_ref().map(|x| x.set_as_disconnected());
+        self._mck.as_ref().map(|x| x.set_as_disconnected());
+
+        self.info.regs.i2scfgr().modify(|w| w.set_i2se(false));
+        self.info.rcc.disable();
+    }
+}
+
+fn output_af() -> AfType {
+    AfType::output(OutputType::PushPull, Speed::VeryHigh)
+}
+
+fn dma_options() -> TransferOptions {
+    TransferOptions {
+        half_transfer_ir: true,
+        ..Default::default()
+    }
+}
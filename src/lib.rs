@@ -35,14 +35,33 @@ pub mod mode {
 }
 
 pub mod adc;
+#[cfg(comp)]
+pub mod comp;
+#[cfg(crc)]
+pub mod crc;
+#[cfg(dac)]
+pub mod dac;
 #[cfg(dma)]
 pub mod dma;
 pub mod flash;
 pub mod gpio;
 pub mod i2c;
+#[cfg(all(dma, i2s))]
+pub mod i2s;
+#[cfg(feature = "low-power")]
+pub mod low_power;
+#[cfg(lptim)]
+pub mod lptim;
+pub mod pwr;
 pub mod rcc;
+#[cfg(rtc)]
+pub mod rtc;
+pub mod spi;
+pub mod system;
 pub mod timer;
 pub mod usart;
+#[cfg(iwdg)]
+pub mod wdg;
 
 #[cfg(any(feature = "embassy-usb-driver-impl", feature = "usb-device-impl"))]
 pub mod usb;
@@ -54,12 +73,25 @@ pub mod embassy;
 pub mod time;
 #[cfg(feature = "time-driver-systick")]
 pub use embassy::systick_time_driver;
-#[cfg(all(feature = "_time-driver", not(feature = "time-driver-systick")))]
+#[cfg(all(
+    feature = "_time-driver",
+    not(any(feature = "time-driver-systick", feature = "time-driver-lptim"))
+))]
 pub use embassy::time_driver;
+#[cfg(feature = "time-driver-lptim")]
+pub use embassy::lptim_time_driver;
 
 #[cfg(feature = "time-driver-systick")]
 use cortex_m::peripheral::SYST;
 
+#[cfg(all(
+    feature = "low-power",
+    any(not(feature = "_time-driver"), feature = "time-driver-systick")
+))]
+compile_error!(
+    "The `low-power` feature requires a `time-driver-tim*`, `time-driver-any` or `time-driver-lptim` feature."
+);
+
 /// `py32-hal` global configuration.
 #[non_exhaustive]
 #[derive(Clone, Copy)]
@@ -117,6 +149,7 @@ pub fn init(config: Config, #[cfg(feature = "time-driver-systick")] systick: SYS
         let p = Peripherals::take_with_cs(cs);
         
         rcc::enable_and_reset_with_cs::<peripherals::DBGMCU>(cs);
+        system::init();
         crate::pac::DBGMCU.cr().modify(|cr| {
             #[cfg(dbgmcu_f072)]
             cr.set_dbg_sleep(config.enable_debug_during_sleep);
@@ -125,14 +158,21 @@ pub fn init(config: Config, #[cfg(feature = "time-driver-systick")] systick: SYS
 
         unsafe {
             rcc::init(config.rcc);
+            rcc::RCC_CONFIG = Some(config.rcc);
             crate::_generated::init_syscfg();
 
             gpio::init(cs);
 
             // must be after rcc init
-            #[cfg(all(feature = "_time-driver", not(feature = "time-driver-systick")))]
+            #[cfg(all(
+                feature = "_time-driver",
+                not(any(feature = "time-driver-systick", feature = "time-driver-lptim"))
+            ))]
             time_driver::init(cs);
 
+            #[cfg(feature = "time-driver-lptim")]
+            lptim_time_driver::init(cs);
+
             #[cfg(feature = "time-driver-systick")]
             systick_time_driver::init(cs, systick);
 
@@ -148,6 +188,21 @@ pub fn init(config: Config, #[cfg(feature = "time-driver-systick")] systick: SYS
     })
 }
 
+/// Initialize the HAL like [`init`], also returning the reset cause and chip identity.
+///
+/// See [`system::info`].
+pub fn init_with_info(
+    config: Config,
+    #[cfg(feature = "time-driver-systick")] systick: SYST,
+) -> (Peripherals, system::SystemInfo) {
+    #[cfg(feature = "time-driver-systick")]
+    let p = init(config, systick);
+    #[cfg(not(feature = "time-driver-systick"))]
+    let p = init(config);
+
+    (p, system::info())
+}
+
 // This must go last, so that it sees all the impl_foo! macros defined earlier.
 pub(crate) mod _generated {
     #![allow(dead_code)]
@@ -0,0 +1,204 @@
+//! Low-power executor
+//!
+//! The low-power executor puts the chip in Stop mode whenever it is idle and the next timer
+//! alarm is far enough in the future. In Stop mode the HSI, HSE and PLL are off, so the
+//! time driver timer is paused and the RTC alarm is used to wake the chip up; the time spent
+//! in Stop mode is then added back to the time driver.
+//!
+//! To use it, enable the `low-power` feature and a `time-driver-tim*` feature, then:
+//!
+//! - replace `embassy_executor::main` with
+//!   `embassy_executor::main(executor = "py32_hal::low_power::Executor")`,
+//! - create an [`Rtc`](crate::rtc::Rtc), make it `'static` (for example with `static_cell`)
+//!   and pass it to `stop_with_rtc`. Until then the executor only uses Sleep mode.
+//!
+//! The RTC interrupt must be bound to [`rtc::InterruptHandler`](crate::rtc::InterruptHandler).
+//!
+//! With `time-driver-lptim` instead, the time driver keeps counting and wakes the chip up
+//! from Stop mode by itself, so no RTC is needed and the one second limit below does not apply.
+//!
+//! Stop mode is entered only when no peripheral clocked by the HSI, HSE or PLL is enabled
+//! (see [`stop_ready`]). Drop such drivers, or keep a [`StopInhibit`] alive, to control when
+//! the chip may stop. The RTC alarm has a resolution of one second, so Stop mode is only
+//! used when the next timer alarm is at least one second away; the chip may wake up to a
+//! second early and go back to sleep.
+//!
+//! On wakeup the clock configuration passed to [`init`](crate::init) is restored.
+//!
+//! With the `low-power-debug-with-sleep` feature, Sleep mode is used instead of Stop mode,
+//! which keeps the debug probe attached while exercising the same code paths.
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use core::arch::asm;
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use cortex_m::peripheral::SCB;
+use embassy_executor::*;
+
+#[cfg(feature = "time-driver-lptim")]
+use crate::lptim_time_driver::{get_driver, LptimDriver as TimeDriver};
+use crate::rcc::{self, REFCOUNT_STOP1};
+#[cfg(not(feature = "time-driver-lptim"))]
+use crate::rtc::Rtc;
+#[cfg(not(feature = "time-driver-lptim"))]
+use crate::time_driver::{get_driver, RtcDriver as TimeDriver};
+
+const THREAD_PENDER: usize = usize::MAX;
+
+static mut EXECUTOR: Option<Executor> = None;
+
+/// Called from the wakeup interrupts, restores the clocks and the time driver.
+///
+/// Safety: must be called from an interrupt handler.
+pub(crate) unsafe fn on_wakeup_irq() {
+    #[allow(static_mut_refs)]
+    if let Some(executor) = EXECUTOR.as_mut() {
+        executor.on_wakeup_irq();
+    }
+}
+
+/// Configure Stop mode to be woken up by the RTC.
+///
+/// Panics if called more than once.
+#[cfg(not(feature = "time-driver-lptim"))]
+pub fn stop_with_rtc(rtc: &'static Rtc<'static>) {
+    #[allow(static_mut_refs)]
+    unwrap!(unsafe { EXECUTOR.as_mut() }).stop_with_rtc(rtc)
+}
+
+/// Returns `true` if no enabled peripheral or [`StopInhibit`] prevents Stop mode.
+pub fn stop_ready() -> bool {
+    unsafe { REFCOUNT_STOP1 == 0 }
+}
+
+/// Keeps the executor out of Stop mode while it is alive.
+///
+/// Useful while waiting on a peripheral that is not clocked in Stop mode, but whose
+/// driver does not hold its clock enabled, or on an external event that can not wake
+/// the chip up.
+pub struct StopInhibit {
+    _private: (),
+}
+
+impl StopInhibit {
+    /// Prevent Stop mode until the returned guard is dropped.
+    pub fn new() -> Self {
+        critical_section::with(|_| unsafe { REFCOUNT_STOP1 += 1 });
+        Self { _private: () }
+    }
+}
+
+impl Default for StopInhibit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for StopInhibit {
+    fn drop(&mut self) {
+        critical_section::with(|_| unsafe { REFCOUNT_STOP1 -= 1 });
+    }
+}
+
+/// Thread mode executor, using WFE to sleep and Stop mode when possible.
+///
+/// This executor runs tasks in thread mode. The interrupt handler is set up
+/// to poll tasks, and when there is no work to do, it runs the WFE instruction,
+/// entering Stop mode if [`stop_ready`] and the next timer alarm allow it.
+///
+/// Only one instance can exist, [`Executor::take`] panics on a second call.
+pub struct Executor {
+    inner: raw::Executor,
+    not_send: PhantomData<*mut ()>,
+    scb: SCB,
+    time_driver: &'static TimeDriver,
+}
+
+impl Executor {
+    /// Create a new Executor.
+    pub fn take() -> &'static mut Self {
+        critical_section::with(|_| unsafe {
+            #[allow(static_mut_refs)]
+            {
+                assert!(EXECUTOR.is_none());
+
+                EXECUTOR = Some(Self {
+                    inner: raw::Executor::new(THREAD_PENDER as *mut ()),
+                    not_send: PhantomData,
+                    scb: cortex_m::Peripherals::steal().SCB,
+                    time_driver: get_driver(),
+                });
+
+                unwrap!(EXECUTOR.as_mut())
+            }
+        })
+    }
+
+    unsafe fn on_wakeup_irq(&mut self) {
+        rcc::reinit();
+        self.time_driver.resume_time();
+        trace!("low power: resume");
+    }
+
+    #[cfg(not(feature = "time-driver-lptim"))]
+    pub(self) fn stop_with_rtc(&mut self, rtc: &'static Rtc<'static>) {
+        self.time_driver.set_rtc(rtc);
+        trace!("low power: stop with rtc configured");
+    }
+
+    fn configure_pwr(&mut self) {
+        self.scb.clear_sleepdeep();
+
+        compiler_fence(Ordering::SeqCst);
+
+        if !stop_ready() {
+            trace!("low power: not ready to stop");
+            return;
+        }
+
+        if self.time_driver.pause_time().is_err() {
+            trace!("low power: failed to pause time");
+            return;
+        }
+
+        trace!("low power: enter stop");
+
+        #[cfg(not(feature = "low-power-debug-with-sleep"))]
+        self.scb.set_sleepdeep();
+    }
+
+    /// Run the executor.
+    ///
+    /// The `init` closure is called with a [`Spawner`] that spawns tasks on
+    /// this executor. Use it to spawn the initial task(s). After `init` returns,
+    /// the executor starts running the tasks.
+    ///
+    /// To spawn more tasks later, you may keep copies of the [`Spawner`] (it is `Copy`),
+    /// for example by passing it as an argument to the initial tasks.
+    ///
+    /// This function requires `&'static mut self`. This means you have to store the
+    /// Executor instance in a place where it'll live forever and grants you mutable
+    /// access. [`Executor::take`] does this for you.
+    ///
+    /// This function never returns.
+    pub fn run(&'static mut self, init: impl FnOnce(Spawner)) -> ! {
+        #[allow(static_mut_refs)]
+        let executor = unsafe { unwrap!(EXECUTOR.as_mut()) };
+        init(executor.inner.spawner());
+
+        loop {
+            unsafe {
+                executor.inner.poll();
+                self.configure_pwr();
+                asm!("wfe");
+                // Not every wakeup source goes through `on_wakeup_irq`, restore the
+                // clocks and the time driver here too.
+                critical_section::with(|_| self.on_wakeup_irq());
+            };
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Low-power timer (LPTIM)
+//!
+//! The LPTIM is a 16-bit up-counter that can be clocked from the LSI or LSE (selected with
+//! `config.rcc.mux`), so it keeps counting in Stop mode and its interrupts can wake the chip up,
+//! see [`Lptim::enable_wakeup`]. When clocked from PCLK it stops with the other peripherals.
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+pub mod pwm;
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::gpio::{AfType, AnyPin, Pull, SealedPin as _};
+use crate::interrupt::typelevel::Interrupt as _;
+use crate::pac::lptim::vals::Presc;
+use crate::pac::lptim::Lptim as Regs;
+use crate::rcc::{self, RccPeripheral};
+use crate::time::Hertz;
+use crate::{interrupt, pac};
+
+// TODO: move to py32-metapac
+/// EXTI line of the LPTIM wakeup event.
+pub(crate) const WAKEUP_EXTI_LINE: usize = 29;
+
+/// Counter clock prescaler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Prescaler {
+    /// Kernel clock divided by 1.
+    Div1,
+    /// Kernel clock divided by 2.
+    Div2,
+    /// Kernel clock divided by 4.
+    Div4,
+    /// Kernel clock divided by 8.
+    Div8,
+    /// Kernel clock divided by 16.
+    Div16,
+    /// Kernel clock divided by 32.
+    Div32,
+    /// Kernel clock divided by 64.
+    Div64,
+    /// Kernel clock divided by 128.
+    Div128,
+}
+
+impl Prescaler {
+    fn divisor(&self) -> u32 {
+        1 << *self as u32
+    }
+}
+
+/// Edge of the external input that increments the counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockEdge {
+    /// Count on rising edges.
+    Rising,
+    /// Count on falling edges.
+    Falling,
+    /// Count on both edges.
+    Both,
+}
+
+/// LPTIM configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Counter clock prescaler, applied to the kernel clock from RCC.
+    pub prescaler: Prescaler,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prescaler: Prescaler::Div1,
+        }
+    }
+}
+
+/// Interrupt handler.
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        let r = T::regs();
+        let s = T::state();
+
+        // IER can only be written while the LPTIM is disabled, so the interrupts stay enabled
+        // and the flags are moved to the state for the futures.
+        let isr = r.isr().read();
+        r.icr().write(|w| {
+            w.set_arrmcf(isr.arrm());
+            w.set_cmpmcf(isr.cmpm());
+        });
+
+        if isr.arrm() {
+            s.arr_match.store(true, Ordering::Relaxed);
+            s.arr_waker.wake();
+        }
+        if isr.cmpm() {
+            s.cmp_match.store(true, Ordering::Relaxed);
+            s.cmp_waker.wake();
+        }
+
+        #[cfg(feature = "low-power")]
+        crate::low_power::on_wakeup_irq();
+    }
+}
+
+/// LPTIM driver.
+pub struct Lptim<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+    _in1: Option<PeripheralRef<'d, AnyPin>>,
+    config: Config,
+}
+
+impl<'d, T: Instance> Lptim<'d, T> {
+    /// Create a new LPTIM counting the kernel clock.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(peri, None, config)
+    }
+
+    /// Create a new LPTIM counting the edges of the IN1 input.
+    ///
+    /// The kernel clock is still needed to sample the input.
+    pub fn new_external_clock(
+        peri: impl Peripheral<P = T> + 'd,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>> + 'd,
+        in1: impl Peripheral<P = impl Channel1Pin<T>> + 'd,
+        pull: Pull,
+        edge: ClockEdge,
+        config: Config,
+    ) -> Self {
+        into_ref!(in1);
+        critical_section::with(|_| in1.set_as_af(in1.af_num(), AfType::input(pull)));
+
+        let this = Self::new_inner(peri, Some(in1.map_into()), config);
+        T::regs().cfgr().modify(|w| {
+            w.set_countmode(true);
+            w.set_ckpol(match edge {
+                ClockEdge::Rising => 0,
+                ClockEdge::Falling => 1,
+                ClockEdge::Both => 2,
+            });
+        });
+        this
+    }
+
+    fn new_inner(
+        peri: impl Peripheral<P = T> + 'd,
+        in1: Option<PeripheralRef<'d, AnyPin>>,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri);
+
+        rcc::enable_and_reset::<T>();
+
+        let r = T::regs();
+        r.cfgr()
+            .write(|w| w.set_presc(Presc::from_bits(config.prescaler as u8)));
+        r.ier().write(|w| {
+            w.set_arrmie(true);
+            w.set_cmpmie(true);
+        });
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+
+        Self {
+            _peri: peri,
+            _in1: in1,
+            config,
+        }
+    }
+
+    /// Counter clock frequency.
+    ///
+    /// For [`new_external_clock`](Self::new_external_clock), this is the sampling clock.
+    pub fn counter_frequency(&self) -> Hertz {
+        T::frequency() / self.config.prescaler.divisor()
+    }
+
+    /// Enable the LPTIM, it must be enabled to write the compare and autoreload registers.
+    fn enable(&mut self) {
+        T::regs().cr().modify(|w| w.set_enable(true));
+    }
+
+    /// Write the autoreload register, the counter counts from 0 to `arr` included.
+    fn write_arr(&mut self, arr: u16) {
+        let r = T::regs();
+        r.arr().write(|w| w.set_arr(arr));
+        // The write only takes effect once synchronized to the LPTIM clock.
+        while !r.isr().read().arrok() {}
+        r.icr().write(|w| w.set_arrokcf(true));
+    }
+
+    /// Discard the events of an earlier run, they are kept for the next wait otherwise.
+    fn clear_events(&mut self) {
+        let s = T::state();
+        s.arr_match.store(false, Ordering::Relaxed);
+        s.cmp_match.store(false, Ordering::Relaxed);
+    }
+
+    /// Start counting from 0 to `arr` and stop.
+    ///
+    /// [`wait_autoreload`](Self::wait_autoreload) completes when the counter reaches `arr`.
+    pub fn start_one_shot(&mut self, arr: u16) {
+        self.clear_events();
+        self.enable();
+        self.write_arr(arr);
+        T::regs().cr().modify(|w| w.set_sngstrt(true));
+    }
+
+    /// Count continuously from 0 to `arr`.
+    pub fn start_continuous(&mut self, arr: u16) {
+        self.clear_events();
+        self.enable();
+        self.write_arr(arr);
+        T::regs().cr().modify(|w| w.set_cntstrt(true));
+    }
+
+    /// Start counting continuously with a period of `period_us` microseconds.
+    ///
+    /// Panics if the period doesn't fit in the 16-bit counter with the configured prescaler.
+    pub fn start_continuous_us(&mut self, period_us: u32) {
+        let ticks = period_us as u64 * self.counter_frequency().0 as u64 / 1_000_000;
+        assert!(
+            (1..=0x1_0000).contains(&ticks),
+            "LPTIM: period out of range"
+        );
+        self.start_continuous((ticks - 1) as u16);
+    }
+
+    /// Stop and disable the LPTIM, which also resets the counter.
+    pub fn stop(&mut self) {
+        T::regs().cr().modify(|w| w.set_enable(false));
+    }
+
+    /// Change the autoreload value of a running counter.
+    pub fn set_autoreload(&mut self, arr: u16) {
+        self.write_arr(arr);
+    }
+
+    /// Set the compare value, the compare flag is set when the counter reaches it.
+    ///
+    /// It must be lower than the autoreload value. The LPTIM must be started.
+    pub fn set_compare(&mut self, cmp: u16) {
+        let r = T::regs();
+        r.cmp().write(|w| w.set_cmp(cmp));
+        while !r.isr().read().cmpok() {}
+        r.icr().write(|w| w.set_cmpokcf(true));
+        // A match of the previous compare value doesn't count for the new one.
+        T::state().cmp_match.store(false, Ordering::Relaxed);
+    }
+
+    /// Current counter value.
+    pub fn counter(&self) -> u16 {
+        // The counter runs from an asynchronous clock, read it until two reads match.
+        let r = T::regs();
+        loop {
+            let cnt = r.cnt().read().cnt();
+            if r.cnt().read().cnt() == cnt {
+                return cnt;
+            }
+        }
+    }
+
+    /// Wait until the counter reaches the autoreload value.
+    ///
+    /// Completes immediately if it was reached since the start or the last wait.
+    pub async fn wait_autoreload(&mut self) {
+        let s = T::state();
+        wait_flag(&s.arr_match, &s.arr_waker).await
+    }
+
+    /// Wait until the counter reaches the compare value.
+    ///
+    /// Completes immediately if it was reached since the last [`set_compare`](Self::set_compare)
+    /// or the last wait.
+    pub async fn wait_compare(&mut self) {
+        let s = T::state();
+        wait_flag(&s.cmp_match, &s.cmp_waker).await
+    }
+
+    /// Route the LPTIM interrupts to their EXTI line, so that they wake the chip up from Stop mode.
+    ///
+    /// Only useful when the LPTIM is clocked from the LSI or LSE.
+    pub fn enable_wakeup(&mut self, enabled: bool) {
+        critical_section::with(|_| {
+            pac::EXTI
+                .imr()
+                .modify(|w| w.set_line(WAKEUP_EXTI_LINE, enabled))
+        });
+    }
+}
+
+impl<'d, T: Instance> Drop for Lptim<'d, T> {
+    fn drop(&mut self) {
+        T::regs().cr().modify(|w| w.set_enable(false));
+        T::Interrupt::disable();
+        rcc::disable::<T>();
+    }
+}
+
+/// Wait for an event set by the interrupt handler, and consume it.
+async fn wait_flag(flag: &AtomicBool, waker: &AtomicWaker) {
+    poll_fn(|cx| {
+        waker.register(cx.waker());
+
+        // No swap on Cortex-M0+, the critical section keeps the handler from setting the
+        // flag again between the load and the store.
+        let fired = critical_section::with(|_| {
+            let fired = flag.load(Ordering::Relaxed);
+            flag.store(false, Ordering::Relaxed);
+            fired
+        });
+
+        if fired {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+struct State {
+    arr_waker: AtomicWaker,
+    cmp_waker: AtomicWaker,
+    arr_match: AtomicBool,
+    cmp_match: AtomicBool,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            arr_waker: AtomicWaker::new(),
+            cmp_waker: AtomicWaker::new(),
+            arr_match: AtomicBool::new(false),
+            cmp_match: AtomicBool::new(false),
+        }
+    }
+}
+
+trait SealedInstance: RccPeripheral {
+    fn regs() -> Regs;
+    fn state() -> &'static State;
+}
+
+/// LPTIM instance trait.
+#[allow(private_bounds)]
+pub trait Instance: Peripheral<P = Self> + SealedInstance + 'static {
+    /// Interrupt for this instance.
+    type Interrupt: interrupt::typelevel::Interrupt;
+}
+
+pin_trait!(Channel1Pin, Instance);
+pin_trait!(Channel2Pin, Instance);
+pin_trait!(OutputPin, Instance);
+
+foreach_interrupt!(
+    ($inst:ident, lptim, $block:ident, GLOBAL, $irq:ident) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            fn regs() -> Regs {
+                crate::pac::$inst
+            }
+
+            fn state() -> &'static State {
+                static STATE: State = State::new();
+                &STATE
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {
+            type Interrupt = crate::interrupt::typelevel::$irq;
+        }
+    };
+);
@@ -0,0 +1,109 @@
+//! PWM output of the LPTIM.
+
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+
+use super::{Config, Instance, OutputPin};
+use crate::gpio::{AfType, AnyPin, OutputType, SealedPin as _, Speed};
+use crate::pac::lptim::vals::Presc;
+use crate::rcc;
+use crate::time::Hertz;
+pub use crate::timer::low_level::OutputPolarity;
+
+/// PWM driver for the LPTIM output pin.
+///
+/// The output is active while the counter is above the compare value, and goes back to
+/// inactive when the counter reaches the autoreload value.
+pub struct Pwm<'d, T: Instance> {
+    _peri: PeripheralRef<'d, T>,
+    _pin: PeripheralRef<'d, AnyPin>,
+    arr: u16,
+}
+
+impl<'d, T: Instance> Pwm<'d, T> {
+    /// Create a new PWM driver with the given frequency.
+    ///
+    /// Panics if the frequency can not be reached with the configured prescaler.
+    pub fn new(
+        peri: impl Peripheral<P = T> + 'd,
+        pin: impl Peripheral<P = impl OutputPin<T>> + 'd,
+        freq: Hertz,
+        polarity: OutputPolarity,
+        config: Config,
+    ) -> Self {
+        into_ref!(peri, pin);
+
+        critical_section::with(|_| {
+            pin.set_low();
+            pin.set_as_af(
+                pin.af_num(),
+                AfType::output(OutputType::PushPull, Speed::VeryHigh),
+            );
+        });
+
+        rcc::enable_and_reset::<T>();
+
+        let counter_freq = T::frequency() / config.prescaler.divisor();
+        let ticks = counter_freq.0 / freq.0;
+        assert!(
+            (2..=0x1_0000).contains(&ticks),
+            "LPTIM: PWM frequency out of range"
+        );
+        let arr = (ticks - 1) as u16;
+
+        let r = T::regs();
+        // CFGR can only be written while the LPTIM is disabled.
+        r.cfgr().write(|w| {
+            w.set_presc(Presc::from_bits(config.prescaler as u8));
+            w.set_wave(false);
+            w.set_wavpol(polarity.into());
+            w.set_preload(true);
+        });
+
+        r.cr().modify(|w| w.set_enable(true));
+        r.arr().write(|w| w.set_arr(arr));
+        while !r.isr().read().arrok() {}
+        r.icr().write(|w| w.set_arrokcf(true));
+
+        let mut this = Self {
+            _peri: peri,
+            _pin: pin.map_into(),
+            arr,
+        };
+        this.set_duty_cycle(0);
+        r.cr().modify(|w| w.set_cntstrt(true));
+        this
+    }
+
+    /// Get max duty value.
+    pub fn max_duty_cycle(&self) -> u16 {
+        self.arr
+    }
+
+    /// Set the duty cycle.
+    ///
+    /// The value ranges from 0 for 0% duty, to [`max_duty_cycle`](Self::max_duty_cycle) for
+    /// almost 100% duty (one counter tick per period stays inactive).
+    /// With the preload enabled, the new value is applied at the end of the current period.
+    pub fn set_duty_cycle(&mut self, duty: u16) {
+        assert!(duty <= self.arr);
+
+        let r = T::regs();
+        r.cmp().write(|w| w.set_cmp(self.arr - duty));
+        while !r.isr().read().cmpok() {}
+        r.icr().write(|w| w.set_cmpokcf(true));
+    }
+
+    /// Set the duty cycle to `percent / 100`.
+    pub fn set_duty_cycle_percent(&mut self, percent: u8) {
+        assert!(percent <= 100);
+        let duty = self.arr as u32 * percent as u32 / 100;
+        self.set_duty_cycle(duty as u16);
+    }
+}
+
+impl<'d, T: Instance> Drop for Pwm<'d, T> {
+    fn drop(&mut self) {
+        T::regs().cr().modify(|w| w.set_enable(false));
+        rcc::disable::<T>();
+    }
+}
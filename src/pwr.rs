@@ -0,0 +1,311 @@
+//! Power control (PWR): Sleep and Stop modes, programmable voltage detector (PVD)
+//!
+//! The PVD driver is only available on chips with a PVD interrupt.
+
+#[cfg(pvd)]
+use core::future::poll_fn;
+#[cfg(pvd)]
+use core::task::Poll;
+
+#[cfg(pvd)]
+use embassy_hal_internal::{into_ref, PeripheralRef};
+#[cfg(pvd)]
+use embassy_sync::waitqueue::AtomicWaker;
+
+#[cfg(pvd)]
+use crate::interrupt::typelevel::Interrupt;
+pub use crate::pac::pwr::vals::{FlsSlptime as FlashWakeupDelay, MrrdyTime as RegulatorReadyDelay};
+#[cfg(pvd)]
+pub use crate::pac::pwr::vals::{FltTime as PvdFilterTime, Pvdt as PvdLevel};
+#[cfg(pvd)]
+use crate::pac::EXTI;
+use crate::pac::{PWR, RCC};
+#[cfg(pvd)]
+use crate::peripherals::PWR as PWR_PERI;
+use crate::rcc;
+#[cfg(pvd)]
+use crate::{interrupt, Peripheral};
+
+// TODO: move to py32-metapac
+/// EXTI line of the PVD output.
+#[cfg(pvd)]
+const PVD_EXTI_LINE: usize = 16;
+
+#[cfg(pvd)]
+static PVD_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// How the core waits in Sleep and Stop modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeupOn {
+    /// Wait for an interrupt (WFI). The interrupt handler runs before returning.
+    Interrupt,
+    /// Wait for an event (WFE): an EXTI line in event mode, or a pending interrupt
+    /// with SEVONPEND set.
+    Event,
+}
+
+/// Voltage regulator mode in Stop mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Regulator {
+    /// Main regulator: faster wakeup.
+    Main,
+    /// Low-power regulator: lower consumption, slower wakeup.
+    LowPower,
+}
+
+/// Stop mode configuration.
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct StopConfig {
+    /// Voltage regulator mode.
+    pub regulator: Regulator,
+    /// How the core waits.
+    pub wakeup_on: WakeupOn,
+}
+
+impl Default for StopConfig {
+    fn default() -> Self {
+        Self {
+            regulator: Regulator::LowPower,
+            wakeup_on: WakeupOn::Interrupt,
+        }
+    }
+}
+
+fn wait(wakeup_on: WakeupOn) {
+    match wakeup_on {
+        WakeupOn::Interrupt => cortex_m::asm::wfi(),
+        WakeupOn::Event => {
+            // Clear the event register first, so that an old event doesn't end the wait.
+            cortex_m::asm::sev();
+            cortex_m::asm::wfe();
+            cortex_m::asm::wfe();
+        }
+    }
+}
+
+fn scb() -> cortex_m::peripheral::SCB {
+    // Only SLEEPDEEP is touched, with a read-modify-write that interrupts don't race with.
+    unsafe { cortex_m::Peripherals::steal() }.SCB
+}
+
+/// Enter Sleep mode until an interrupt or event.
+///
+/// The CPU clock is stopped, the peripherals keep running.
+pub fn enter_sleep(wakeup_on: WakeupOn) {
+    scb().clear_sleepdeep();
+    wait(wakeup_on);
+}
+
+/// Enter Stop mode until an EXTI interrupt or event.
+///
+/// The HSI, HSE and PLL are stopped, only the LSI, LSE and the peripherals clocked by them
+/// (RTC, IWDG, LPTIM) keep running. SRAM and registers are retained.
+///
+/// The chip wakes up on the HSI, the clock configuration passed to [`init`](crate::init)
+/// is restored before returning. Peripherals enabled in RCC keep their configuration, but a
+/// transfer in progress is lost.
+pub fn enter_stop(config: StopConfig) {
+    RCC.apbenr1().modify(|w| w.set_pwren(true));
+    PWR.cr1()
+        .modify(|w| w.set_lpr(config.regulator == Regulator::LowPower));
+
+    let mut scb = scb();
+    scb.set_sleepdeep();
+    wait(config.wakeup_on);
+    scb.clear_sleepdeep();
+
+    critical_section::with(|_| unsafe { rcc::reinit() });
+}
+
+/// Set the delays applied when waking up from Stop mode.
+///
+/// Longer delays are needed with the low-power regulator at low temperatures, shorter ones
+/// give a faster wakeup.
+pub fn set_stop_wakeup_timing(flash: FlashWakeupDelay, regulator: RegulatorReadyDelay) {
+    RCC.apbenr1().modify(|w| w.set_pwren(true));
+    PWR.cr1().modify(|w| {
+        w.set_fls_slptime(flash);
+        w.set_mrrdy_time(regulator);
+    });
+}
+
+/// PVD interrupt handler.
+#[cfg(pvd)]
+pub struct InterruptHandler {
+    _private: (),
+}
+
+#[cfg(pvd)]
+impl interrupt::typelevel::Handler<interrupt::typelevel::PVD> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        // The line is left masked for the future to see that it fired, like `ExtiInput`.
+        EXTI.imr().modify(|w| w.set_line(PVD_EXTI_LINE, false));
+        EXTI.pr().write(|w| w.set_line(PVD_EXTI_LINE, true));
+        PVD_WAKER.wake();
+
+        #[cfg(feature = "low-power")]
+        crate::low_power::on_wakeup_irq();
+    }
+}
+
+/// Side of the threshold VDD crossed to.
+#[cfg(pvd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Crossing {
+    /// VDD dropped below the threshold.
+    Below,
+    /// VDD rose above the threshold.
+    Above,
+}
+
+/// PVD configuration.
+#[cfg(pvd)]
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct PvdConfig {
+    /// Threshold level.
+    pub level: PvdLevel,
+    /// Digital filter on the PVD output, `None` disables it.
+    pub filter: Option<PvdFilterTime>,
+}
+
+#[cfg(pvd)]
+impl Default for PvdConfig {
+    fn default() -> Self {
+        Self {
+            level: PvdLevel::from_bits(0),
+            filter: None,
+        }
+    }
+}
+
+/// Programmable voltage detector.
+///
+/// Compares VDD to a threshold, and wakes the chip up from Stop mode when it is crossed.
+#[cfg(pvd)]
+pub struct Pvd<'d> {
+    _peri: PeripheralRef<'d, PWR_PERI>,
+}
+
+#[cfg(pvd)]
+impl<'d> Pvd<'d> {
+    /// Enable the PVD.
+    pub fn new(
+        peri: impl Peripheral<P = PWR_PERI> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::PVD, InterruptHandler> + 'd,
+        config: PvdConfig,
+    ) -> Self {
+        into_ref!(peri);
+
+        // Not `enable_and_reset`: resetting the PWR would lock the backup domain again.
+        RCC.apbenr1().modify(|w| w.set_pwren(true));
+        PWR.cr2().modify(|w| {
+            w.set_srcsel(false);
+            w.set_pvdt(config.level);
+            w.set_flten(config.filter.is_some());
+            if let Some(filter) = config.filter {
+                w.set_flt_time(filter);
+            }
+            w.set_pvde(true);
+        });
+
+        critical_section::with(|_| {
+            EXTI.rtsr().modify(|w| w.set_line(PVD_EXTI_LINE, true));
+            EXTI.ftsr().modify(|w| w.set_line(PVD_EXTI_LINE, true));
+        });
+
+        interrupt::typelevel::PVD::unpend();
+        unsafe { interrupt::typelevel::PVD::enable() };
+
+        Self { _peri: peri }
+    }
+
+    /// Returns `true` if VDD is below the threshold.
+    pub fn is_below(&self) -> bool {
+        PWR.sr().read().pvdo()
+    }
+
+    /// Change the threshold level.
+    pub fn set_level(&mut self, level: PvdLevel) {
+        PWR.cr2().modify(|w| w.set_pvdt(level));
+    }
+
+    /// Wait until VDD crosses the threshold, in either direction.
+    pub async fn wait_for_threshold_crossing(&mut self) -> Crossing {
+        if self.wait(None).await {
+            Crossing::Below
+        } else {
+            Crossing::Above
+        }
+    }
+
+    /// Wait until VDD is below the threshold, returns immediately if it already is.
+    pub async fn wait_for_below(&mut self) {
+        self.wait(Some(true)).await;
+    }
+
+    /// Wait until VDD is above the threshold, returns immediately if it already is.
+    pub async fn wait_for_above(&mut self) {
+        self.wait(Some(false)).await;
+    }
+
+    /// Wait for a crossing, or until `is_below` equals `target`. Returns `is_below`.
+    async fn wait(&mut self, target: Option<bool>) -> bool {
+        let arm = || {
+            critical_section::with(|_| {
+                EXTI.pr().write(|w| w.set_line(PVD_EXTI_LINE, true));
+                EXTI.imr().modify(|w| w.set_line(PVD_EXTI_LINE, true));
+            })
+        };
+        // The line is armed before the level is checked, so that no crossing is missed.
+        arm();
+
+        // Mask the line again if the future is dropped before it completes.
+        let _guard = MaskOnDrop;
+
+        poll_fn(|cx| {
+            PVD_WAKER.register(cx.waker());
+
+            let fired = !EXTI.imr().read().line(PVD_EXTI_LINE);
+            let below = self.is_below();
+            match target {
+                None if fired => Poll::Ready(below),
+                Some(target) if below == target => Poll::Ready(below),
+                Some(_) if fired => {
+                    // Crossed back before the task ran, wait for the next crossing.
+                    arm();
+                    Poll::Pending
+                }
+                _ => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(pvd)]
+impl<'d> Drop for Pvd<'d> {
+    fn drop(&mut self) {
+        interrupt::typelevel::PVD::disable();
+        critical_section::with(|_| {
+            EXTI.imr().modify(|w| w.set_line(PVD_EXTI_LINE, false));
+            EXTI.rtsr().modify(|w| w.set_line(PVD_EXTI_LINE, false));
+            EXTI.ftsr().modify(|w| w.set_line(PVD_EXTI_LINE, false));
+        });
+        PWR.cr2().modify(|w| w.set_pvde(false));
+    }
+}
+
+#[cfg(pvd)]
+struct MaskOnDrop;
+
+#[cfg(pvd)]
+impl Drop for MaskOnDrop {
+    fn drop(&mut self) {
+        critical_section::with(|_| EXTI.imr().modify(|w| w.set_line(PVD_EXTI_LINE, false)));
+    }
+}
@@ -0,0 +1,262 @@
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+#[cfg(not(rcc_f002b))]
+pub use crate::pac::rcc::vals::Rtcsel as RtcClockSource;
+use crate::pac::RCC;
+use crate::time::Hertz;
+
+/// LSI speed
+pub const LSI_FREQ: Hertz = Hertz(32_768);
+
+/// LSE oscillator drive strength
+#[cfg(not(rcc_f002b))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LseDrive {
+    /// Lowest drive, lowest consumption.
+    Low = 1,
+    /// Medium drive, the default.
+    Medium = 2,
+    /// Highest drive, for crystals that are hard to start.
+    High = 3,
+}
+
+#[cfg(not(rcc_f002b))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LseMode {
+    /// 32.768 kHz crystal oscillator (LSEBYP=0)
+    Oscillator(LseDrive),
+    /// external clock (LSEBYP=1)
+    Bypass,
+}
+
+#[cfg(not(rcc_f002b))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LseConfig {
+    /// LSE frequency.
+    pub frequency: Hertz,
+    /// LSE mode.
+    pub mode: LseMode,
+    /// Give up on the LSE if it is not ready after this many milliseconds.
+    ///
+    /// The LSE is then left off, and `Clocks.lse` (and the RTC clock, if it uses the LSE)
+    /// is `None`. With `None`, `init` waits forever for the oscillator.
+    pub startup_timeout_ms: Option<u32>,
+    /// Enable the LSE clock security system, see [`lse_failure_detected`].
+    pub css: bool,
+}
+
+#[cfg(not(rcc_f002b))]
+impl Default for LseConfig {
+    fn default() -> Self {
+        Self {
+            frequency: Hertz(32_768),
+            mode: LseMode::Oscillator(LseDrive::Medium),
+            startup_timeout_ms: Some(2000),
+            css: false,
+        }
+    }
+}
+
+/// Low speed clocks configuration
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct LsConfig {
+    /// RTC clock source.
+    #[cfg(not(rcc_f002b))]
+    pub rtc: RtcClockSource,
+    /// Enable the LSI oscillator.
+    ///
+    /// The LSI is also started by the IWDG hardware, regardless of this setting.
+    pub lsi: bool,
+    /// LSE configuration, `None` keeps the LSE off.
+    #[cfg(not(rcc_f002b))]
+    pub lse: Option<LseConfig>,
+}
+
+impl LsConfig {
+    /// Use the LSE crystal as the RTC clock.
+    #[cfg(not(rcc_f002b))]
+    pub fn default_lse() -> Self {
+        Self {
+            rtc: RtcClockSource::LSE,
+            lse: Some(LseConfig::default()),
+            lsi: false,
+        }
+    }
+
+    /// Use the LSI as the RTC clock.
+    pub const fn default_lsi() -> Self {
+        Self {
+            #[cfg(not(rcc_f002b))]
+            rtc: RtcClockSource::LSI,
+            lsi: true,
+            #[cfg(not(rcc_f002b))]
+            lse: None,
+        }
+    }
+
+    /// Leave both low speed oscillators off.
+    pub const fn off() -> Self {
+        Self {
+            #[cfg(not(rcc_f002b))]
+            rtc: RtcClockSource::DISABLE,
+            lsi: false,
+            #[cfg(not(rcc_f002b))]
+            lse: None,
+        }
+    }
+}
+
+impl Default for LsConfig {
+    fn default() -> Self {
+        Self::off()
+    }
+}
+
+/// Frequencies of the low speed clocks, returned by [`LsConfig::init`].
+pub(crate) struct LsClocks {
+    pub lsi: Option<Hertz>,
+    pub lse: Option<Hertz>,
+    pub rtc: Option<Hertz>,
+}
+
+/// Allow writes to the backup domain (BDCR and the RTC registers).
+#[cfg(not(rcc_f002b))]
+pub(crate) fn unlock() {
+    RCC.apbenr1().modify(|w| w.set_pwren(true));
+    crate::pac::PWR.cr1().modify(|w| w.set_dbp(true));
+    while !crate::pac::PWR.cr1().read().dbp() {}
+}
+
+/// Returns `true` if the LSE clock security system detected a failure of the LSE.
+///
+/// The LSE stays off until the backup domain is reset, and the RTC stops counting if it
+/// was clocked by the LSE.
+#[cfg(not(rcc_f002b))]
+pub fn lse_failure_detected() -> bool {
+    RCC.bdcr().read().lsecssd()
+}
+
+impl LsConfig {
+    /// `sys` is only used to time the LSE startup timeout.
+    pub(crate) fn init(&self, #[allow(unused)] sys: Hertz) -> LsClocks {
+        let lsi = if self.lsi {
+            RCC.csr().modify(|w| w.set_lsion(true));
+            while !RCC.csr().read().lsirdy() {}
+            Some(LSI_FREQ)
+        } else {
+            None
+        };
+
+        #[cfg(rcc_f002b)]
+        {
+            LsClocks {
+                lsi,
+                lse: None,
+                rtc: None,
+            }
+        }
+
+        #[cfg(not(rcc_f002b))]
+        {
+            match self.rtc {
+                RtcClockSource::DISABLE => {}
+                RtcClockSource::LSI => assert!(self.lsi),
+                RtcClockSource::LSE => assert!(self.lse.is_some()),
+                _ => panic!("rcc: unsupported RTC clock source"),
+            }
+
+            // Nothing in the backup domain is used, leave it as it is.
+            if self.rtc == RtcClockSource::DISABLE && self.lse.is_none() {
+                return LsClocks {
+                    lsi,
+                    lse: None,
+                    rtc: None,
+                };
+            }
+
+            unlock();
+
+            let bdcr = RCC.bdcr().read();
+            let lse_en = self.lse.is_some();
+            let lse_byp = self.lse.map(|c| c.mode == LseMode::Bypass).unwrap_or(false);
+
+            // The RTC clock source can only be changed after a backup domain reset, which also
+            // clears the counter and the LSE settings. Only do it when the configuration
+            // actually changes, so that the calendar survives a system reset.
+            // A failed LSE can also only be restarted after a reset.
+            let reset = bdcr.rtcen() != (self.rtc != RtcClockSource::DISABLE)
+                || bdcr.rtcsel() != self.rtc
+                || bdcr.lseon() != lse_en
+                || (lse_en && bdcr.lsebyp() != lse_byp)
+                || bdcr.lsecssd();
+
+            if reset {
+                debug!("rcc: resetting backup domain");
+                RCC.bdcr().modify(|w| w.set_bdrst(true));
+                RCC.bdcr().modify(|w| w.set_bdrst(false));
+            }
+
+            let lse = match self.lse {
+                Some(lse) if reset => start_lse(lse, sys),
+                Some(lse) => Some(lse.frequency),
+                None => None,
+            };
+
+            if let Some(lse_config) = self.lse {
+                RCC.bdcr()
+                    .modify(|w| w.set_lsecsson(lse_config.css && lse.is_some()));
+            }
+
+            let rtc = match self.rtc {
+                RtcClockSource::LSI => lsi,
+                RtcClockSource::LSE => lse,
+                _ => None,
+            };
+
+            if reset && rtc.is_some() {
+                RCC.bdcr().modify(|w| {
+                    w.set_rtcsel(self.rtc);
+                    w.set_rtcen(true);
+                });
+            }
+
+            LsClocks { lsi, lse, rtc }
+        }
+    }
+}
+
+/// Start the LSE after a backup domain reset, returns `None` if it timed out.
+#[cfg(not(rcc_f002b))]
+fn start_lse(config: LseConfig, sys: Hertz) -> Option<Hertz> {
+    if let LseMode::Oscillator(drive) = config.mode {
+        RCC.ecscr().modify(|w| w.set_lse_driver(drive as u8));
+    }
+    RCC.bdcr().modify(|w| {
+        w.set_lsebyp(config.mode == LseMode::Bypass);
+        w.set_lseon(true);
+    });
+
+    match config.startup_timeout_ms {
+        None => while !RCC.bdcr().read().lserdy() {},
+        Some(timeout_ms) => {
+            let mut remaining_ms = timeout_ms;
+            while !RCC.bdcr().read().lserdy() {
+                if remaining_ms == 0 {
+                    warn!("rcc: LSE did not start within {} ms", timeout_ms);
+                    RCC.bdcr().modify(|w| w.set_lseon(false));
+                    return None;
+                }
+                remaining_ms -= 1;
+                cortex_m::asm::delay(sys.0 / 1000);
+            }
+        }
+    }
+
+    Some(config.frequency)
+}
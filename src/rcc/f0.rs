@@ -77,7 +77,8 @@ pub struct Config {
     pub apb1_pre: APBPrescaler,
     /// Per-peripheral kernel clock selection muxes
     pub mux: super::mux::ClockMux,
-    // pub ls: super::LsConfig,
+    /// Low speed clocks (LSI, LSE) and RTC clock source
+    pub ls: super::LsConfig,
 }
 
 impl Default for Config {
@@ -90,7 +91,7 @@ impl Default for Config {
             pll: None,
             ahb_pre: AHBPrescaler::DIV1,
             apb1_pre: APBPrescaler::DIV1,
-            // ls: Default::default(),
+            ls: Default::default(),
             mux: Default::default(),
         }
     }
@@ -245,7 +246,7 @@ pub(crate) unsafe fn init(config: Config) {
         RCC.cr().modify(|w| w.set_hsion(false));
     }
 
-    // let rtc = config.ls.init();
+    let ls = config.ls.init(sys);
 
     /*
     TODO: Maybe add something like this to clock_mux? How can we autogenerate the data for this?
@@ -287,7 +288,9 @@ pub(crate) unsafe fn init(config: Config) {
         pclk1_tim: Some(pclk1_tim).into(),
         sys: Some(sys).into(),
         hsi: hsi_value.into(),
-        lse: None.into(),
+        lsi: ls.lsi.into(),
+        lse: ls.lse.into(),
+        rtc: ls.rtc.into(),
         pll: pll.into(),
     };
     crate::rcc::set_freqs(clocks);
@@ -31,6 +31,14 @@ macro_rules! impl_peri {
             type Source = $source;
 
             unsafe fn _apply_clock_settings(source: Self::Source, _prescaler: McoPrescaler) {
+                // The low speed oscillators are only running if enabled in `rcc::LsConfig`.
+                match source {
+                    McoSource::LSI => assert!(super::get_freqs().lsi.to_hertz().is_some()),
+                    #[cfg(not(rcc_f002b))]
+                    McoSource::LSE => assert!(super::get_freqs().lse.to_hertz().is_some()),
+                    _ => {}
+                }
+
                 RCC.cfgr().modify(|w| {
                     w.$set_source(source);
                     w.$set_prescaler(_prescaler);
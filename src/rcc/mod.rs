@@ -6,8 +6,10 @@
 
 use core::mem::MaybeUninit;
 
+mod bd;
 #[cfg(mco)]
 mod mco;
+pub use bd::*;
 use critical_section::CriticalSection;
 #[cfg(mco)]
 pub use mco::*;
@@ -33,25 +35,38 @@ pub struct Clocks {
     pub sys: crate::time::MaybeHertz,
 
     pub hsi: crate::time::MaybeHertz,
+    pub lsi: crate::time::MaybeHertz,
     pub lse: crate::time::MaybeHertz,
     #[cfg(not(rcc_f002b))]
     pub pll: crate::time::MaybeHertz,
-    // pub rtc: crate::time::MaybeHertz,
+    pub rtc: crate::time::MaybeHertz,
     // pub sys: Option<crate::time::Hertz>,
     // pub usb: Option<crate::time::Hertz>,
 }
 
-// #[cfg(feature = "low-power")]
-// /// Must be written within a critical section
-// ///
-// /// May be read without a critical section
-// pub(crate) static mut REFCOUNT_STOP1: u32 = 0;
+#[cfg(feature = "low-power")]
+/// Number of enabled peripherals (and other users) that can not run in Stop mode.
+///
+/// Must be written within a critical section
+///
+/// May be read without a critical section
+pub(crate) static mut REFCOUNT_STOP1: u32 = 0;
 
-// #[cfg(feature = "low-power")]
-// /// Must be written within a critical section
-// ///
-// /// May be read without a critical section
-// pub(crate) static mut REFCOUNT_STOP2: u32 = 0;
+/// Clock configuration to restore when waking up from Stop mode.
+pub(crate) static mut RCC_CONFIG: Option<Config> = None;
+
+/// Restore the clock configuration after Stop mode, which switches the system clock back to HSI.
+///
+/// Does nothing if the system clock is still the configured one, so it may be called after any wakeup.
+///
+/// Safety: must be called with interrupts disabled, like `init`.
+pub(crate) unsafe fn reinit() {
+    if let Some(config) = RCC_CONFIG {
+        if RCC.cfgr().read().sws() != config.sys {
+            init(config);
+        }
+    }
+}
 
 /// Frozen clock frequencies
 ///
@@ -94,18 +109,19 @@ pub(crate) struct RccInfo {
     /// maintain a refcount in `crate::_generated::REFCOUNTS` at this index. If the bit is not
     /// shared, this is 0xff (we don't use an `Option` to save one byte of storage).
     refcount_idx_or_0xff: u8,
-    // /// Stop mode of the peripheral, used to maintain `REFCOUNT_STOP1` and `REFCOUNT_STOP2`.
-    // #[cfg(feature = "low-power")]
-    // stop_mode: StopMode,
+    /// Stop mode of the peripheral, used to maintain `REFCOUNT_STOP1`.
+    #[cfg(feature = "low-power")]
+    stop_mode: StopMode,
 }
 
-// #[cfg(feature = "low-power")]
-// #[allow(dead_code)]
-// pub(crate) enum StopMode {
-//     Standby,
-//     Stop2,
-//     Stop1,
-// }
+#[cfg(feature = "low-power")]
+#[allow(dead_code)]
+pub(crate) enum StopMode {
+    /// Keeps working in Stop mode.
+    Standby,
+    /// Clocked by HSI/HSE/PLL, which are off in Stop mode.
+    Stop1,
+}
 
 impl RccInfo {
     /// Safety:
@@ -117,7 +133,7 @@ impl RccInfo {
         reset_offset_and_bit: Option<(u8, u8)>,
         enable_offset_and_bit: (u8, u8),
         refcount_idx: Option<u8>,
-        // #[cfg(feature = "low-power")] stop_mode: StopMode,
+        #[cfg(feature = "low-power")] stop_mode: StopMode,
     ) -> Self {
         let (reset_offset_or_0xff, reset_bit) = match reset_offset_and_bit {
             Some((offset, bit)) => (offset, bit),
@@ -134,8 +150,8 @@ impl RccInfo {
             enable_offset,
             enable_bit,
             refcount_idx_or_0xff,
-            // #[cfg(feature = "low-power")]
-            // stop_mode,
+            #[cfg(feature = "low-power")]
+            stop_mode,
         }
     }
 
@@ -159,16 +175,13 @@ impl RccInfo {
             }
         }
 
-        // #[cfg(feature = "low-power")]
-        // match self.stop_mode {
-        //     StopMode::Standby => {}
-        //     StopMode::Stop2 => unsafe {
-        //         REFCOUNT_STOP2 += 1;
-        //     },
-        //     StopMode::Stop1 => unsafe {
-        //         REFCOUNT_STOP1 += 1;
-        //     },
-        // }
+        #[cfg(feature = "low-power")]
+        match self.stop_mode {
+            StopMode::Standby => {}
+            StopMode::Stop1 => unsafe {
+                REFCOUNT_STOP1 += 1;
+            },
+        }
 
         // set the xxxRST bit
         let reset_ptr = self.reset_ptr();
@@ -225,16 +238,13 @@ impl RccInfo {
             }
         }
 
-        // #[cfg(feature = "low-power")]
-        // match self.stop_mode {
-        //     StopMode::Standby => {}
-        //     StopMode::Stop2 => unsafe {
-        //         REFCOUNT_STOP2 -= 1;
-        //     },
-        //     StopMode::Stop1 => unsafe {
-        //         REFCOUNT_STOP1 -= 1;
-        //     },
-        // }
+        #[cfg(feature = "low-power")]
+        match self.stop_mode {
+            StopMode::Standby => {}
+            StopMode::Stop1 => unsafe {
+                REFCOUNT_STOP1 -= 1;
+            },
+        }
 
         // clear the xxxEN bit
         let enable_ptr = self.enable_ptr();
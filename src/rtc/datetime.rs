@@ -0,0 +1,297 @@
+/// Errors regarding the [`DateTime`] struct.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The [DateTime] contains an invalid year value. Must be between `1970..=2105`.
+    InvalidYear,
+    /// The [DateTime] contains an invalid month value. Must be between `1..=12`.
+    InvalidMonth,
+    /// The [DateTime] contains an invalid day value. Must be between `1..=31`.
+    InvalidDay,
+    /// The [DateTime] contains an invalid hour value. Must be between `0..=23`.
+    InvalidHour,
+    /// The [DateTime] contains an invalid minute value. Must be between `0..=59`.
+    InvalidMinute,
+    /// The [DateTime] contains an invalid second value. Must be between `0..=59`.
+    InvalidSecond,
+}
+
+/// A reference to a day of the week
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum DayOfWeek {
+    Monday = 0,
+    Tuesday = 1,
+    Wednesday = 2,
+    Thursday = 3,
+    Friday = 4,
+    Saturday = 5,
+    Sunday = 6,
+}
+
+/// Structure containing date and time information
+///
+/// The PY32 RTC only has a 32-bit seconds counter, the calendar is computed in software.
+/// The counter holds the number of seconds since 1970-01-01 00:00:00, so the
+/// supported range is 1970 to 2105.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    /// 1970..=2105
+    year: u16,
+    /// 1..=12, 1 is January
+    month: u8,
+    /// 1..=28,29,30,31 depending on month/year
+    day: u8,
+    /// 0..=23
+    hour: u8,
+    /// 0..=59
+    minute: u8,
+    /// 0..=59
+    second: u8,
+}
+
+impl DateTime {
+    /// Get the year (1970..=2105)
+    pub const fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// Get the month (1..=12, 1 is January)
+    pub const fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// Get the day (1..=31)
+    pub const fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Get the day of week, computed from the date
+    pub const fn day_of_week(&self) -> DayOfWeek {
+        // 1970-01-01 was a Thursday.
+        match (days_from_civil(self.year as i32, self.month as u32, self.day as u32) + 3) % 7 {
+            0 => DayOfWeek::Monday,
+            1 => DayOfWeek::Tuesday,
+            2 => DayOfWeek::Wednesday,
+            3 => DayOfWeek::Thursday,
+            4 => DayOfWeek::Friday,
+            5 => DayOfWeek::Saturday,
+            _ => DayOfWeek::Sunday,
+        }
+    }
+
+    /// Get the hour (0..=23)
+    pub const fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// Get the minute (0..=59)
+    pub const fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// Get the second (0..=59)
+    pub const fn second(&self) -> u8 {
+        self.second
+    }
+
+    /// Create a new DateTime with the given information.
+    pub fn from(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<Self, Error> {
+        if !(1970..=2105).contains(&year) {
+            Err(Error::InvalidYear)
+        } else if !(1..=12).contains(&month) {
+            Err(Error::InvalidMonth)
+        } else if day < 1 || day > days_in_month(year, month) {
+            Err(Error::InvalidDay)
+        } else if hour > 23 {
+            Err(Error::InvalidHour)
+        } else if minute > 59 {
+            Err(Error::InvalidMinute)
+        } else if second > 59 {
+            Err(Error::InvalidSecond)
+        } else {
+            Ok(Self {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+            })
+        }
+    }
+
+    /// Create a DateTime from a number of seconds since 1970-01-01 00:00:00.
+    ///
+    /// Timestamps from 2106-01-01 on give dates in 2106, which [`from`](Self::from) rejects.
+    pub const fn from_timestamp(secs: u32) -> Self {
+        let days = secs / 86400;
+        let rem = secs % 86400;
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+
+    /// Number of seconds since 1970-01-01 00:00:00.
+    pub const fn timestamp(&self) -> u32 {
+        let days = days_from_civil(self.year as i32, self.month as u32, self.day as u32);
+        days * 86400 + self.hour as u32 * 3600 + self.minute as u32 * 60 + self.second as u32
+    }
+}
+
+const fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Algorithms from http://howardhinnant.github.io/date_algorithms.html,
+// restricted to dates after 1970-01-01.
+
+/// Number of days since 1970-01-01.
+const fn days_from_civil(year: i32, month: u32, day: u32) -> u32 {
+    let y = if month <= 2 { year - 1 } else { year } as u32;
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// (year, month, day) from a number of days since 1970-01-01.
+const fn civil_from_days(days: u32) -> (u16, u8, u8) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as u16, month as u8, day as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DateTime, DayOfWeek, Error};
+
+    fn date(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime::from(year, month, day, hour, minute, second).unwrap()
+    }
+
+    fn assert_round_trip(dt: DateTime, secs: u32) {
+        assert_eq!(dt.timestamp(), secs);
+        assert_eq!(DateTime::from_timestamp(secs), dt);
+    }
+
+    #[test]
+    fn test_epoch() {
+        let dt = date(1970, 1, 1, 0, 0, 0);
+        assert_round_trip(dt, 0);
+        assert_eq!(dt.day_of_week(), DayOfWeek::Thursday);
+    }
+
+    #[test]
+    fn test_leap_day_2000() {
+        // 2000 is a leap year, being divisible by 400.
+        let dt = date(2000, 2, 29, 0, 0, 0);
+        assert_round_trip(dt, 951_782_400);
+        assert_eq!(dt.day_of_week(), DayOfWeek::Tuesday);
+        assert_eq!(
+            DateTime::from_timestamp(951_782_400 - 1),
+            date(2000, 2, 28, 23, 59, 59)
+        );
+        assert_eq!(
+            DateTime::from_timestamp(951_782_400 + 86_400),
+            date(2000, 3, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_no_leap_day_2100() {
+        // 2100 is not a leap year, being divisible by 100 but not by 400.
+        assert_eq!(DateTime::from(2100, 2, 29, 0, 0, 0), Err(Error::InvalidDay));
+
+        let feb28 = date(2100, 2, 28, 0, 0, 0);
+        let mar1 = date(2100, 3, 1, 0, 0, 0);
+        assert_round_trip(feb28, 4_107_456_000);
+        assert_round_trip(mar1, 4_107_542_400);
+        assert_eq!(feb28.day_of_week(), DayOfWeek::Sunday);
+        assert_eq!(mar1.day_of_week(), DayOfWeek::Monday);
+        assert_eq!(
+            DateTime::from_timestamp(4_107_542_400 - 1),
+            date(2100, 2, 28, 23, 59, 59)
+        );
+    }
+
+    #[test]
+    fn test_upper_bound() {
+        let dt = date(2105, 12, 31, 23, 59, 59);
+        assert_round_trip(dt, 4_291_747_199);
+        assert_eq!(dt.day_of_week(), DayOfWeek::Thursday);
+
+        // The last second of the counter is in 2106, past the range accepted by `from`.
+        let last = DateTime::from_timestamp(u32::MAX);
+        assert_eq!((last.year(), last.month(), last.day()), (2106, 2, 7));
+        assert_eq!((last.hour(), last.minute(), last.second()), (6, 28, 15));
+        assert_eq!(last.timestamp(), u32::MAX);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(
+            DateTime::from(1969, 12, 31, 23, 59, 59),
+            Err(Error::InvalidYear)
+        );
+        assert_eq!(DateTime::from(2106, 1, 1, 0, 0, 0), Err(Error::InvalidYear));
+        assert_eq!(
+            DateTime::from(2024, 0, 1, 0, 0, 0),
+            Err(Error::InvalidMonth)
+        );
+        assert_eq!(
+            DateTime::from(2024, 13, 1, 0, 0, 0),
+            Err(Error::InvalidMonth)
+        );
+        assert_eq!(DateTime::from(2024, 1, 0, 0, 0, 0), Err(Error::InvalidDay));
+        assert_eq!(DateTime::from(2024, 4, 31, 0, 0, 0), Err(Error::InvalidDay));
+        assert_eq!(DateTime::from(2023, 2, 29, 0, 0, 0), Err(Error::InvalidDay));
+        assert_eq!(
+            DateTime::from(2024, 1, 1, 24, 0, 0),
+            Err(Error::InvalidHour)
+        );
+        assert_eq!(
+            DateTime::from(2024, 1, 1, 0, 60, 0),
+            Err(Error::InvalidMinute)
+        );
+        assert_eq!(
+            DateTime::from(2024, 1, 1, 0, 0, 60),
+            Err(Error::InvalidSecond)
+        );
+    }
+}
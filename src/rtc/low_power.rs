@@ -0,0 +1,124 @@
+use embassy_time::{Duration, TICK_HZ};
+
+use super::{Rtc, ALARM_WAKER, RTC_REGS};
+use crate::pac::EXTI;
+
+// TODO: move to py32-metapac
+/// EXTI line of the RTC alarm.
+pub(crate) const RTC_EXTI_LINE: usize = 19;
+
+/// Longest Stop period, the executor wakes up at least this often.
+const MAX_STOP_SECS: u32 = 0xFFFF;
+
+/// A point in time read from the RTC counter and prescaler divider.
+#[derive(Clone, Copy)]
+pub(crate) struct RtcInstant {
+    /// Counter value, in seconds.
+    secs: u32,
+    /// RTC clock cycles elapsed in the current second.
+    subsecs: u32,
+}
+
+impl RtcInstant {
+    fn now(prl: u32) -> Self {
+        // The divider counts down from PRL, the counter is incremented when it reloads.
+        loop {
+            let high = RTC_REGS.cnth().read().cnth();
+            let low = RTC_REGS.cntl().read().cntl();
+            let div =
+                (RTC_REGS.divh().read().divh() as u32) << 16 | RTC_REGS.divl().read().divl() as u32;
+            if RTC_REGS.cnth().read().cnth() == high && RTC_REGS.cntl().read().cntl() == low {
+                return Self {
+                    secs: (high as u32) << 16 | low as u32,
+                    subsecs: prl - div.min(prl),
+                };
+            }
+        }
+    }
+
+    /// Number of RTC clock cycles since the epoch of the counter.
+    fn cycles(&self, rtc_hz: u32) -> u64 {
+        self.secs as u64 * rtc_hz as u64 + self.subsecs as u64
+    }
+}
+
+impl<'d> Rtc<'d> {
+    /// Route the RTC alarm to its EXTI line, so that it wakes the core up from Stop mode.
+    pub(crate) fn enable_wakeup_line(&self) {
+        critical_section::with(|_| {
+            EXTI.rtsr().modify(|w| w.set_line(RTC_EXTI_LINE, true));
+            EXTI.imr().modify(|w| w.set_line(RTC_EXTI_LINE, true));
+        });
+    }
+
+    /// Program the alarm to fire no later than `requested_duration` from now.
+    ///
+    /// The alarm has a resolution of one second, so it may fire up to a second early. If a
+    /// [`Rtc::wait_alarm`] is pending and due earlier, its alarm is kept instead.
+    /// Returns `Err` if the duration is too short for the alarm to be set.
+    pub(crate) fn start_wakeup_alarm(
+        &self,
+        requested_duration: Duration,
+        cs: critical_section::CriticalSection,
+    ) -> Result<(), ()> {
+        let rtc_hz = self.rtc_hz;
+        let now = RtcInstant::now(rtc_hz - 1);
+
+        let requested_cycles = requested_duration
+            .as_ticks()
+            .min(MAX_STOP_SECS as u64 * TICK_HZ)
+            * rtc_hz as u64
+            / TICK_HZ;
+        let mut alarm = ((now.cycles(rtc_hz) + requested_cycles) / rtc_hz as u64) as u32;
+        if let Some(user_alarm) = self.user_alarm.borrow(cs).get() {
+            alarm = alarm.min(user_alarm);
+        }
+        if alarm <= now.secs {
+            return Err(());
+        }
+
+        self.write_alarm(alarm);
+        RTC_REGS.crl().modify(|w| w.set_alrf(false));
+
+        // The counter may have reached the alarm while it was being written.
+        if self.timestamp() >= alarm {
+            return Err(());
+        }
+
+        RTC_REGS.crh().modify(|w| w.set_alrie(true));
+        self.stop_time.borrow(cs).set(Some(now));
+
+        trace!(
+            "rtc: start wakeup alarm for {} ms (alarm at {})",
+            requested_duration.as_millis(),
+            alarm
+        );
+
+        Ok(())
+    }
+
+    /// Disable the wakeup alarm and return the time elapsed since it was started.
+    ///
+    /// The alarm of a pending [`Rtc::wait_alarm`] is restored, and its task woken up to
+    /// re-arm the interrupt or to complete if the alarm is already due.
+    pub(crate) fn stop_wakeup_alarm(
+        &self,
+        cs: critical_section::CriticalSection,
+    ) -> Option<Duration> {
+        RTC_REGS.crh().modify(|w| w.set_alrie(false));
+        RTC_REGS.crl().modify(|w| w.set_alrf(false));
+        EXTI.pr().write(|w| w.set_line(RTC_EXTI_LINE, true));
+
+        if let Some(user_alarm) = self.user_alarm.borrow(cs).get() {
+            self.write_alarm(user_alarm);
+            ALARM_WAKER.wake();
+        }
+
+        let start = self.stop_time.borrow(cs).take()?;
+        let rtc_hz = self.rtc_hz;
+        let now = RtcInstant::now(rtc_hz - 1);
+
+        let cycles = now.cycles(rtc_hz).saturating_sub(start.cycles(rtc_hz));
+        Some(Duration::from_ticks(cycles * TICK_HZ / rtc_hz as u64))
+    }
+}
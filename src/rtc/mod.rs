@@ -0,0 +1,261 @@
+//! Real Time Clock (RTC)
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+mod datetime;
+#[cfg(feature = "low-power")]
+mod low_power;
+
+#[cfg(feature = "low-power")]
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+#[cfg(feature = "low-power")]
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+#[cfg(feature = "low-power")]
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+
+pub use self::datetime::{DateTime, DayOfWeek, Error as DateTimeError};
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::RTC as RTC_REGS;
+use crate::peripherals::RTC;
+use crate::time::Hertz;
+use crate::{interrupt, rcc, Peripheral};
+
+static ALARM_WAKER: AtomicWaker = AtomicWaker::new();
+static SECOND_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// RTC interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::RTC> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let crl = RTC_REGS.crl().read();
+        let crh = RTC_REGS.crh().read();
+
+        // The flags are left set for the futures to consume, only the interrupt is masked.
+        if crl.alrf() && crh.alrie() {
+            RTC_REGS.crh().modify(|w| w.set_alrie(false));
+            ALARM_WAKER.wake();
+
+            // The alarm woke the core up from Stop mode.
+            #[cfg(feature = "low-power")]
+            {
+                crate::pac::EXTI
+                    .pr()
+                    .write(|w| w.set_line(low_power::RTC_EXTI_LINE, true));
+                crate::low_power::on_wakeup_irq();
+            }
+        }
+        if crl.secf() && crh.secie() {
+            RTC_REGS.crh().modify(|w| w.set_secie(false));
+            SECOND_WAKER.wake();
+        }
+        if crl.owf() {
+            RTC_REGS.crl().modify(|w| w.set_owf(false));
+        }
+    }
+}
+
+/// RTC driver.
+///
+/// The RTC is a 32-bit seconds counter in the backup domain, clocked by the LSE or the LSI
+/// as selected by [`rcc::LsConfig`](crate::rcc::LsConfig). It keeps running across system
+/// resets, so [`Rtc::new`] does not touch the counter.
+///
+/// When the RTC clock source is changed, the backup domain is reset during `py32_hal::init`
+/// and the counter restarts from 1970-01-01 00:00:00.
+pub struct Rtc<'d> {
+    _peri: PeripheralRef<'d, RTC>,
+    #[cfg(feature = "low-power")]
+    rtc_hz: u32,
+    #[cfg(feature = "low-power")]
+    stop_time: Mutex<CriticalSectionRawMutex, Cell<Option<low_power::RtcInstant>>>,
+    /// Alarm of a pending [`Rtc::wait_alarm`], restored after the wakeup alarm.
+    #[cfg(feature = "low-power")]
+    user_alarm: Mutex<CriticalSectionRawMutex, Cell<Option<u32>>>,
+}
+
+impl<'d> Rtc<'d> {
+    /// Create a new RTC driver.
+    ///
+    /// Panics if no RTC clock source was selected in the RCC config.
+    pub fn new(
+        peri: impl Peripheral<P = RTC> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::RTC, InterruptHandler> + 'd,
+    ) -> Self {
+        into_ref!(peri);
+
+        let rtc_clk = unwrap!(
+            unsafe { rcc::get_freqs() }.rtc.to_hertz(),
+            "RTC clock source is not configured"
+        );
+
+        rcc::enable_and_reset::<RTC>();
+        rcc::unlock();
+
+        // Wait for the APB interface to be synchronized with the RTC domain.
+        RTC_REGS.crl().modify(|w| w.set_rsf(false));
+        while !RTC_REGS.crl().read().rsf() {}
+
+        let this = Self {
+            _peri: peri,
+            #[cfg(feature = "low-power")]
+            rtc_hz: rtc_clk.0,
+            #[cfg(feature = "low-power")]
+            stop_time: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None)),
+            #[cfg(feature = "low-power")]
+            user_alarm: Mutex::const_new(CriticalSectionRawMutex::new(), Cell::new(None)),
+        };
+        this.set_prescaler(rtc_clk);
+
+        #[cfg(feature = "low-power")]
+        this.enable_wakeup_line();
+
+        interrupt::typelevel::RTC::unpend();
+        unsafe { interrupt::typelevel::RTC::enable() };
+
+        this
+    }
+
+    /// Divide the RTC clock down to a 1 Hz counter clock.
+    fn set_prescaler(&self, rtc_clk: Hertz) {
+        let prl = rtc_clk.0 - 1;
+        assert!(prl <= 0xF_FFFF);
+
+        self.write(|r| {
+            r.prlh().write(|w| w.set_prlh((prl >> 16) as u8));
+            r.prll().write(|w| w.set_prll(prl as u16));
+        });
+    }
+
+    /// Run `f` with the RTC in configuration mode, required to write PRL, CNT and ALR.
+    fn write<R>(&self, f: impl FnOnce(crate::pac::rtc::Rtc) -> R) -> R {
+        while !RTC_REGS.crl().read().rtoff() {}
+        RTC_REGS.crl().modify(|w| w.set_cnf(true));
+
+        let result = f(RTC_REGS);
+
+        RTC_REGS.crl().modify(|w| w.set_cnf(false));
+        while !RTC_REGS.crl().read().rtoff() {}
+
+        result
+    }
+
+    /// Set the alarm to a number of seconds since 1970-01-01 00:00:00.
+    fn write_alarm(&self, secs: u32) {
+        self.write(|r| {
+            r.alrh().write(|w| w.set_alrh((secs >> 16) as u16));
+            r.alrl().write(|w| w.set_alrl(secs as u16));
+        });
+    }
+
+    /// Number of seconds since 1970-01-01 00:00:00.
+    pub fn timestamp(&self) -> u32 {
+        // CNTH may change between the two reads, retry if it did.
+        loop {
+            let high = RTC_REGS.cnth().read().cnth();
+            let low = RTC_REGS.cntl().read().cntl();
+            if RTC_REGS.cnth().read().cnth() == high {
+                return (high as u32) << 16 | low as u32;
+            }
+        }
+    }
+
+    /// Set the counter to a number of seconds since 1970-01-01 00:00:00.
+    pub fn set_timestamp(&mut self, secs: u32) {
+        self.write(|r| {
+            r.cnth().write(|w| w.set_cnth((secs >> 16) as u16));
+            r.cntl().write(|w| w.set_cntl(secs as u16));
+        });
+    }
+
+    /// Set the date and time.
+    pub fn set_datetime(&mut self, t: DateTime) {
+        self.set_timestamp(t.timestamp());
+    }
+
+    /// Return the current date and time.
+    pub fn now(&self) -> DateTime {
+        DateTime::from_timestamp(self.timestamp())
+    }
+
+    /// Wait until the given date and time.
+    ///
+    /// Returns immediately if `at` is already in the past. Only one alarm can be pending.
+    ///
+    /// With the `low-power` feature, the alarm is shared with the executor: the earlier of the
+    /// two is programmed before entering Stop mode, and this alarm is restored after wakeup.
+    pub async fn wait_alarm(&mut self, at: DateTime) {
+        let secs = at.timestamp();
+        if self.timestamp() >= secs {
+            return;
+        }
+
+        let _on_drop = OnDrop::new(|| {
+            RTC_REGS.crh().modify(|w| w.set_alrie(false));
+            #[cfg(feature = "low-power")]
+            critical_section::with(|cs| self.user_alarm.borrow(cs).set(None));
+        });
+
+        critical_section::with(|_cs| {
+            self.write_alarm(secs);
+            RTC_REGS.crl().modify(|w| w.set_alrf(false));
+            #[cfg(feature = "low-power")]
+            self.user_alarm.borrow(_cs).set(Some(secs));
+        });
+
+        poll_fn(|cx| {
+            ALARM_WAKER.register(cx.waker());
+
+            // The counter may have gone past the alarm while it was being written.
+            if RTC_REGS.crl().read().alrf() || self.timestamp() >= secs {
+                RTC_REGS.crh().modify(|w| w.set_alrie(false));
+                RTC_REGS.crl().modify(|w| w.set_alrf(false));
+                Poll::Ready(())
+            } else {
+                RTC_REGS.crh().modify(|w| w.set_alrie(true));
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Wait for the next second tick of the counter.
+    pub async fn wait_second(&mut self) {
+        RTC_REGS.crl().modify(|w| w.set_secf(false));
+
+        poll_fn(|cx| {
+            SECOND_WAKER.register(cx.waker());
+
+            if RTC_REGS.crl().read().secf() {
+                RTC_REGS.crh().modify(|w| w.set_secie(false));
+                RTC_REGS.crl().modify(|w| w.set_secf(false));
+                Poll::Ready(())
+            } else {
+                RTC_REGS.crh().modify(|w| w.set_secie(true));
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<'d> Drop for Rtc<'d> {
+    fn drop(&mut self) {
+        // The counter keeps running, only the interrupts are turned off.
+        RTC_REGS.crh().modify(|w| {
+            w.set_alrie(false);
+            w.set_secie(false);
+        });
+        interrupt::typelevel::RTC::disable();
+    }
+}
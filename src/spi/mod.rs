@@ -0,0 +1,884 @@
+//! Serial Peripheral Interface (SPI)
+#![macro_use]
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use core::marker::PhantomData;
+use core::ptr;
+
+use embassy_embedded_hal::SetConfig;
+#[cfg(dma)]
+use embassy_futures::join::join;
+use embassy_hal_internal::PeripheralRef;
+pub use embedded_hal_1::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+
+#[cfg(dma)]
+use crate::dma::{word, ChannelAndRequest};
+use crate::gpio::{AfType, AnyPin, OutputType, Pull, SealedPin as _, Speed};
+#[cfg(dma)]
+use crate::mode::Async;
+use crate::mode::{Blocking, Mode as PeriMode};
+use crate::pac::spi::{regs, vals, Spi as Regs};
+use crate::rcc::{RccInfo, SealedRccPeripheral};
+use crate::time::Hertz;
+use crate::Peripheral;
+
+#[cfg(dma)]
+mod slave;
+#[cfg(dma)]
+pub use slave::{SlaveConfig, SpiSlave};
+
+/// SPI error.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Mode fault
+    ModeFault,
+    /// Overrun.
+    Overrun,
+}
+
+/// SPI bit order
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitOrder {
+    /// Least significant bit first.
+    LsbFirst,
+    /// Most significant bit first.
+    MsbFirst,
+}
+
+/// SPI configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// SPI mode.
+    pub mode: Mode,
+    /// Bit order.
+    pub bit_order: BitOrder,
+    /// Clock frequency.
+    pub frequency: Hertz,
+    /// Enable internal pullup on MISO.
+    ///
+    /// There are some ICs that require a pull-up on the MISO pin for some applications.
+    /// If you  are unsure, you probably don't need this.
+    pub miso_pull: Pull,
+    /// signal rise/fall speed (slew rate) - defaults to `VeryHigh`.
+    /// Lower it, e.g. to `Low`, to reduce ringing at low SPI speeds.
+    pub rise_fall_speed: Speed,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+            frequency: Hertz(1_000_000),
+            miso_pull: Pull::None,
+            rise_fall_speed: Speed::VeryHigh,
+        }
+    }
+}
+
+// Shared with the slave config.
+fn raw_phase(mode: Mode) -> vals::Cpha {
+    match mode.phase {
+        Phase::CaptureOnSecondTransition => vals::Cpha::SECONDEDGE,
+        Phase::CaptureOnFirstTransition => vals::Cpha::FIRSTEDGE,
+    }
+}
+
+fn raw_polarity(mode: Mode) -> vals::Cpol {
+    match mode.polarity {
+        Polarity::IdleHigh => vals::Cpol::IDLEHIGH,
+        Polarity::IdleLow => vals::Cpol::IDLELOW,
+    }
+}
+
+fn raw_byte_order(bit_order: BitOrder) -> vals::Lsbfirst {
+    match bit_order {
+        BitOrder::LsbFirst => vals::Lsbfirst::LSBFIRST,
+        BitOrder::MsbFirst => vals::Lsbfirst::MSBFIRST,
+    }
+}
+
+impl Config {
+    fn sck_af(&self) -> AfType {
+        AfType::output(OutputType::PushPull, self.rise_fall_speed)
+    }
+
+    fn mosi_af(&self) -> AfType {
+        AfType::output(OutputType::PushPull, self.rise_fall_speed)
+    }
+}
+
+/// SPI driver.
+pub struct Spi<'d, M: PeriMode> {
+    pub(crate) info: &'static Info,
+    kernel_clock: Hertz,
+    sck: Option<PeripheralRef<'d, AnyPin>>,
+    mosi: Option<PeripheralRef<'d, AnyPin>>,
+    miso: Option<PeripheralRef<'d, AnyPin>>,
+    #[cfg(dma)]
+    tx_dma: Option<ChannelAndRequest<'d>>,
+    #[cfg(dma)]
+    rx_dma: Option<ChannelAndRequest<'d>>,
+    _phantom: PhantomData<M>,
+    current_word_size: word_impl::Config,
+}
+
+impl<'d, M: PeriMode> Spi<'d, M> {
+    fn new_inner<T: Instance>(
+        _peri: impl Peripheral<P = T> + 'd,
+        sck: Option<PeripheralRef<'d, AnyPin>>,
+        mosi: Option<PeripheralRef<'d, AnyPin>>,
+        miso: Option<PeripheralRef<'d, AnyPin>>,
+        #[cfg(dma)] tx_dma: Option<ChannelAndRequest<'d>>,
+        #[cfg(dma)] rx_dma: Option<ChannelAndRequest<'d>>,
+        config: Config,
+    ) -> Self {
+        let mut this = Self {
+            info: T::info(),
+            kernel_clock: T::frequency(),
+            sck,
+            mosi,
+            miso,
+            #[cfg(dma)]
+            tx_dma,
+            #[cfg(dma)]
+            rx_dma,
+            current_word_size: <u8 as SealedWord>::CONFIG,
+            _phantom: PhantomData,
+        };
+        this.enable_and_init(config);
+        this
+    }
+
+    fn enable_and_init(&mut self, config: Config) {
+        let br = compute_baud_rate(self.kernel_clock, config.frequency);
+        let cpha = raw_phase(config.mode);
+        let cpol = raw_polarity(config.mode);
+        let lsbfirst = raw_byte_order(config.bit_order);
+
+        self.info.rcc.enable_and_reset();
+
+        let regs = self.info.regs;
+        regs.cr2().modify(|w| {
+            w.set_ssoe(false);
+            w.set_frxth(<u8 as SealedWord>::CONFIG.1);
+        });
+        regs.cr1().modify(|w| {
+            w.set_cpha(cpha);
+            w.set_cpol(cpol);
+
+            w.set_mstr(vals::Mstr::MASTER);
+            w.set_br(br);
+            w.set_spe(true);
+            w.set_lsbfirst(lsbfirst);
+            w.set_ssi(true);
+            w.set_ssm(true);
+            w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+            // we're doing "fake rxonly", by actually writing one
+            // byte to DR for each byte we want to receive. if we
+            // set OUTPUTDISABLED here, this hangs.
+            w.set_rxonly(vals::Rxonly::FULLDUPLEX);
+            w.set_dff(<u8 as SealedWord>::CONFIG.0);
+        });
+    }
+
+    /// Reconfigures it with the supplied config.
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ()> {
+        let cpha = raw_phase(config.mode);
+        let cpol = raw_polarity(config.mode);
+
+        let lsbfirst = raw_byte_order(config.bit_order);
+
+        let br = compute_baud_rate(self.kernel_clock, config.frequency);
+
+        self.info.regs.cr1().modify(|w| {
+            w.set_cpha(cpha);
+            w.set_cpol(cpol);
+            w.set_br(br);
+            w.set_lsbfirst(lsbfirst);
+        });
+
+        Ok(())
+    }
+
+    /// Get current SPI configuration.
+    pub fn get_current_config(&self) -> Config {
+        let cfg = self.info.regs.cr1().read();
+        let polarity = if cfg.cpol() == vals::Cpol::IDLELOW {
+            Polarity::IdleLow
+        } else {
+            Polarity::IdleHigh
+        };
+        let phase = if cfg.cpha() == vals::Cpha::FIRSTEDGE {
+            Phase::CaptureOnFirstTransition
+        } else {
+            Phase::CaptureOnSecondTransition
+        };
+
+        let bit_order = if cfg.lsbfirst() == vals::Lsbfirst::LSBFIRST {
+            BitOrder::LsbFirst
+        } else {
+            BitOrder::MsbFirst
+        };
+
+        let miso_pull = Pull::None;
+        let rise_fall_speed = Speed::VeryHigh;
+
+        let br = cfg.br();
+        let frequency = self.kernel_clock / (2u32 << br.to_bits());
+
+        Config {
+            mode: Mode { polarity, phase },
+            bit_order,
+            frequency,
+            miso_pull,
+            rise_fall_speed,
+        }
+    }
+
+    pub(crate) fn set_word_size(&mut self, word_size: word_impl::Config) {
+        if self.current_word_size == word_size {
+            return;
+        }
+
+        self.info.regs.cr1().modify(|reg| {
+            reg.set_spe(false);
+            reg.set_dff(word_size.0)
+        });
+        self.info.regs.cr2().modify(|w| {
+            w.set_frxth(word_size.1);
+        });
+        self.info.regs.cr1().modify(|reg| {
+            reg.set_spe(true);
+        });
+
+        self.current_word_size = word_size;
+    }
+
+    /// Blocking write.
+    pub fn blocking_write<W: Word>(&mut self, words: &[W]) -> Result<(), Error> {
+        self.info.regs.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(self.info.regs);
+        self.set_word_size(W::CONFIG);
+        for word in words.iter() {
+            // if we're doing tx only, after writing the last byte to FIFO we have to wait
+            // until it's actually sent. The BSY flag is not reliable for this on
+            // this SPI block, it clears too soon. Workaround is to wait for RXNE:
+            // the last byte is actually sent when it's received back.
+            let _ = transfer_word(self.info.regs, *word)?;
+        }
+
+        Ok(())
+    }
+
+    /// Blocking read.
+    pub fn blocking_read<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.info.regs.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(self.info.regs);
+        self.set_word_size(W::CONFIG);
+        for word in words.iter_mut() {
+            *word = transfer_word(self.info.regs, W::default())?;
+        }
+        Ok(())
+    }
+
+    /// Blocking in-place bidirectional transfer.
+    ///
+    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
+    pub fn blocking_transfer_in_place<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.info.regs.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(self.info.regs);
+        self.set_word_size(W::CONFIG);
+        for word in words.iter_mut() {
+            *word = transfer_word(self.info.regs, *word)?;
+        }
+        Ok(())
+    }
+
+    /// Blocking bidirectional transfer.
+    ///
+    /// This transfers both buffers at the same time, so it is NOT equivalent to `write` followed by `read`.
+    ///
+    /// The transfer runs for `max(read.len(), write.len())` bytes. If `read` is shorter extra bytes are ignored.
+    /// If `write` is shorter it is padded with zero bytes.
+    pub fn blocking_transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        self.info.regs.cr1().modify(|w| w.set_spe(true));
+        flush_rx_fifo(self.info.regs);
+        self.set_word_size(W::CONFIG);
+        let len = read.len().max(write.len());
+        for i in 0..len {
+            let wb = write.get(i).copied().unwrap_or_default();
+            let rb = transfer_word(self.info.regs, wb)?;
+            if let Some(r) = read.get_mut(i) {
+                *r = rb;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'d> Spi<'d, Blocking> {
+    /// Create a new blocking SPI driver.
+    pub fn new_blocking<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(
+            peri,
+            new_pin!(sck, config.sck_af()),
+            new_pin!(mosi, config.mosi_af()),
+            new_pin!(miso, AfType::input(config.miso_pull)),
+            #[cfg(dma)]
+            None,
+            #[cfg(dma)]
+            None,
+            config,
+        )
+    }
+
+    /// Create a new blocking SPI driver, in RX-only mode (only MISO pin, no MOSI).
+    pub fn new_blocking_rxonly<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(
+            peri,
+            new_pin!(sck, config.sck_af()),
+            None,
+            new_pin!(miso, AfType::input(config.miso_pull)),
+            #[cfg(dma)]
+            None,
+            #[cfg(dma)]
+            None,
+            config,
+        )
+    }
+
+    /// Create a new blocking SPI driver, in TX-only mode (only MOSI pin, no MISO).
+    pub fn new_blocking_txonly<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(
+            peri,
+            new_pin!(sck, config.sck_af()),
+            new_pin!(mosi, config.mosi_af()),
+            None,
+            #[cfg(dma)]
+            None,
+            #[cfg(dma)]
+            None,
+            config,
+        )
+    }
+}
+
+#[cfg(dma)]
+impl<'d> Spi<'d, Async> {
+    /// Create a new SPI driver.
+    pub fn new<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        tx_dma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        rx_dma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(
+            peri,
+            new_pin!(sck, config.sck_af()),
+            new_pin!(mosi, config.mosi_af()),
+            new_pin!(miso, AfType::input(config.miso_pull)),
+            new_dma!(tx_dma),
+            new_dma!(rx_dma),
+            config,
+        )
+    }
+
+    /// Create a new SPI driver, in RX-only mode (only MISO pin, no MOSI).
+    ///
+    /// The TX DMA channel is still required: it clocks out dummy words so the
+    /// peripheral generates SCK while receiving.
+    pub fn new_rxonly<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        tx_dma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        rx_dma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(
+            peri,
+            new_pin!(sck, config.sck_af()),
+            None,
+            new_pin!(miso, AfType::input(config.miso_pull)),
+            new_dma!(tx_dma),
+            new_dma!(rx_dma),
+            config,
+        )
+    }
+
+    /// Create a new SPI driver, in TX-only mode (only MOSI pin, no MISO).
+    pub fn new_txonly<T: Instance>(
+        peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        tx_dma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        config: Config,
+    ) -> Self {
+        Self::new_inner(
+            peri,
+            new_pin!(sck, config.sck_af()),
+            new_pin!(mosi, config.mosi_af()),
+            None,
+            new_dma!(tx_dma),
+            None,
+            config,
+        )
+    }
+
+    /// SPI write, using DMA.
+    pub async fn write<W: Word>(&mut self, data: &[W]) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.info.regs.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        self.set_word_size(W::CONFIG);
+
+        let tx_dst = self.info.regs.tx_ptr();
+        let tx_f = unsafe {
+            self.tx_dma
+                .as_mut()
+                .unwrap()
+                .write(data, tx_dst, Default::default())
+        };
+
+        set_txdmaen(self.info.regs, true);
+        self.info.regs.cr1().modify(|w| {
+            w.set_spe(true);
+        });
+
+        tx_f.await;
+
+        finish_dma(self.info.regs);
+
+        Ok(())
+    }
+
+    /// SPI read, using DMA.
+    pub async fn read<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.info.regs.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        self.set_word_size(W::CONFIG);
+
+        flush_rx_fifo(self.info.regs);
+
+        set_rxdmaen(self.info.regs, true);
+
+        let clock_byte_count = data.len();
+
+        let rx_src = self.info.regs.rx_ptr();
+        let rx_f = unsafe {
+            self.rx_dma
+                .as_mut()
+                .unwrap()
+                .read(rx_src, data, Default::default())
+        };
+
+        let tx_dst = self.info.regs.tx_ptr();
+        let clock_byte = W::default();
+        let tx_f = unsafe {
+            self.tx_dma.as_mut().unwrap().write_repeated(
+                &clock_byte,
+                clock_byte_count,
+                tx_dst,
+                Default::default(),
+            )
+        };
+
+        set_txdmaen(self.info.regs, true);
+        self.info.regs.cr1().modify(|w| {
+            w.set_spe(true);
+        });
+
+        join(tx_f, rx_f).await;
+
+        finish_dma(self.info.regs);
+
+        Ok(())
+    }
+
+    async fn transfer_inner<W: Word>(
+        &mut self,
+        read: *mut [W],
+        write: *const [W],
+    ) -> Result<(), Error> {
+        assert_eq!(read.len(), write.len());
+        if read.len() == 0 {
+            return Ok(());
+        }
+
+        self.info.regs.cr1().modify(|w| {
+            w.set_spe(false);
+        });
+        self.set_word_size(W::CONFIG);
+
+        flush_rx_fifo(self.info.regs);
+
+        set_rxdmaen(self.info.regs, true);
+
+        let rx_src = self.info.regs.rx_ptr();
+        let rx_f = unsafe {
+            self.rx_dma
+                .as_mut()
+                .unwrap()
+                .read_raw(rx_src, read, Default::default())
+        };
+
+        let tx_dst = self.info.regs.tx_ptr();
+        let tx_f = unsafe {
+            self.tx_dma
+                .as_mut()
+                .unwrap()
+                .write_raw(write, tx_dst, Default::default())
+        };
+
+        set_txdmaen(self.info.regs, true);
+        self.info.regs.cr1().modify(|w| {
+            w.set_spe(true);
+        });
+
+        join(tx_f, rx_f).await;
+
+        finish_dma(self.info.regs);
+
+        Ok(())
+    }
+
+    /// Bidirectional transfer, using DMA.
+    ///
+    /// This transfers both buffers at the same time, so it is NOT equivalent to `write` followed by `read`.
+    ///
+    /// `read` and `write` must have the same length.
+    pub async fn transfer<W: Word>(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        self.transfer_inner(read, write).await
+    }
+
+    /// In-place bidirectional transfer, using DMA.
+    ///
+    /// This writes the contents of `data` on MOSI, and puts the received data on MISO in `data`, at the same time.
+    pub async fn transfer_in_place<W: Word>(&mut self, data: &mut [W]) -> Result<(), Error> {
+        self.transfer_inner(data, data).await
+    }
+}
+
+impl<'d, M: PeriMode> Drop for Spi<'d, M> {
+    fn drop(&mut self) {
+        self.sck.as_ref().map(|x| x.set_as_disconnected());
+        self.mosi.as_ref().map(|x| x.set_as_disconnected());
+        self.miso.as_ref().map(|x| x.set_as_disconnected());
+
+        self.info.rcc.disable();
+    }
+}
+
+pub(crate) fn compute_baud_rate(kernel_clock: Hertz, freq: Hertz) -> vals::Br {
+    let val = match kernel_clock.0 / freq.0 {
+        0 => panic!("You are trying to reach a frequency higher than the clock"),
+        1..=2 => 0b000,
+        3..=5 => 0b001,
+        6..=11 => 0b010,
+        12..=23 => 0b011,
+        24..=39 => 0b100,
+        40..=95 => 0b101,
+        96..=191 => 0b110,
+        _ => 0b111,
+    };
+
+    vals::Br::from_bits(val)
+}
+
+pub(crate) trait RegsExt {
+    fn tx_ptr<W>(&self) -> *mut W;
+    fn rx_ptr<W>(&self) -> *mut W;
+}
+
+impl RegsExt for Regs {
+    fn tx_ptr<W>(&self) -> *mut W {
+        self.dr().as_ptr() as *mut W
+    }
+
+    fn rx_ptr<W>(&self) -> *mut W {
+        self.dr().as_ptr() as *mut W
+    }
+}
+
+pub(crate) fn check_error_flags(sr: regs::Sr) -> Result<(), Error> {
+    if sr.ovr() {
+        return Err(Error::Overrun);
+    }
+    if sr.modf() {
+        return Err(Error::ModeFault);
+    }
+
+    Ok(())
+}
+
+fn spin_until_tx_ready(regs: Regs) -> Result<(), Error> {
+    loop {
+        let sr = regs.sr().read();
+
+        check_error_flags(sr)?;
+
+        if sr.txe() {
+            return Ok(());
+        }
+    }
+}
+
+fn spin_until_rx_ready(regs: Regs) -> Result<(), Error> {
+    loop {
+        let sr = regs.sr().read();
+
+        check_error_flags(sr)?;
+
+        if sr.rxne() {
+            return Ok(());
+        }
+    }
+}
+
+pub(crate) fn flush_rx_fifo(regs: Regs) {
+    while regs.sr().read().rxne() {
+        let _ = unsafe { ptr::read_volatile(regs.rx_ptr::<u8>()) };
+    }
+}
+
+#[allow(unused)]
+pub(crate) fn set_txdmaen(regs: Regs, val: bool) {
+    regs.cr2().modify(|reg| {
+        reg.set_txdmaen(val);
+    });
+}
+
+#[allow(unused)]
+pub(crate) fn set_rxdmaen(regs: Regs, val: bool) {
+    regs.cr2().modify(|reg| {
+        reg.set_rxdmaen(val);
+    });
+}
+
+#[allow(unused)]
+fn finish_dma(regs: Regs) {
+    while regs.sr().read().ftlvl().to_bits() > 0 {}
+
+    while regs.sr().read().bsy() {}
+
+    // Disable the spi peripheral
+    regs.cr1().modify(|w| {
+        w.set_spe(false);
+    });
+
+    // The peripheral automatically disables the DMA stream on completion without error,
+    // but it does not clear the RXDMAEN/TXDMAEN flag in CR2.
+    regs.cr2().modify(|reg| {
+        reg.set_txdmaen(false);
+        reg.set_rxdmaen(false);
+    });
+}
+
+fn transfer_word<W: Word>(regs: Regs, tx_word: W) -> Result<W, Error> {
+    spin_until_tx_ready(regs)?;
+
+    unsafe {
+        ptr::write_volatile(regs.tx_ptr(), tx_word);
+    }
+
+    spin_until_rx_ready(regs)?;
+
+    let rx_word = unsafe { ptr::read_volatile(regs.rx_ptr()) };
+    Ok(rx_word)
+}
+
+// Note: It is not possible to impl these traits generically in embedded-hal 0.2 due to a conflict with
+// some marker traits. For details, see https://github.com/rust-embedded/embedded-hal/pull/289
+macro_rules! impl_blocking {
+    ($w:ident) => {
+        impl<'d, M: PeriMode> embedded_hal_02::blocking::spi::Write<$w> for Spi<'d, M> {
+            type Error = Error;
+
+            fn write(&mut self, words: &[$w]) -> Result<(), Self::Error> {
+                self.blocking_write(words)
+            }
+        }
+
+        impl<'d, M: PeriMode> embedded_hal_02::blocking::spi::Transfer<$w> for Spi<'d, M> {
+            type Error = Error;
+
+            fn transfer<'w>(&mut self, words: &'w mut [$w]) -> Result<&'w [$w], Self::Error> {
+                self.blocking_transfer_in_place(words)?;
+                Ok(words)
+            }
+        }
+    };
+}
+
+impl_blocking!(u8);
+impl_blocking!(u16);
+
+impl<'d, M: PeriMode> embedded_hal_1::spi::ErrorType for Spi<'d, M> {
+    type Error = Error;
+}
+
+impl<'d, W: Word, M: PeriMode> embedded_hal_1::spi::SpiBus<W> for Spi<'d, M> {
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.blocking_read(words)
+    }
+
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.blocking_write(words)
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.blocking_transfer(read, write)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.blocking_transfer_in_place(words)
+    }
+}
+
+impl embedded_hal_1::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_1::spi::ErrorKind {
+        match *self {
+            Self::ModeFault => embedded_hal_1::spi::ErrorKind::ModeFault,
+            Self::Overrun => embedded_hal_1::spi::ErrorKind::Overrun,
+        }
+    }
+}
+
+#[cfg(dma)]
+impl<'d, W: Word> embedded_hal_async::spi::SpiBus<W> for Spi<'d, Async> {
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.write(words).await
+    }
+
+    async fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.read(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.transfer(read, write).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words).await
+    }
+}
+
+pub(crate) trait SealedWord {
+    const CONFIG: word_impl::Config;
+}
+
+/// Word sizes usable for SPI.
+#[cfg(dma)]
+#[allow(private_bounds)]
+pub trait Word: word::Word + SealedWord + Default {}
+
+/// Word sizes usable for SPI.
+#[cfg(not(dma))]
+#[allow(private_bounds)]
+pub trait Word: SealedWord + Default + Copy + 'static {}
+
+macro_rules! impl_word {
+    ($T:ty, $config:expr) => {
+        impl SealedWord for $T {
+            const CONFIG: Config = $config;
+        }
+        impl Word for $T {}
+    };
+}
+
+pub(crate) mod word_impl {
+    use super::*;
+
+    pub type Config = (vals::Dff, vals::Frxth);
+
+    impl_word!(u8, (vals::Dff::BITS8, vals::Frxth::QUARTER));
+    impl_word!(u16, (vals::Dff::BITS16, vals::Frxth::HALF));
+}
+
+pub(crate) struct Info {
+    pub(crate) regs: Regs,
+    pub(crate) rcc: RccInfo,
+}
+
+struct State {}
+
+impl State {
+    #[allow(unused)]
+    const fn new() -> Self {
+        Self {}
+    }
+}
+
+peri_trait!();
+
+pin_trait!(SckPin, Instance);
+pin_trait!(MosiPin, Instance);
+pin_trait!(MisoPin, Instance);
+pin_trait!(CsPin, Instance);
+pin_trait!(MckPin, Instance);
+pin_trait!(CkPin, Instance);
+pin_trait!(WsPin, Instance);
+#[cfg(dma)]
+dma_trait!(RxDma, Instance);
+#[cfg(dma)]
+dma_trait!(TxDma, Instance);
+
+foreach_peripheral!(
+    (spi, $inst:ident) => {
+        peri_trait_impl!($inst, Info {
+            regs: crate::pac::$inst,
+            rcc: crate::peripherals::$inst::RCC_INFO,
+        });
+    };
+);
+
+impl<'d, M: PeriMode> SetConfig for Spi<'d, M> {
+    type Config = Config;
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
+        self.set_config(config)
+    }
+}
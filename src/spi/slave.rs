@@ -0,0 +1,286 @@
+//! SPI slave with DMA ring buffers
+
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::Poll;
+
+use embassy_embedded_hal::SetConfig;
+use embassy_hal_internal::{into_ref, PeripheralRef};
+
+use super::{
+    check_error_flags, raw_byte_order, raw_phase, raw_polarity, BitOrder, CsPin, Error, Info,
+    Instance, MisoPin, Mode, MosiPin, RegsExt, RxDma, SckPin, SealedWord, TxDma, Word, MODE_0,
+};
+use crate::dma::{ReadableRingBuffer, WritableRingBuffer};
+use crate::gpio::{AfType, AnyPin, OutputType, Pull, SealedPin as _, Speed};
+use crate::pac::spi::vals;
+use crate::Peripheral;
+
+/// SPI slave configuration.
+#[non_exhaustive]
+#[derive(Copy, Clone)]
+pub struct SlaveConfig {
+    /// SPI mode, must match the mode used by the host.
+    pub mode: Mode,
+    /// Bit order.
+    pub bit_order: BitOrder,
+    /// MISO rise/fall speed (slew rate) - defaults to `VeryHigh`.
+    pub rise_fall_speed: Speed,
+}
+
+impl Default for SlaveConfig {
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+            rise_fall_speed: Speed::VeryHigh,
+        }
+    }
+}
+
+/// SPI slave driver.
+///
+/// The chip select is handled in hardware on the NSS pin: the peripheral only
+/// shifts data while the host drives NSS low.
+///
+/// Received words are continuously written by DMA into a circular buffer, in the same
+/// way as [`RingBufferedUartRx`](crate::usart::RingBufferedUartRx). Transmitted words are
+/// taken from a second circular buffer, which can be filled with [`SpiSlave::preload`]
+/// before [`SpiSlave::start`] so that the first host transaction is answered without any
+/// CPU involvement.
+///
+/// The frame size is selected by the word type `W`: `u8` for 8-bit frames, `u16` for 16-bit frames.
+pub struct SpiSlave<'d, W: Word> {
+    info: &'static Info,
+    sck: Option<PeripheralRef<'d, AnyPin>>,
+    mosi: Option<PeripheralRef<'d, AnyPin>>,
+    miso: Option<PeripheralRef<'d, AnyPin>>,
+    nss: Option<PeripheralRef<'d, AnyPin>>,
+    rx_ring: ReadableRingBuffer<'d, W>,
+    tx_ring: WritableRingBuffer<'d, W>,
+}
+
+impl<'d, W: Word> SpiSlave<'d, W> {
+    /// Create a new SPI slave driver.
+    ///
+    /// `tx_buf` and `rx_buf` are registered to the DMA controller and used as circular buffers.
+    /// `rx_buf` must be large enough to hold the data received between two calls to [`read`](Self::read).
+    pub fn new<T: Instance>(
+        _peri: impl Peripheral<P = T> + 'd,
+        sck: impl Peripheral<P = impl SckPin<T>> + 'd,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'd,
+        miso: impl Peripheral<P = impl MisoPin<T>> + 'd,
+        nss: impl Peripheral<P = impl CsPin<T>> + 'd,
+        tx_dma: impl Peripheral<P = impl TxDma<T>> + 'd,
+        rx_dma: impl Peripheral<P = impl RxDma<T>> + 'd,
+        tx_buf: &'d mut [W],
+        rx_buf: &'d mut [W],
+        config: SlaveConfig,
+    ) -> Self {
+        assert!(!tx_buf.is_empty() && tx_buf.len() <= 0xFFFF);
+        assert!(!rx_buf.is_empty() && rx_buf.len() <= 0xFFFF);
+        into_ref!(tx_dma, rx_dma);
+
+        let info = T::info();
+        let regs = info.regs;
+
+        let tx_request = tx_dma.request();
+        let rx_request = rx_dma.request();
+        let opts = Default::default();
+        let rx_ring =
+            unsafe { ReadableRingBuffer::new(rx_dma, rx_request, regs.rx_ptr(), rx_buf, opts) };
+        let tx_ring =
+            unsafe { WritableRingBuffer::new(tx_dma, tx_request, regs.tx_ptr(), tx_buf, opts) };
+
+        let mut this = Self {
+            info,
+            sck: new_pin!(sck, AfType::input(Pull::None)),
+            mosi: new_pin!(mosi, AfType::input(Pull::None)),
+            miso: new_pin!(
+                miso,
+                AfType::output(OutputType::PushPull, config.rise_fall_speed)
+            ),
+            nss: new_pin!(nss, AfType::input(Pull::Up)),
+            rx_ring,
+            tx_ring,
+        };
+        this.enable_and_init(config);
+        this
+    }
+
+    fn enable_and_init(&mut self, config: SlaveConfig) {
+        self.info.rcc.enable_and_reset();
+
+        let regs = self.info.regs;
+        regs.cr2().modify(|w| {
+            w.set_ssoe(false);
+            w.set_frxth(W::CONFIG.1);
+        });
+        regs.cr1().modify(|w| {
+            w.set_mstr(vals::Mstr::SLAVE);
+            // Hardware NSS management.
+            w.set_ssm(false);
+            w.set_bidimode(vals::Bidimode::UNIDIRECTIONAL);
+            w.set_rxonly(vals::Rxonly::FULLDUPLEX);
+            w.set_dff(W::CONFIG.0);
+        });
+        self.set_config(&config);
+    }
+
+    /// Reconfigures it with the supplied config.
+    ///
+    /// The peripheral must be stopped, this panics otherwise.
+    pub fn set_config(&mut self, config: &SlaveConfig) {
+        assert!(!self.is_started());
+
+        self.info.regs.cr1().modify(|w| {
+            w.set_cpha(raw_phase(config.mode));
+            w.set_cpol(raw_polarity(config.mode));
+            w.set_lsbfirst(raw_byte_order(config.bit_order));
+        });
+    }
+
+    fn is_started(&self) -> bool {
+        self.info.regs.cr1().read().spe()
+    }
+
+    /// Write words into the TX buffer before the peripheral is started.
+    ///
+    /// Returns the number of words written. This panics if called after [`start`](Self::start).
+    pub fn preload(&mut self, data: &[W]) -> Result<usize, Error> {
+        assert!(!self.is_started());
+
+        match self.tx_ring.write_immediate(data) {
+            Ok((len, _)) => Ok(len),
+            Err(_) => Err(Error::Overrun),
+        }
+    }
+
+    /// Start the DMA transfers and enable the peripheral.
+    ///
+    /// Note: This is also done automatically by [`read()`](Self::read) if required.
+    pub fn start(&mut self) {
+        if self.is_started() {
+            return;
+        }
+
+        compiler_fence(Ordering::SeqCst);
+        let regs = self.info.regs;
+
+        // Sequence from the reference manual: RXDMAEN, DMA channels, TXDMAEN, then SPE.
+        regs.cr2().modify(|w| w.set_rxdmaen(true));
+        // Drop words left over from a previous run, e.g. one stopped by an overrun.
+        self.rx_ring.clear();
+        self.rx_ring.start();
+        self.tx_ring.start();
+        regs.cr2().modify(|w| w.set_txdmaen(true));
+        regs.cr1().modify(|w| w.set_spe(true));
+    }
+
+    /// Stop the DMA transfers and disable the peripheral.
+    ///
+    /// Any word still in the TX FIFO is discarded.
+    pub fn stop(&mut self) {
+        let regs = self.info.regs;
+
+        self.tx_ring.request_pause();
+        self.rx_ring.request_pause();
+        while self.tx_ring.is_running() || self.rx_ring.is_running() {}
+
+        regs.cr1().modify(|w| w.set_spe(false));
+        regs.cr2().modify(|w| {
+            w.set_txdmaen(false);
+            w.set_rxdmaen(false);
+        });
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Read words that are readily available in the RX buffer.
+    /// If no words are currently available the call waits until some words are
+    /// available (at least one word and at most half the buffer size).
+    ///
+    /// The DMA controller only wakes the task at the half and full points of the buffer,
+    /// so a short transaction may not be returned until more data arrives.
+    ///
+    /// Receive in the background is terminated if an error is returned.
+    /// It must then manually be started again by calling `start()` or by re-calling `read()`.
+    pub async fn read(&mut self, buf: &mut [W]) -> Result<usize, Error> {
+        if !self.is_started() {
+            self.start();
+        }
+
+        loop {
+            if let Err(e) = check_error_flags(self.info.regs.sr().read()) {
+                self.stop();
+                return Err(e);
+            }
+
+            match self.rx_ring.read(buf) {
+                Ok((0, _)) => {}
+                Ok((len, _)) => return Ok(len),
+                Err(_) => {
+                    self.stop();
+                    return Err(Error::Overrun);
+                }
+            }
+
+            let mut dma_init = false;
+            poll_fn(|cx| {
+                self.rx_ring.set_waker(cx.waker());
+
+                let status = match dma_init {
+                    false => Poll::Pending,
+                    true => Poll::Ready(()),
+                };
+
+                dma_init = true;
+                status
+            })
+            .await;
+        }
+    }
+
+    /// Queue words to be sent in subsequent host transactions.
+    ///
+    /// Returns the number of words written, which may be less than `data.len()` if the buffer is full.
+    pub fn write(&mut self, data: &[W]) -> Result<usize, Error> {
+        match self.tx_ring.write(data) {
+            Ok((len, _)) => Ok(len),
+            Err(_) => Err(Error::Overrun),
+        }
+    }
+
+    /// Queue all of `data` to be sent, waiting for space in the TX buffer if needed.
+    pub async fn write_exact(&mut self, data: &[W]) -> Result<(), Error> {
+        match self.tx_ring.write_exact(data).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Error::Overrun),
+        }
+    }
+}
+
+impl<'d, W: Word> SetConfig for SpiSlave<'d, W> {
+    type Config = SlaveConfig;
+    type ConfigError = ();
+    fn set_config(&mut self, config: &Self::Config) -> Result<(), ()> {
+        if self.is_started() {
+            return Err(());
+        }
+        self.set_config(config);
+        Ok(())
+    }
+}
+
+impl<'d, W: Word> Drop for SpiSlave<'d, W> {
+    fn drop(&mut self) {
+        self.stop();
+
+        self.sck.as_ref().map(|x| x.set_as_disconnected());
+        self.mosi.as_ref().map(|x| x.set_as_disconnected());
+        self.miso.as_ref().map(|x| x.set_as_disconnected());
+        self.nss.as_ref().map(|x| x.set_as_disconnected());
+
+        self.info.rcc.disable();
+    }
+}
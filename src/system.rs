@@ -0,0 +1,168 @@
+//! Reset cause and chip identification
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::pac::rcc::regs::Csr;
+use crate::pac::{DBGMCU, RCC};
+
+/// `RCC_CSR` as it was when [`init`](crate::init) ran, before the reset flags were cleared.
+static RESET_FLAGS: AtomicU32 = AtomicU32::new(0);
+
+/// Cause of the last reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetCause {
+    /// Power-on or brown-out reset.
+    PowerOn,
+    /// Reset from the NRST pin.
+    Pin,
+    /// Software reset (`SCB::sys_reset`).
+    Software,
+    /// Independent watchdog reset.
+    IndependentWatchdog,
+    /// Window watchdog reset.
+    WindowWatchdog,
+    /// Illegal entry into a low-power mode.
+    LowPower,
+    /// Reload of the option bytes.
+    OptionByteLoad,
+    /// No reset flag was set.
+    Unknown,
+}
+
+/// Device and revision identifiers from `DBGMCU_IDCODE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IdCode {
+    /// Device identifier.
+    pub dev_id: u16,
+    /// Revision identifier.
+    pub rev_id: u16,
+}
+
+/// Save and clear the reset flags, must be called once at startup.
+pub(crate) fn init() {
+    RESET_FLAGS.store(RCC.csr().read().0, Ordering::Relaxed);
+    RCC.csr().modify(|w| w.set_rmvf(true));
+}
+
+fn reset_flags() -> Csr {
+    Csr(RESET_FLAGS.load(Ordering::Relaxed))
+}
+
+/// Returns the cause of the last reset.
+///
+/// The hardware reset flags are cleared by [`init`](crate::init), so the cause of each
+/// reset is reported on its own. The NRST pin is also driven low by internal resets, so
+/// the pin flag is only reported when no other flag is set.
+pub fn reset_cause() -> ResetCause {
+    let csr = reset_flags();
+
+    if csr.pwrrstf() {
+        ResetCause::PowerOn
+    } else if csr.lpwrrstf() {
+        ResetCause::LowPower
+    } else if csr.iwdgrstf() {
+        ResetCause::IndependentWatchdog
+    } else if csr.wwdgrstf() {
+        ResetCause::WindowWatchdog
+    } else if csr.sftrstf() {
+        ResetCause::Software
+    } else if csr.oblrstf() {
+        ResetCause::OptionByteLoad
+    } else if csr.pinrstf() {
+        ResetCause::Pin
+    } else {
+        ResetCause::Unknown
+    }
+}
+
+/// Returns `true` if the flag for `cause` was set at the last reset.
+///
+/// Unlike [`reset_cause`], this does not prioritize between several flags.
+pub fn was_reset_by(cause: ResetCause) -> bool {
+    let csr = reset_flags();
+
+    match cause {
+        ResetCause::PowerOn => csr.pwrrstf(),
+        ResetCause::Pin => csr.pinrstf(),
+        ResetCause::Software => csr.sftrstf(),
+        ResetCause::IndependentWatchdog => csr.iwdgrstf(),
+        ResetCause::WindowWatchdog => csr.wwdgrstf(),
+        ResetCause::LowPower => csr.lpwrrstf(),
+        ResetCause::OptionByteLoad => csr.oblrstf(),
+        ResetCause::Unknown => reset_cause() == ResetCause::Unknown,
+    }
+}
+
+// TODO: move to py32-metapac
+#[cfg(rcc_f002b)]
+const UID_BASE: usize = 0x1FFF_0000;
+#[cfg(not(rcc_f002b))]
+const UID_BASE: usize = 0x1FFF_0E00;
+
+/// Returns the 96-bit unique device ID.
+pub fn uid() -> [u8; 12] {
+    let mut uid = [0u8; 12];
+    for (i, chunk) in uid.chunks_exact_mut(4).enumerate() {
+        let word = unsafe { core::ptr::read_volatile((UID_BASE as *const u32).add(i)) };
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    uid
+}
+
+/// Returns the 96-bit unique device ID as a 24 characters hex string.
+pub fn uid_hex_bytes() -> [u8; 24] {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    let mut hex = [0u8; 24];
+    for (i, byte) in uid().iter().enumerate() {
+        hex[i * 2] = HEX[(byte >> 4) as usize];
+        hex[i * 2 + 1] = HEX[(byte & 0x0F) as usize];
+    }
+    hex
+}
+
+/// Returns the flash size in bytes of the chip selected by the `py32*` feature.
+///
+/// This is a compile-time constant, it is not read from the chip: a chip with less flash than
+/// the selected one is not detected.
+pub const fn configured_flash_size() -> usize {
+    crate::pac::FLASH_SIZE
+}
+
+/// Returns the device and revision identifiers.
+///
+/// The DBGMCU clock is enabled by [`init`](crate::init).
+pub fn idcode() -> IdCode {
+    let idcode = DBGMCU.idcode().read();
+    IdCode {
+        dev_id: idcode.dev_id(),
+        rev_id: idcode.rev_id(),
+    }
+}
+
+/// Reset cause and chip identity, returned by [`info`] and [`init_with_info`](crate::init_with_info).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SystemInfo {
+    /// Cause of the last reset, see [`reset_cause`].
+    pub reset_cause: ResetCause,
+    /// 96-bit unique device ID, see [`uid`].
+    pub uid: [u8; 12],
+    /// Flash size in bytes of the chip selected by the `py32*` feature, see
+    /// [`configured_flash_size`].
+    pub configured_flash_size: usize,
+    /// Device and revision identifiers read from `DBGMCU_IDCODE`.
+    pub idcode: IdCode,
+}
+
+/// Returns the reset cause and chip identity.
+pub fn info() -> SystemInfo {
+    SystemInfo {
+        reset_cause: reset_cause(),
+        uid: uid(),
+        configured_flash_size: configured_flash_size(),
+        idcode: idcode(),
+    }
+}
@@ -396,6 +396,20 @@ impl<'d, T: BasicInstance> Timer<'d, T> {
     pub fn regs_basic(&self) -> crate::pac::timer::TimBasic {
         unsafe { crate::pac::timer::TimBasic::from_ptr(T::regs()) }
     }
+
+    /// Set the master mode, which selects the event output on TRGO.
+    pub fn set_master_mode(&self, mms: vals::Mms) {
+        self.regs_basic().cr2().modify(|r| r.set_mms(mms));
+    }
+
+    /// Output a TRGO pulse on each update event, at `frequency`.
+    ///
+    /// This paces the peripherals triggered by the timer, such as the ADC or the DAC.
+    /// Call [`start`](Self::start) to begin.
+    pub fn set_trigger_output_frequency(&self, frequency: Hertz) {
+        self.set_frequency(frequency);
+        self.set_master_mode(vals::Mms::UPDATE);
+    }
 }
 
 impl<'d, T: GeneralInstance1Channel> Timer<'d, T> {
@@ -0,0 +1,140 @@
+//! Watchdog Timers (IWDG, WWDG)
+
+// The following code is modified from embassy-stm32
+// https://github.com/embassy-rs/embassy/tree/main/embassy-stm32
+// Special thanks to the Embassy Project and its contributors for their work!
+
+use core::marker::PhantomData;
+
+use embassy_hal_internal::{into_ref, Peripheral};
+
+use crate::pac::iwdg::vals::{Key, Pr};
+use crate::rcc::LSI_FREQ;
+use crate::system::ResetCause;
+
+#[cfg(wwdg)]
+mod wwdg;
+#[cfg(wwdg)]
+pub use wwdg::{Error as WindowWatchdogError, InterruptHandler, WindowWatchdog};
+
+/// Independent watchdog (IWDG) driver.
+///
+/// The IWDG is clocked by the LSI, which is started by the hardware when the
+/// watchdog is unleashed. Once started, it can only be stopped by a reset.
+///
+/// The PY32 IWDG has no window register, a refresh that comes too early can be caught with
+/// the window watchdog (WWDG) instead.
+pub struct IndependentWatchdog<'d, T: Instance> {
+    wdg: PhantomData<&'d mut T>,
+}
+
+// 12-bit reload value
+const MAX_RL: u16 = 0xFFF;
+
+/// Calculates maximum watchdog timeout in us (RL = 0xFFF) for a given prescaler
+const fn get_timeout_us(prescaler: u16, reload_value: u16) -> u32 {
+    1_000_000 * (reload_value + 1) as u32 / (LSI_FREQ.0 / prescaler as u32)
+}
+
+/// Calculates watchdog reload value for the given prescaler and desired timeout
+///
+/// Timeouts shorter than one watchdog count give the shortest timeout, RL = 0.
+const fn reload_value(prescaler: u16, timeout_us: u32) -> u16 {
+    ((timeout_us / prescaler as u32 * LSI_FREQ.0 / 1_000_000) as u16).saturating_sub(1)
+}
+
+impl<'d, T: Instance> IndependentWatchdog<'d, T> {
+    /// Creates an IWDG (Independent Watchdog) instance with a given timeout value in microseconds.
+    ///
+    /// Panics if the timeout is longer than the watchdog can count (about 32 s). Timeouts
+    /// shorter than one watchdog count (about 122 µs) are rounded up to it.
+    pub fn new(_instance: impl Peripheral<P = T> + 'd, timeout_us: u32) -> Self {
+        into_ref!(_instance);
+
+        // Find lowest prescaler value, which makes watchdog period longer or equal to timeout.
+        // This iterates from 4 (2^2) to 256 (2^8).
+        let psc_power = unwrap!((2..=8).find(|psc_power| {
+            let psc = 2u16.pow(*psc_power);
+            timeout_us <= get_timeout_us(psc, MAX_RL)
+        }));
+
+        // Prescaler value
+        let psc = 2u16.pow(psc_power);
+
+        // Convert prescaler power to PR register value
+        let pr = psc_power as u8 - 2;
+        assert!(pr <= 0b110);
+
+        // Reload value
+        let rl = reload_value(psc, timeout_us);
+
+        let wdg = T::regs();
+        wdg.kr().write(|w| w.set_key(Key::ENABLE));
+        wdg.pr().write(|w| w.set_pr(Pr::from_bits(pr)));
+        wdg.rlr().write(|w| w.set_rl(rl));
+
+        trace!(
+            "Watchdog configured with {}us timeout, desired was {}us (PR={}, RL={})",
+            get_timeout_us(psc, rl),
+            timeout_us,
+            pr,
+            rl
+        );
+
+        IndependentWatchdog { wdg: PhantomData }
+    }
+
+    /// Unleash (start) the watchdog.
+    pub fn unleash(&mut self) {
+        T::regs().kr().write(|w| w.set_key(Key::START));
+    }
+
+    /// Pet (reload, refresh) the watchdog.
+    pub fn pet(&mut self) {
+        T::regs().kr().write(|w| w.set_key(Key::RESET));
+    }
+
+    /// Returns `true` if the last reset was caused by the IWDG.
+    pub fn caused_last_reset() -> bool {
+        crate::system::was_reset_by(ResetCause::IndependentWatchdog)
+    }
+}
+
+trait SealedInstance {
+    fn regs() -> crate::pac::iwdg::Iwdg;
+}
+
+/// IWDG instance trait.
+#[allow(private_bounds)]
+pub trait Instance: SealedInstance {}
+
+foreach_peripheral!(
+    (iwdg, $inst:ident) => {
+        impl SealedInstance for crate::peripherals::$inst {
+            fn regs() -> crate::pac::iwdg::Iwdg {
+                crate::pac::$inst
+            }
+        }
+
+        impl Instance for crate::peripherals::$inst {}
+    };
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_value_rounds_short_timeouts_up() {
+        // One count is 4 / 32.768 kHz = 122 us with the smallest prescaler.
+        assert_eq!(reload_value(4, 0), 0);
+        assert_eq!(reload_value(4, 100), 0);
+        assert_eq!(reload_value(4, 250), 1);
+        assert_eq!(get_timeout_us(4, reload_value(4, 0)), 122);
+    }
+
+    #[test]
+    fn reload_value_at_max_timeout() {
+        assert_eq!(reload_value(256, get_timeout_us(256, MAX_RL)), MAX_RL);
+    }
+}
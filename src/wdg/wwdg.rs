@@ -0,0 +1,198 @@
+//! Window Watchdog (WWDG)
+
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::Poll;
+
+use critical_section::Mutex;
+use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::interrupt::typelevel::Interrupt;
+use crate::pac::wwdg::vals::Wdgtb;
+use crate::pac::WWDG as WWDG_REGS;
+use crate::peripherals::WWDG;
+use crate::{interrupt, rcc};
+
+/// Value of the counter below which the chip is reset.
+const COUNTER_MIN: u8 = 0x40;
+/// 7-bit down counter
+const COUNTER_MAX: u8 = 0x7F;
+
+static EWI_WAKER: AtomicWaker = AtomicWaker::new();
+static EWI_COUNT: AtomicU32 = AtomicU32::new(0);
+static EWI_CALLBACK: Mutex<Cell<Option<fn()>>> = Mutex::new(Cell::new(None));
+
+/// WWDG early wakeup interrupt handler.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::WWDG> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        if !WWDG_REGS.sr().read().ewif() {
+            return;
+        }
+        WWDG_REGS.sr().write(|w| w.set_ewif(false));
+
+        if let Some(callback) = critical_section::with(|cs| EWI_CALLBACK.borrow(cs).get()) {
+            callback();
+        }
+        // Only written here, no read-modify-write atomics on Cortex-M0+.
+        EWI_COUNT.store(
+            EWI_COUNT.load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Relaxed,
+        );
+        EWI_WAKER.wake();
+    }
+}
+
+/// WWDG error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The watchdog was refreshed before the window opened.
+    ///
+    /// The hardware would have reset the chip, so the refresh was not done.
+    TooEarly,
+}
+
+/// Window watchdog (WWDG) driver.
+///
+/// The WWDG must be refreshed after `min_refresh_us` and before `max_refresh_us`
+/// have elapsed since the previous refresh, otherwise the chip is reset.
+/// It is clocked from PCLK1, so the timing is only valid while the clock configuration is unchanged.
+pub struct WindowWatchdog<'d> {
+    _peri: PeripheralRef<'d, WWDG>,
+    counter: u8,
+    window: u8,
+}
+
+impl<'d> WindowWatchdog<'d> {
+    /// Create a WWDG instance refreshing between `min_refresh_us` and `max_refresh_us`.
+    ///
+    /// The timings are rounded to whole counter ticks: the chip is reset no later than
+    /// `max_refresh_us` after a refresh, and refreshing is allowed no sooner than `min_refresh_us`.
+    /// The counter does not run until [`unleash`](Self::unleash) is called.
+    ///
+    /// Panics if the timings can not be reached with the current PCLK1 frequency.
+    pub fn new(
+        peri: impl Peripheral<P = WWDG> + 'd,
+        _irq: impl interrupt::typelevel::Binding<interrupt::typelevel::WWDG, InterruptHandler> + 'd,
+        min_refresh_us: u32,
+        max_refresh_us: u32,
+    ) -> Self {
+        into_ref!(peri);
+        assert!(min_refresh_us < max_refresh_us);
+
+        rcc::enable_and_reset::<WWDG>();
+        let pclk = rcc::frequency::<WWDG>().0 as u64;
+
+        // Number of counter ticks in `us` microseconds, the counter clock is PCLK1 / 4096 / 2^WDGTB.
+        let ticks = |us: u32, tb: u8| us as u64 * pclk / (4096u64 << tb) / 1_000_000;
+
+        // Use the finest prescaler that can count up to the maximum refresh time.
+        let tb = unwrap!((0..=3u8)
+            .find(|&tb| ticks(max_refresh_us, tb) <= (COUNTER_MAX - COUNTER_MIN + 1) as u64));
+        let max_ticks = ticks(max_refresh_us, tb) as u8;
+        assert!(
+            max_ticks > 0,
+            "WWDG: max refresh time is shorter than a counter tick"
+        );
+
+        // Round the minimum time up, so that refreshing is never allowed too early.
+        let tick_ns = (4096u64 << tb) * 1_000_000_000 / pclk;
+        let min_ticks = ((min_refresh_us as u64 * 1000).div_ceil(tick_ns)) as u8;
+        assert!(min_ticks < max_ticks, "WWDG: refresh window is empty");
+
+        // The chip is reset when the counter goes from 0x40 to 0x3F.
+        let counter = COUNTER_MIN - 1 + max_ticks;
+        // Refreshing is only allowed once the counter is at or below the window value.
+        let window = counter - min_ticks;
+
+        WWDG_REGS.cfr().write(|w| {
+            w.set_wdgtb(Wdgtb::from_bits(tb));
+            w.set_w(window);
+            w.set_ewi(true);
+        });
+
+        trace!(
+            "WWDG configured with window {}..{} ticks of {}ns (WDGTB={})",
+            min_ticks,
+            max_ticks,
+            tick_ns,
+            tb
+        );
+
+        interrupt::typelevel::WWDG::unpend();
+        unsafe { interrupt::typelevel::WWDG::enable() };
+
+        Self {
+            _peri: peri,
+            counter,
+            window,
+        }
+    }
+
+    /// Unleash (start) the watchdog. It can only be stopped by a reset.
+    pub fn unleash(&mut self) {
+        WWDG_REGS.cr().write(|w| {
+            w.set_t(self.counter);
+            w.set_wdga(true);
+        });
+    }
+
+    /// Returns `true` if the refresh window is open.
+    pub fn is_window_open(&self) -> bool {
+        WWDG_REGS.cr().read().t() <= self.window
+    }
+
+    /// Pet (reload, refresh) the watchdog.
+    ///
+    /// Returns [`Error::TooEarly`] without refreshing if the window is not open yet,
+    /// instead of letting the hardware reset the chip.
+    pub fn pet(&mut self) -> Result<(), Error> {
+        if !self.is_window_open() {
+            return Err(Error::TooEarly);
+        }
+
+        WWDG_REGS.cr().write(|w| {
+            w.set_t(self.counter);
+            w.set_wdga(true);
+        });
+        Ok(())
+    }
+
+    /// Returns `true` if the last reset was caused by the WWDG.
+    pub fn caused_last_reset() -> bool {
+        crate::system::was_reset_by(crate::system::ResetCause::WindowWatchdog)
+    }
+
+    /// Set a function called from the early wakeup interrupt.
+    ///
+    /// The interrupt fires one counter tick before the reset, which is enough time for
+    /// last-gasp logging or saving state, but not to recover.
+    pub fn set_early_wakeup_callback(&mut self, callback: Option<fn()>) {
+        critical_section::with(|cs| EWI_CALLBACK.borrow(cs).set(callback));
+    }
+
+    /// Wait for the early wakeup interrupt, one counter tick before the reset.
+    ///
+    /// The task may not be polled in time if the executor is busy,
+    /// use [`set_early_wakeup_callback`](Self::set_early_wakeup_callback) for work that must happen.
+    pub async fn wait_early_wakeup(&mut self) {
+        let start = EWI_COUNT.load(Ordering::Relaxed);
+        poll_fn(|cx| {
+            EWI_WAKER.register(cx.waker());
+
+            // The flag is cleared by the interrupt handler, count the interrupts instead.
+            if EWI_COUNT.load(Ordering::Relaxed) != start {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}